@@ -0,0 +1,31 @@
+# Generated by Django 4.1.5 on 2023-02-24 09:12
+
+import django.db.models.deletion
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+
+    dependencies = [
+        ("osrd_infra", "0009_tile_bbox"),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name="ErrorSetting",
+            fields=[
+                ("id", models.BigAutoField(auto_created=True, primary_key=True, serialize=False, verbose_name="ID")),
+                ("error_type", models.CharField(max_length=64)),
+                ("enabled", models.BooleanField(default=True)),
+                ("is_warning", models.BooleanField(null=True)),
+                (
+                    "infra",
+                    models.ForeignKey(on_delete=django.db.models.deletion.CASCADE, to="osrd_infra.infra"),
+                ),
+            ],
+            options={
+                "verbose_name_plural": "error settings",
+                "unique_together": {("infra", "error_type")},
+            },
+        ),
+    ]
@@ -145,6 +145,19 @@ class DetectorModel(models.Model):
         unique_together = (("infra", "obj_id"),)
 
 
+class ErrorSetting(models.Model):
+    """Validation settings of an infra error type (disabled rule or severity override)"""
+
+    infra = models.ForeignKey(Infra, on_delete=models.CASCADE)
+    error_type = models.CharField(max_length=64)
+    enabled = models.BooleanField(default=True)
+    is_warning = models.BooleanField(null=True)
+
+    class Meta:
+        verbose_name_plural = "error settings"
+        unique_together = (("infra", "error_type"),)
+
+
 def _into_model(obj, infra=None):
     obj_type = type(obj)
     if obj_type not in OBJ_TO_MODEL:
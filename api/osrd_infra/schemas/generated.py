@@ -73,6 +73,47 @@ class OverlappingSwitches(InfraErrorTrait):
     reference: ObjectReference
 
 
+class DegenerateGeometry(InfraErrorTrait):
+    error_type: Literal["degenerate_geometry"] = Field(default="degenerate_geometry")
+
+
+class OverlappingRanges(InfraErrorTrait):
+    error_type: Literal["overlapping_ranges"] = Field(default="overlapping_ranges")
+    overlapping_field: str
+
+
+class OverlappingSignals(InfraErrorTrait):
+    error_type: Literal["overlapping_signals"] = Field(default="overlapping_signals")
+    reference: ObjectReference
+
+
+class UnknownSignalingSystem(InfraErrorTrait):
+    error_type: Literal["unknown_signaling_system"] = Field(default="unknown_signaling_system")
+    signaling_system: str
+
+
+class InvalidSpeedLimit(InfraErrorTrait):
+    error_type: Literal["invalid_speed_limit"] = Field(default="invalid_speed_limit")
+    speed_limit: float
+    expected_range: Tuple[float, float]
+
+
+class OverlappingSpeedSections(InfraErrorTrait):
+    error_type: Literal["overlapping_speed_sections"] = Field(default="overlapping_speed_sections")
+    reference: ObjectReference
+
+
+class DanglingSwitchPort(InfraErrorTrait):
+    error_type: Literal["dangling_switch_port"] = Field(default="dangling_switch_port")
+    port_name: str
+
+
+class CloseDetectors(InfraErrorTrait):
+    error_type: Literal["close_detectors"] = Field(default="close_detectors")
+    reference: ObjectReference
+    distance: float
+
+
 # Warnings
 class EmptyObject(InfraWarningTrait):
     error_type: Literal["empty_object"] = Field(default="empty_object")
@@ -101,21 +142,84 @@ class OverlappingTrackLinks(InfraWarningTrait):
     reference: ObjectReference
 
 
+class InconsistentLength(InfraWarningTrait):
+    error_type: Literal["inconsistent_length"] = Field(default="inconsistent_length")
+    geo_length: float
+
+
+class SelfIntersectingGeometry(InfraWarningTrait):
+    error_type: Literal["self_intersecting_geometry"] = Field(default="self_intersecting_geometry")
+    point: Tuple[float, float]
+
+
+class LinkedDetectorTooFar(InfraWarningTrait):
+    error_type: Literal["linked_detector_too_far"] = Field(default="linked_detector_too_far")
+    reference: ObjectReference
+    max_distance: float
+
+
+class NoRouteEntry(InfraWarningTrait):
+    error_type: Literal["no_route_entry"] = Field(default="no_route_entry")
+    reference: ObjectReference
+
+
+class MissingSpeedLimit(InfraWarningTrait):
+    error_type: Literal["missing_speed_limit"] = Field(default="missing_speed_limit")
+
+
+class OutOfSpeedSection(InfraWarningTrait):
+    error_type: Literal["out_of_speed_section"] = Field(default="out_of_speed_section")
+
+
+class MisplacedSwitchPort(InfraWarningTrait):
+    error_type: Literal["misplaced_switch_port"] = Field(default="misplaced_switch_port")
+    port_name: str
+    distance: float
+    max_distance: float
+
+
+class DetectorAtTrackEnd(InfraWarningTrait):
+    error_type: Literal["detector_at_track_end"] = Field(default="detector_at_track_end")
+
+
+class LongDetectionSection(InfraWarningTrait):
+    error_type: Literal["long_detection_section"] = Field(default="long_detection_section")
+    length: float
+    max_length: float
+
+
 InfraError = Annotated[
     Union[
+        CloseDetectors,
+        DanglingSwitchPort,
+        DegenerateGeometry,
+        DetectorAtTrackEnd,
         DuplicatedGroup,
         EmptyObject,
+        InconsistentLength,
         InvalidGroup,
         InvalidReference,
         InvalidRoute,
+        InvalidSpeedLimit,
         InvalidSwitchPorts,
+        LinkedDetectorTooFar,
+        LongDetectionSection,
+        MisplacedSwitchPort,
         MissingRoute,
+        MissingSpeedLimit,
         NoBufferStop,
+        NoRouteEntry,
         ObjectOutOfPath,
         OutOfRange,
+        OutOfSpeedSection,
+        OverlappingRanges,
+        OverlappingSignals,
+        OverlappingSpeedSections,
         OverlappingSwitches,
         OverlappingTrackLinks,
+        SelfIntersectingGeometry,
         UnknownPortName,
+        UnknownSignalingSystem,
         UnusedPort,
     ],
     Field(discriminator="error_type"),
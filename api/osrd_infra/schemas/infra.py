@@ -505,12 +505,22 @@ def register_extension(object, name):
     return register_extension
 
 
+class KilometrePointAnchor(BaseModel):
+    offset: float = Field(description="Offset on the track section in meters")
+    kilometre_point: constr(regex=r"^-?\d+\+\d+(\.\d+)?$") = Field(
+        description="Kilometre point of the line located at the offset, written '<km>+<m>' (ex: '12+345')"
+    )
+
+
 @register_extension(object=TrackSection, name="sncf")
 class TrackSectionSncfExtension(BaseModel):
     line_code: int = Field(description="Code of the line used by the corresponding track section")
     line_name: NonBlankStr = Field(description="Name of the line used by the corresponding track section")
     track_number: int = Field(description="Number corresponding to the track used", ge=0)
     track_name: NonBlankStr = Field(description="Name corresponding to the track used")
+    kilometre_points: List[KilometrePointAnchor] = Field(
+        default_factory=list, description="Kilometre points of the line located along the track section"
+    )
 
 
 @register_extension(object=OperationalPoint, name="sncf")
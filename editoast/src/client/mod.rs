@@ -1,11 +1,16 @@
 mod postgres_config;
 mod redis_config;
+mod tile_cache_config;
+mod validation_config;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use derivative::Derivative;
 pub use postgres_config::PostgresConfig;
 pub use redis_config::RedisConfig;
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
+pub use tile_cache_config::{TileCacheBackend, TileCacheConfig};
+pub use validation_config::ValidationConfig;
 
 #[derive(Parser, Debug)]
 #[clap(author, version)]
@@ -14,6 +19,10 @@ pub struct Client {
     pub postgres_config: PostgresConfig,
     #[clap(flatten)]
     pub redis_config: RedisConfig,
+    #[clap(flatten)]
+    pub tile_cache_config: TileCacheConfig,
+    #[clap(flatten)]
+    pub validation_config: ValidationConfig,
     #[clap(subcommand)]
     pub command: Commands,
 }
@@ -24,6 +33,10 @@ pub enum Commands {
     Generate(GenerateArgs),
     Clear(ClearArgs),
     ImportRailjson(ImportRailjsonArgs),
+    MigrateRailjson(MigrateRailjsonArgs),
+    GenerateSchematic(GenerateSchematicArgs),
+    SeedTiles(SeedTilesArgs),
+    ExportTiles(ExportTilesArgs),
 }
 
 #[derive(Args, Debug, Derivative, Clone)]
@@ -32,13 +45,30 @@ pub struct MapLayersConfig {
     #[derivative(Default(value = "18"))]
     #[clap(long, env, default_value_t = 18)]
     pub max_zoom: u64,
-    /// Number maximum of tiles before we consider invalidating full Redis cache is required
+    /// Maximum number of tiles of an invalidated zone for which cache keys are enumerated,
+    /// the cached tiles are looked up for larger zones
     #[derivative(Default(value = "250_000"))]
     #[clap(long, env, default_value_t = 250_000)]
     pub max_tiles: u64,
     #[derivative(Default(value = r#""http://localhost:8090".into()"#))]
     #[clap(long, env, default_value_t = String::from("http://localhost:8090"))]
     pub root_url: String,
+    /// Maximum zoom of the tiles seeded in background after an infra refresh, no seeding if not set
+    #[clap(long, env)]
+    pub seed_max_zoom: Option<u64>,
+}
+
+#[derive(Args, Debug, Derivative, Clone)]
+#[derivative(Default)]
+pub struct RailjsonConfig {
+    /// Maximum size in bytes of an imported railjson
+    #[derivative(Default(value = "250 * 1024 * 1024"))]
+    #[clap(long, env, default_value_t = 250 * 1024 * 1024)]
+    pub railjson_max_size: u64,
+    /// Maximum size in bytes of an imported objects csv
+    #[derivative(Default(value = "50 * 1024 * 1024"))]
+    #[clap(long, env, default_value_t = 50 * 1024 * 1024)]
+    pub csv_max_size: u64,
 }
 
 #[derive(Args, Debug, Derivative)]
@@ -53,6 +83,8 @@ pub struct RunserverArgs {
     pub address: String,
     #[clap(flatten)]
     pub map_layers_config: MapLayersConfig,
+    #[clap(flatten)]
+    pub railjson_config: RailjsonConfig,
 }
 
 #[derive(Args, Debug)]
@@ -83,3 +115,91 @@ pub struct ImportRailjsonArgs {
     #[clap(short = 'g', long)]
     pub generate: bool,
 }
+
+#[derive(Args, Debug)]
+#[clap(
+    about,
+    long_about = "Upgrade a railjson file to the current version of the format"
+)]
+pub struct MigrateRailjsonArgs {
+    /// Railjson file path, the file is upgraded in place
+    pub railjson_path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+#[clap(
+    about,
+    long_about = "Compute the schematic geometry of infra tracks from their topology"
+)]
+pub struct GenerateSchematicArgs {
+    /// List of infra ids
+    #[clap(required = true)]
+    pub infra_ids: Vec<u64>,
+}
+
+#[derive(Args, Debug)]
+#[clap(
+    about,
+    long_about = "Render the map tiles covering the infras extent and store them in the cache"
+)]
+pub struct SeedTilesArgs {
+    /// List of infra ids (all infras if not given)
+    pub infra_ids: Vec<u64>,
+    /// Layers to seed (all layers if not given)
+    #[clap(short, long)]
+    pub layers: Vec<String>,
+    #[clap(long, default_value_t = 0)]
+    pub min_zoom: u64,
+    #[clap(long, default_value_t = 12)]
+    pub max_zoom: u64,
+    /// Number of tiles rendered concurrently
+    #[clap(short, long, default_value_t = 8)]
+    pub concurrency: usize,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum TilesArchiveFormat {
+    Mbtiles,
+    Pmtiles,
+}
+
+#[derive(Args, Debug)]
+#[clap(
+    about,
+    long_about = "Render all the map layers of an infra and write them in a single tiles archive"
+)]
+pub struct ExportTilesArgs {
+    /// Infra id
+    #[clap(long)]
+    pub infra: u64,
+    #[clap(long, value_enum, default_value_t = TilesArchiveFormat::Mbtiles)]
+    pub format: TilesArchiveFormat,
+    /// Range of rendered zoom levels, for example `0-16`
+    #[clap(long, default_value = "0-16", value_parser = parse_zoom_range)]
+    pub zoom: RangeInclusive<u64>,
+    /// Archive file path (`infra_<id>.<format>` if not given)
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+    /// Number of tiles rendered concurrently
+    #[clap(short, long, default_value_t = 8)]
+    pub concurrency: usize,
+}
+
+/// Parses a zoom range formatted as `min-max`
+fn parse_zoom_range(value: &str) -> Result<RangeInclusive<u64>, String> {
+    let (min, max) = value
+        .split_once('-')
+        .ok_or_else(|| format!("Invalid zoom range '{value}', expected 'min-max'"))?;
+    let min: u64 = min
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid min zoom '{min}'"))?;
+    let max: u64 = max
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid max zoom '{max}'"))?;
+    if min > max || max > 30 {
+        return Err(format!("Invalid zoom range '{value}'"));
+    }
+    Ok(min..=max)
+}
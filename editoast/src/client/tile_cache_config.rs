@@ -0,0 +1,23 @@
+use clap::{Args, ValueEnum};
+use derivative::Derivative;
+
+/// Storage of the rendered map tiles
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileCacheBackend {
+    /// Tiles are shared by all editoast instances using the Redis server
+    Redis,
+    /// Tiles are kept in an in-process LRU cache
+    Memory,
+}
+
+#[derive(Args, Debug, Derivative, Clone)]
+#[derivative(Default)]
+pub struct TileCacheConfig {
+    #[derivative(Default(value = "TileCacheBackend::Redis"))]
+    #[clap(long, env, value_enum, default_value_t = TileCacheBackend::Redis)]
+    pub tile_cache: TileCacheBackend,
+    /// Maximum number of tiles kept by the memory tile cache
+    #[derivative(Default(value = "100_000"))]
+    #[clap(long, env, default_value_t = 100_000)]
+    pub tile_cache_size: usize,
+}
@@ -0,0 +1,36 @@
+use clap::Args;
+use derivative::Derivative;
+
+/// Thresholds used by the infra error generation
+#[derive(Args, Debug, Derivative, Clone)]
+#[derivative(Default)]
+pub struct ValidationConfig {
+    /// Relative tolerance between the length of a track section and the length of its geometry (ex: `0.1` for 10%)
+    #[derivative(Default(value = "0.05"))]
+    #[clap(long, env, default_value_t = 0.05)]
+    pub track_length_tolerance: f64,
+    /// Maximum speed limit of the speed sections in m/s
+    #[derivative(Default(value = "100."))]
+    #[clap(long, env, default_value_t = 100.)]
+    pub max_speed_limit: f64,
+    /// Maximum length of a detection section in meters
+    #[derivative(Default(value = "10_000."))]
+    #[clap(long, env, default_value_t = 10_000.)]
+    pub detection_section_max_length: f64,
+    /// Minimum distance between two detectors of a same track in meters
+    #[derivative(Default(value = "1."))]
+    #[clap(long, env, default_value_t = 1.)]
+    pub detectors_min_distance: f64,
+    /// Distance in meters to a track end under which a detector is considered located on it
+    #[derivative(Default(value = "0.01"))]
+    #[clap(long, env, default_value_t = 0.01)]
+    pub detector_track_end_tolerance: f64,
+    /// Maximum distance in meters between the geometries of the ports of a switch
+    #[derivative(Default(value = "5."))]
+    #[clap(long, env, default_value_t = 5.)]
+    pub switch_port_max_distance: f64,
+    /// Maximum distance in meters along the tracks between a signal and its linked detector
+    #[derivative(Default(value = "50."))]
+    #[clap(long, env, default_value_t = 50.)]
+    pub linked_detector_max_distance: f64,
+}
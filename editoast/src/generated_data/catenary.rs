@@ -1,5 +1,6 @@
 use super::utils::InvolvedObjects;
 use super::GeneratedData;
+use crate::client::ValidationConfig;
 use crate::diesel::ExpressionMethods;
 use crate::error::Result;
 use crate::infra_cache::InfraCache;
@@ -18,7 +19,12 @@ impl GeneratedData for CatenaryLayer {
         "osrd_infra_catenarylayer"
     }
 
-    fn generate(conn: &mut PgConnection, infra: i64, _infra_cache: &InfraCache) -> Result<()> {
+    fn generate(
+        conn: &mut PgConnection,
+        infra: i64,
+        _infra_cache: &InfraCache,
+        _config: &ValidationConfig,
+    ) -> Result<()> {
         sql_query(include_str!("sql/generate_catenary_layer.sql"))
             .bind::<BigInt, _>(infra)
             .execute(conn)?;
@@ -30,6 +36,7 @@ impl GeneratedData for CatenaryLayer {
         infra: i64,
         operations: &[crate::schema::operation::OperationResult],
         infra_cache: &crate::infra_cache::InfraCache,
+        _config: &ValidationConfig,
     ) -> Result<()> {
         let involved_objects =
             InvolvedObjects::from_operations(operations, infra_cache, ObjectType::Catenary);
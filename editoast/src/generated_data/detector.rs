@@ -6,6 +6,7 @@ use std::iter::Iterator;
 
 use super::utils::InvolvedObjects;
 use super::GeneratedData;
+use crate::client::ValidationConfig;
 use crate::diesel::ExpressionMethods;
 use crate::error::Result;
 use crate::infra_cache::InfraCache;
@@ -19,7 +20,12 @@ impl GeneratedData for DetectorLayer {
         "osrd_infra_detectorlayer"
     }
 
-    fn generate(conn: &mut PgConnection, infra: i64, _infra_cache: &InfraCache) -> Result<()> {
+    fn generate(
+        conn: &mut PgConnection,
+        infra: i64,
+        _infra_cache: &InfraCache,
+        _config: &ValidationConfig,
+    ) -> Result<()> {
         sql_query(include_str!("sql/generate_detector_layer.sql"))
             .bind::<BigInt, _>(infra)
             .execute(conn)?;
@@ -31,6 +37,7 @@ impl GeneratedData for DetectorLayer {
         infra: i64,
         operations: &[crate::schema::operation::OperationResult],
         infra_cache: &crate::infra_cache::InfraCache,
+        _config: &ValidationConfig,
     ) -> Result<()> {
         let involved_objects =
             InvolvedObjects::from_operations(operations, infra_cache, ObjectType::Detector);
@@ -1,13 +1,36 @@
-use super::NoContext;
+use super::{GeneratorContext, GlobalErrorGenerator};
+use crate::client::ValidationConfig;
 use crate::generated_data::error::ObjectErrorGenerator;
-use crate::infra_cache::Graph;
+use crate::infra_cache::{compute_detection_sections, Graph};
 use crate::infra_cache::{InfraCache, ObjectCache};
 use crate::schema::{InfraError, ObjectRef, ObjectType};
 
-pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 2] = [
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<Context>; 4] = [
     ObjectErrorGenerator::new(1, check_invalid_ref),
     ObjectErrorGenerator::new(2, check_out_of_range),
+    ObjectErrorGenerator::new_ctx(3, check_track_end),
+    ObjectErrorGenerator::new_ctx(3, check_close_detectors),
 ];
+pub const GLOBAL_GENERATORS: [GlobalErrorGenerator<Context>; 1] =
+    [GlobalErrorGenerator::new_ctx(check_detection_sections)];
+
+/// Context for the detector error generators
+#[derive(Debug)]
+pub struct Context {
+    detection_section_max_length: f64,
+    detectors_min_distance: f64,
+    track_end_tolerance: f64,
+}
+
+impl GeneratorContext for Context {
+    fn new(config: &ValidationConfig) -> Self {
+        Self {
+            detection_section_max_length: config.detection_section_max_length,
+            detectors_min_distance: config.detectors_min_distance,
+            track_end_tolerance: config.detector_track_end_tolerance,
+        }
+    }
+}
 
 /// Retrieve invalide ref error for detectors
 pub fn check_invalid_ref(
@@ -49,6 +72,94 @@ pub fn check_out_of_range(
         vec![]
     }
 }
+
+/// Check that detectors aren't located on a track end, where the track they delimit is ambiguous
+fn check_track_end(
+    detector: &ObjectCache,
+    infra_cache: &InfraCache,
+    _: &Graph,
+    context: Context,
+) -> (Vec<InfraError>, Context) {
+    let detector = detector.unwrap_detector();
+    let track_cache = infra_cache
+        .track_sections()
+        .get(&detector.track)
+        .unwrap()
+        .unwrap_track_section();
+    let tolerance = context.track_end_tolerance;
+    if detector.position < tolerance || detector.position > track_cache.length - tolerance {
+        (
+            vec![InfraError::new_detector_at_track_end(detector)],
+            context,
+        )
+    } else {
+        (vec![], context)
+    }
+}
+
+/// Check that detectors of a same track aren't too close, creating degenerate detection sections
+pub fn check_close_detectors(
+    detector: &ObjectCache,
+    infra_cache: &InfraCache,
+    _: &Graph,
+    context: Context,
+) -> (Vec<InfraError>, Context) {
+    let detector = detector.unwrap_detector();
+    let track_refs = infra_cache
+        .track_sections_refs
+        .get(&detector.track)
+        .unwrap();
+    // Errors are reported on the detector with the greatest id of each pair
+    let mut close_detectors: Vec<_> = track_refs
+        .iter()
+        .filter(|obj_ref| obj_ref.obj_type == ObjectType::Detector)
+        .filter(|obj_ref| obj_ref.obj_id < detector.obj_id)
+        .filter_map(|obj_ref| {
+            let other = infra_cache.detectors().get(&obj_ref.obj_id)?;
+            let distance = (other.unwrap_detector().position - detector.position).abs();
+            (distance < context.detectors_min_distance)
+                .then_some((obj_ref.obj_id.clone(), distance))
+        })
+        .collect();
+    close_detectors.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let errors = close_detectors
+        .into_iter()
+        .map(|(other, distance)| InfraError::new_close_detectors(detector, other, distance))
+        .collect();
+    (errors, context)
+}
+
+/// Check that the infra is divided into detection sections no longer than a threshold
+pub fn check_detection_sections(
+    infra_cache: &InfraCache,
+    graph: &Graph,
+    context: Context,
+) -> (Vec<InfraError>, Context) {
+    let errors = compute_detection_sections(infra_cache, graph)
+        .into_iter()
+        .filter(|section| section.length() > context.detection_section_max_length)
+        .map(|section| {
+            // The error is reported on the longest track range of the section
+            let track_range = section
+                .track_ranges
+                .iter()
+                .max_by(|a, b| (a.end - a.begin).total_cmp(&(b.end - b.begin)))
+                .unwrap();
+            let track = infra_cache
+                .track_sections()
+                .get::<String>(&track_range.track)
+                .unwrap()
+                .unwrap_track_section();
+            InfraError::new_long_detection_section(
+                track,
+                section.length(),
+                context.detection_section_max_length,
+            )
+        })
+        .collect();
+    (errors, context)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::infra_cache::tests::{create_detector_cache, create_small_infra_cache};
@@ -57,6 +168,10 @@ mod tests {
     use super::check_invalid_ref;
     use super::check_out_of_range;
     use super::InfraError;
+    use super::{
+        check_close_detectors, check_detection_sections, check_track_end, Context, GeneratorContext,
+    };
+    use crate::infra_cache::tests::create_track_section_cache;
     use crate::infra_cache::Graph;
 
     #[test]
@@ -89,4 +204,50 @@ mod tests {
         let infra_error = InfraError::new_out_of_range(&detector, "position", 530., [0.0, 500.]);
         assert_eq!(infra_error, errors[0]);
     }
+
+    #[test]
+    fn detector_at_track_end() {
+        let mut infra_cache = create_small_infra_cache();
+        let detector = create_detector_cache("D_error", "A", 500.);
+        infra_cache.add(detector.clone());
+        let (errors, _) = check_track_end(
+            &detector.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            Context::new(&Default::default()),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_detector_at_track_end(&detector);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn close_detectors() {
+        let mut infra_cache = create_small_infra_cache();
+        let detector = create_detector_cache("D2", "B", 250.5);
+        infra_cache.add(detector.clone());
+        let (errors, _) = check_close_detectors(
+            &detector.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            Context::new(&Default::default()),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_close_detectors(&detector, "D1", 0.5);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn long_detection_section() {
+        let mut infra_cache = create_small_infra_cache();
+        let track = create_track_section_cache("E", 20_000.);
+        infra_cache.add(track.clone());
+        let context = Context::new(&Default::default());
+        let max_length = context.detection_section_max_length;
+        let (errors, _) =
+            check_detection_sections(&infra_cache, &Graph::load(&infra_cache), context);
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_long_detection_section(&track, 20_000., max_length);
+        assert_eq!(infra_error, errors[0]);
+    }
 }
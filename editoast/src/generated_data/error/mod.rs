@@ -2,11 +2,15 @@ pub mod buffer_stops;
 pub mod detectors;
 pub mod operational_points;
 pub mod routes;
+mod settings;
 pub mod signals;
 pub mod speed_sections;
 pub mod switch_types;
 pub mod switches;
 pub mod track_section_links;
+pub mod track_sections;
+
+pub use settings::{ErrorSetting, ErrorSettings};
 
 use std::collections::HashMap;
 
@@ -15,6 +19,7 @@ use diesel::{sql_query, PgConnection, RunQueryDsl};
 use serde_json::to_value;
 
 use super::GeneratedData;
+use crate::client::ValidationConfig;
 use crate::error::Result;
 use crate::infra_cache::Graph;
 use crate::infra_cache::{InfraCache, ObjectCache};
@@ -24,6 +29,17 @@ use crate::schema::{InfraError, OSRDObject, ObjectType};
 #[derive(Debug, Default)]
 pub struct NoContext;
 
+/// Context shared by the error generators of an object type, built at the start of the generation
+pub trait GeneratorContext {
+    fn new(config: &ValidationConfig) -> Self;
+}
+
+impl<Ctx: Default> GeneratorContext for Ctx {
+    fn new(_: &ValidationConfig) -> Self {
+        Self::default()
+    }
+}
+
 type ObjectErrorGenerators<Ctx> = [ObjectErrorGenerator<Ctx>];
 type GlobalErrorGenerators<Ctx> = [GlobalErrorGenerator<Ctx>];
 
@@ -31,7 +47,7 @@ type FnErrorGeneratorNoContext = fn(&ObjectCache, &InfraCache, &Graph) -> Vec<In
 type FnErrorGeneratorContext<Ctx> =
     fn(&ObjectCache, &InfraCache, &Graph, Ctx) -> (Vec<InfraError>, Ctx);
 
-pub enum ObjectErrorGenerator<Ctx: Default> {
+pub enum ObjectErrorGenerator<Ctx> {
     NoContext {
         priority: u32,
         check_function: FnErrorGeneratorNoContext,
@@ -42,7 +58,7 @@ pub enum ObjectErrorGenerator<Ctx: Default> {
     },
 }
 
-impl<Ctx: Default> ObjectErrorGenerator<Ctx> {
+impl<Ctx> ObjectErrorGenerator<Ctx> {
     pub const fn new(priority: u32, check_function: FnErrorGeneratorNoContext) -> Self {
         ObjectErrorGenerator::NoContext {
             priority,
@@ -86,15 +102,18 @@ impl<Ctx> GlobalErrorGenerator<Ctx> {
 /// Generate errors given static object and global error generators.
 /// This function assume that object error generators list isn't empty and sorted by priority.
 /// Global errors are generated at the end.
-fn generate_errors<Ctx: Default>(
+/// The validation settings of the infra are applied to the generated errors.
+fn generate_errors<Ctx: GeneratorContext>(
     object_type: ObjectType,
     infra_cache: &InfraCache,
     graph: &Graph,
+    config: &ValidationConfig,
+    settings: &ErrorSettings,
     object_err_generators: &'static ObjectErrorGenerators<Ctx>,
     global_err_generators: &'static GlobalErrorGenerators<Ctx>,
 ) -> Vec<InfraError> {
     let mut errors = Vec::new();
-    let mut context = Ctx::default();
+    let mut context = Ctx::new(config);
 
     // Generate object errors
     for el in infra_cache.get_objects_by_type(object_type).values() {
@@ -116,9 +135,10 @@ fn generate_errors<Ctx: Default>(
                 }
             };
             // Update found error
+            // Disabled errors still stop the next priorities, which rely on the checked data
             found_error |= !new_errors.is_empty();
             // Add errors to the list
-            errors.extend(new_errors);
+            errors.extend(settings.apply(new_errors));
             // Update priority
             current_priority = f.get_priority();
         }
@@ -135,7 +155,7 @@ fn generate_errors<Ctx: Default>(
             }
         };
         // Add errors to the list
-        errors.extend(new_errors);
+        errors.extend(settings.apply(new_errors));
     }
     errors
 }
@@ -183,22 +203,41 @@ impl GeneratedData for ErrorLayer {
         "osrd_infra_errorlayer"
     }
 
-    fn generate(conn: &mut PgConnection, infra_id: i64, infra_cache: &InfraCache) -> Result<()> {
+    fn generate(
+        conn: &mut PgConnection,
+        infra_id: i64,
+        infra_cache: &InfraCache,
+        config: &ValidationConfig,
+    ) -> Result<()> {
         // Create a graph for topological errors
         let graph = Graph::load(infra_cache);
+        let settings = ErrorSettings::load(conn, infra_id)?;
 
         // Generate the errors
         let mut infra_errors = generate_errors(
+            ObjectType::TrackSection,
+            infra_cache,
+            &graph,
+            config,
+            &settings,
+            &track_sections::OBJECT_GENERATORS,
+            &[],
+        );
+        infra_errors.extend(generate_errors(
             ObjectType::Signal,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &signals::OBJECT_GENERATORS,
             &[],
-        );
+        ));
         infra_errors.extend(generate_errors(
             ObjectType::SpeedSection,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &speed_sections::OBJECT_GENERATORS,
             &[],
         ));
@@ -207,6 +246,8 @@ impl GeneratedData for ErrorLayer {
             ObjectType::SwitchType,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &switch_types::OBJECT_GENERATORS,
             &[],
         ));
@@ -215,13 +256,17 @@ impl GeneratedData for ErrorLayer {
             ObjectType::Detector,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &detectors::OBJECT_GENERATORS,
-            &[],
+            &detectors::GLOBAL_GENERATORS,
         ));
         infra_errors.extend(generate_errors(
             ObjectType::BufferStop,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &buffer_stops::OBJECT_GENERATORS,
             &buffer_stops::GLOBAL_GENERATORS,
         ));
@@ -230,6 +275,8 @@ impl GeneratedData for ErrorLayer {
             ObjectType::OperationalPoint,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &operational_points::OBJECT_GENERATORS,
             &[],
         ));
@@ -238,6 +285,8 @@ impl GeneratedData for ErrorLayer {
             ObjectType::Route,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &routes::OBJECT_GENERATORS,
             &routes::GLOBAL_GENERATORS,
         ));
@@ -246,6 +295,8 @@ impl GeneratedData for ErrorLayer {
             ObjectType::TrackSectionLink,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &track_section_links::OBJECT_GENERATORS,
             &track_section_links::GLOBAL_GENERATORS,
         ));
@@ -254,6 +305,8 @@ impl GeneratedData for ErrorLayer {
             ObjectType::Switch,
             infra_cache,
             &graph,
+            config,
+            &settings,
             &switches::OBJECT_GENERATORS,
             &[],
         ));
@@ -269,9 +322,10 @@ impl GeneratedData for ErrorLayer {
         infra: i64,
         _operations: &[crate::schema::operation::OperationResult],
         infra_cache: &InfraCache,
+        config: &ValidationConfig,
     ) -> Result<()> {
         // Clear the whole layer and regenerate it
-        Self::refresh(conn, infra, infra_cache)
+        Self::refresh(conn, infra, infra_cache, config)
     }
 }
 
@@ -279,7 +333,8 @@ impl GeneratedData for ErrorLayer {
 mod test {
     use super::{
         buffer_stops, detectors, generate_errors, operational_points, routes, signals,
-        speed_sections, switch_types, switches, track_section_links, Graph,
+        speed_sections, switch_types, switches, track_section_links, track_sections, ErrorSetting,
+        ErrorSettings, Graph,
     };
 
     use crate::infra_cache::tests::{create_buffer_stop_cache, create_small_infra_cache};
@@ -293,10 +348,22 @@ mod test {
         let graph = Graph::load(&small_infra_cache);
 
         // Generate the errors
+        assert!(generate_errors(
+            ObjectType::TrackSection,
+            &small_infra_cache,
+            &graph,
+            &Default::default(),
+            &Default::default(),
+            &track_sections::OBJECT_GENERATORS,
+            &[],
+        )
+        .is_empty());
         assert!(generate_errors(
             ObjectType::Signal,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &signals::OBJECT_GENERATORS,
             &[],
         )
@@ -305,6 +372,8 @@ mod test {
             ObjectType::SpeedSection,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &speed_sections::OBJECT_GENERATORS,
             &[],
         )
@@ -313,6 +382,8 @@ mod test {
             ObjectType::SwitchType,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &switch_types::OBJECT_GENERATORS,
             &[],
         )
@@ -321,14 +392,18 @@ mod test {
             ObjectType::Detector,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &detectors::OBJECT_GENERATORS,
-            &[],
+            &detectors::GLOBAL_GENERATORS,
         )
         .is_empty());
         assert!(generate_errors(
             ObjectType::BufferStop,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &buffer_stops::OBJECT_GENERATORS,
             &buffer_stops::GLOBAL_GENERATORS,
         )
@@ -337,6 +412,8 @@ mod test {
             ObjectType::Route,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &routes::OBJECT_GENERATORS,
             &routes::GLOBAL_GENERATORS,
         )
@@ -345,6 +422,8 @@ mod test {
             ObjectType::OperationalPoint,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &operational_points::OBJECT_GENERATORS,
             &[],
         )
@@ -353,6 +432,8 @@ mod test {
             ObjectType::TrackSectionLink,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &[],
             &track_section_links::GLOBAL_GENERATORS,
         )
@@ -361,6 +442,8 @@ mod test {
             ObjectType::Switch,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &switches::OBJECT_GENERATORS,
             &[],
         )
@@ -378,9 +461,37 @@ mod test {
             ObjectType::BufferStop,
             &small_infra_cache,
             &graph,
+            &Default::default(),
+            &Default::default(),
             &buffer_stops::OBJECT_GENERATORS,
             &[],
         );
         assert_eq!(1, errors.len());
     }
+
+    #[test]
+    fn disabled_error_priority_check() {
+        let mut small_infra_cache = create_small_infra_cache();
+        let bf = create_buffer_stop_cache("BF_error", "E", 530.0);
+        small_infra_cache.add(bf);
+        let settings: ErrorSettings = [ErrorSetting {
+            error_type: "invalid_reference".into(),
+            enabled: false,
+            is_warning: None,
+        }]
+        .into_iter()
+        .collect();
+
+        let graph = Graph::load(&small_infra_cache);
+        let errors = generate_errors(
+            ObjectType::BufferStop,
+            &small_infra_cache,
+            &graph,
+            &Default::default(),
+            &settings,
+            &buffer_stops::OBJECT_GENERATORS,
+            &[],
+        );
+        assert!(errors.is_empty());
+    }
 }
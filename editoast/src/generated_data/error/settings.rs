@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel::{delete, insert_into, PgConnection};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::schema::InfraError;
+use crate::tables::osrd_infra_errorsetting;
+use crate::tables::osrd_infra_errorsetting::dsl;
+
+/// Validation setting of an infra error type.
+/// Error types without setting are enabled and keep their default severity.
+#[derive(Debug, Clone, PartialEq, Eq, Queryable, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ErrorSetting {
+    pub error_type: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Override the severity of the error type, `None` keeps the default one
+    #[serde(default)]
+    pub is_warning: Option<bool>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = osrd_infra_errorsetting)]
+struct NewErrorSetting<'a> {
+    infra_id: i64,
+    error_type: &'a str,
+    enabled: bool,
+    is_warning: Option<bool>,
+}
+
+/// Validation settings of an infra indexed by error type
+#[derive(Debug, Default, Clone)]
+pub struct ErrorSettings {
+    settings: HashMap<String, ErrorSetting>,
+}
+
+impl ErrorSettings {
+    /// Load the validation settings of an infra
+    pub fn load(conn: &mut PgConnection, infra_id: i64) -> Result<Self> {
+        let settings: Vec<ErrorSetting> = dsl::osrd_infra_errorsetting
+            .filter(dsl::infra_id.eq(infra_id))
+            .select((dsl::error_type, dsl::enabled, dsl::is_warning))
+            .order(dsl::error_type)
+            .load(conn)?;
+        Ok(settings.into_iter().collect())
+    }
+
+    /// Replace the validation settings of an infra
+    pub fn save(&self, conn: &mut PgConnection, infra_id: i64) -> Result<()> {
+        delete(dsl::osrd_infra_errorsetting.filter(dsl::infra_id.eq(infra_id))).execute(conn)?;
+        let new_settings: Vec<_> = self
+            .settings
+            .values()
+            .map(|setting| NewErrorSetting {
+                infra_id,
+                error_type: &setting.error_type,
+                enabled: setting.enabled,
+                is_warning: setting.is_warning,
+            })
+            .collect();
+        if !new_settings.is_empty() {
+            insert_into(dsl::osrd_infra_errorsetting)
+                .values(&new_settings)
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the validation settings of an infra to another one
+    pub fn clone_infra(conn: &mut PgConnection, infra_id: i64, new_infra_id: i64) -> Result<()> {
+        Self::load(conn, infra_id)?.save(conn, new_infra_id)
+    }
+
+    /// Return the settings sorted by error type
+    pub fn to_vec(&self) -> Vec<ErrorSetting> {
+        let mut settings: Vec<_> = self.settings.values().cloned().collect();
+        settings.sort_by(|a, b| a.error_type.cmp(&b.error_type));
+        settings
+    }
+
+    /// Return whether the given error type is checked
+    pub fn is_enabled<T: AsRef<str>>(&self, error_type: T) -> bool {
+        self.settings
+            .get(error_type.as_ref())
+            .is_none_or(|setting| setting.enabled)
+    }
+
+    /// Remove the errors of disabled types and apply severity overrides
+    pub fn apply(&self, errors: Vec<InfraError>) -> Vec<InfraError> {
+        errors
+            .into_iter()
+            .filter(|error| self.is_enabled(error.get_error_type()))
+            .map(|mut error| {
+                let is_warning = self
+                    .settings
+                    .get(error.get_error_type())
+                    .and_then(|setting| setting.is_warning);
+                if let Some(is_warning) = is_warning {
+                    error.set_warning(is_warning);
+                }
+                error
+            })
+            .collect()
+    }
+}
+
+impl FromIterator<ErrorSetting> for ErrorSettings {
+    fn from_iter<I: IntoIterator<Item = ErrorSetting>>(iter: I) -> Self {
+        let settings = iter
+            .into_iter()
+            .map(|setting| (setting.error_type.clone(), setting))
+            .collect();
+        Self { settings }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorSetting, ErrorSettings};
+    use crate::infra_cache::tests::create_track_section_cache;
+    use crate::schema::{InfraError, ObjectRef, ObjectType};
+
+    #[test]
+    fn apply_settings() {
+        let track = create_track_section_cache("A", 100.);
+        let reference = ObjectRef::new(ObjectType::TrackSection, "B");
+        let errors = vec![
+            InfraError::new_invalid_reference(&track, "field", reference),
+            InfraError::new_degenerate_geometry(&track, "geo"),
+            InfraError::new_out_of_range(&track, "field", 120., [0., 100.]),
+        ];
+        let settings: ErrorSettings = [
+            ErrorSetting {
+                error_type: "invalid_reference".into(),
+                enabled: false,
+                is_warning: None,
+            },
+            ErrorSetting {
+                error_type: "out_of_range".into(),
+                enabled: true,
+                is_warning: Some(true),
+            },
+        ]
+        .into_iter()
+        .collect();
+
+        let errors = settings.apply(errors);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0],
+            InfraError::new_degenerate_geometry(&track, "geo")
+        );
+        let mut out_of_range = InfraError::new_out_of_range(&track, "field", 120., [0., 100.]);
+        out_of_range.set_warning(true);
+        assert_eq!(errors[1], out_of_range);
+    }
+}
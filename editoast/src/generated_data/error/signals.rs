@@ -1,14 +1,48 @@
-use super::NoContext;
+use std::collections::{HashMap, HashSet};
+
+use super::GeneratorContext;
+use crate::client::ValidationConfig;
 use crate::generated_data::error::ObjectErrorGenerator;
 use crate::infra_cache::Graph;
 use crate::infra_cache::{InfraCache, ObjectCache};
-use crate::schema::{InfraError, ObjectRef, ObjectType};
+use crate::schema::{
+    Direction, Endpoint, InfraError, OSRDIdentified, ObjectRef, ObjectType, SignalCache,
+    TrackEndpoint,
+};
 
-pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 2] = [
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<Context>; 7] = [
     ObjectErrorGenerator::new(1, check_invalid_ref),
+    ObjectErrorGenerator::new(1, check_invalid_ref_linked_detector),
     ObjectErrorGenerator::new(2, check_out_of_range),
+    ObjectErrorGenerator::new_ctx(3, check_linked_detector_distance),
+    ObjectErrorGenerator::new_ctx(3, check_route_entry),
+    ObjectErrorGenerator::new_ctx(3, check_overlapping),
+    ObjectErrorGenerator::new_ctx(3, check_unknown_signaling_systems),
 ];
 
+/// Context for the signal error generators
+#[derive(Debug)]
+pub struct Context {
+    /// Signaling systems used by the logical signals of the infra
+    signaling_systems: Option<HashSet<String>>,
+    /// Detectors used as route entry point with their direction
+    route_entries: Option<HashSet<(String, Direction)>>,
+    /// Signal location (track, position, direction) to their id
+    signal_locations: HashMap<(String, u64, Direction), String>,
+    linked_detector_max_distance: f64,
+}
+
+impl GeneratorContext for Context {
+    fn new(config: &ValidationConfig) -> Self {
+        Self {
+            signaling_systems: None,
+            route_entries: None,
+            signal_locations: Default::default(),
+            linked_detector_max_distance: config.linked_detector_max_distance,
+        }
+    }
+}
+
 /// Retrieve invalid refs for signals
 pub fn check_invalid_ref(
     signal: &ObjectCache,
@@ -48,14 +82,219 @@ pub fn check_out_of_range(
     }
 }
 
+/// Check that the linked detector exists
+pub fn check_invalid_ref_linked_detector(
+    signal: &ObjectCache,
+    infra_cache: &InfraCache,
+    _: &Graph,
+) -> Vec<InfraError> {
+    let signal = signal.unwrap_signal();
+    match &signal.linked_detector {
+        Some(detector) if !infra_cache.detectors().contains_key(detector) => {
+            let obj_ref = ObjectRef::new(ObjectType::Detector, detector);
+            vec![InfraError::new_invalid_reference(
+                signal,
+                "linked_detector",
+                obj_ref,
+            )]
+        }
+        _ => vec![],
+    }
+}
+
+/// Check that the next signaling systems of logical signals are used in the infra
+pub fn check_unknown_signaling_systems(
+    signal: &ObjectCache,
+    infra_cache: &InfraCache,
+    _: &Graph,
+    mut context: Context,
+) -> (Vec<InfraError>, Context) {
+    let signal = signal.unwrap_signal();
+    let signaling_systems = context.signaling_systems.get_or_insert_with(|| {
+        infra_cache
+            .signals()
+            .values()
+            .flat_map(|signal| &signal.unwrap_signal().logical_signals)
+            .map(|logical_signal| logical_signal.signaling_system.clone())
+            .collect()
+    });
+
+    let mut infra_errors = vec![];
+    for (index, logical_signal) in signal.logical_signals.iter().enumerate() {
+        for (next_index, next) in logical_signal.next_signaling_systems.iter().enumerate() {
+            if !signaling_systems.contains(next) {
+                infra_errors.push(InfraError::new_unknown_signaling_system(
+                    signal,
+                    format!("logical_signals.{index}.next_signaling_systems.{next_index}"),
+                    next,
+                ));
+            }
+        }
+    }
+    (infra_errors, context)
+}
+
+/// Check that the linked detector is close to the signal
+fn check_linked_detector_distance(
+    signal: &ObjectCache,
+    infra_cache: &InfraCache,
+    graph: &Graph,
+    context: Context,
+) -> (Vec<InfraError>, Context) {
+    let signal = signal.unwrap_signal();
+    let detector = match &signal.linked_detector {
+        Some(detector) => infra_cache.detectors().get(detector).unwrap(),
+        None => return (vec![], context),
+    };
+    let detector = detector.unwrap_detector();
+    let max_distance = context.linked_detector_max_distance;
+    if is_within_distance(
+        signal,
+        (&detector.track, detector.position),
+        max_distance,
+        infra_cache,
+        graph,
+    ) {
+        (vec![], context)
+    } else {
+        let error = InfraError::new_linked_detector_too_far(signal, &detector.obj_id, max_distance);
+        (vec![error], context)
+    }
+}
+
+/// Whether a location can be reached from a signal within the given distance along the tracks
+fn is_within_distance(
+    signal: &SignalCache,
+    (track, position): (&String, f64),
+    max_distance: f64,
+    infra_cache: &InfraCache,
+    graph: &Graph,
+) -> bool {
+    if &signal.track == track {
+        return (signal.position - position).abs() <= max_distance;
+    }
+    let signal_track = infra_cache
+        .track_sections()
+        .get(&signal.track)
+        .unwrap()
+        .unwrap_track_section();
+
+    // Explore the track endpoints reachable from the signal, keeping the shortest distance
+    let mut to_visit = vec![
+        (signal_track.get_begin(), signal.position),
+        (
+            signal_track.get_end(),
+            signal_track.length - signal.position,
+        ),
+    ];
+    let mut visited: HashMap<TrackEndpoint, f64> = HashMap::new();
+    while let Some((endpoint, distance)) = to_visit.pop() {
+        if distance > max_distance || visited.get(&endpoint).is_some_and(|d| *d <= distance) {
+            continue;
+        }
+        for neighbour in graph.get_neighbours(&endpoint) {
+            let neighbour_track = match infra_cache.track_sections().get::<String>(&neighbour.track)
+            {
+                Some(neighbour_track) => neighbour_track.unwrap_track_section(),
+                None => continue,
+            };
+            let (offset, other_end) = match neighbour.endpoint {
+                Endpoint::Begin => (position, neighbour_track.get_end()),
+                Endpoint::End => (
+                    neighbour_track.length - position,
+                    neighbour_track.get_begin(),
+                ),
+            };
+            if &neighbour_track.obj_id == track && distance + offset <= max_distance {
+                return true;
+            }
+            to_visit.push((other_end, distance + neighbour_track.length));
+        }
+        visited.insert(endpoint, distance);
+    }
+    false
+}
+
+/// Check that the linked detector is the entry point of a route in the signal direction
+pub fn check_route_entry(
+    signal: &ObjectCache,
+    infra_cache: &InfraCache,
+    _: &Graph,
+    mut context: Context,
+) -> (Vec<InfraError>, Context) {
+    let signal = signal.unwrap_signal();
+    let detector = match &signal.linked_detector {
+        Some(detector) => detector,
+        None => return (vec![], context),
+    };
+    let route_entries = context.route_entries.get_or_insert_with(|| {
+        infra_cache
+            .routes()
+            .values()
+            .map(ObjectCache::unwrap_route)
+            .filter(|route| route.entry_point.is_detector())
+            .map(|route| {
+                (
+                    route.entry_point.get_id().clone(),
+                    route.entry_point_direction,
+                )
+            })
+            .collect()
+    });
+    if route_entries.contains(&(detector.clone(), signal.direction)) {
+        (vec![], context)
+    } else {
+        let obj_ref = ObjectRef::new(ObjectType::Detector, detector);
+        let error = InfraError::new_no_route_entry(signal, "direction", obj_ref);
+        (vec![error], context)
+    }
+}
+
+/// Check that no other signal is located at the same position with the same direction
+pub fn check_overlapping(
+    signal: &ObjectCache,
+    _: &InfraCache,
+    _: &Graph,
+    mut context: Context,
+) -> (Vec<InfraError>, Context) {
+    let signal = signal.unwrap_signal();
+    // Adding 0 turns -0 into +0, both having a different bit representation
+    let location = (
+        signal.track.clone(),
+        (signal.position + 0.).to_bits(),
+        signal.direction,
+    );
+    match context.signal_locations.get(&location) {
+        Some(other) => {
+            let error = InfraError::new_overlapping_signals(signal, other);
+            (vec![error], context)
+        }
+        None => {
+            context
+                .signal_locations
+                .insert(location, signal.obj_id.clone());
+            (vec![], context)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::check_invalid_ref;
     use super::check_out_of_range;
-    use super::InfraError;
-    use crate::infra_cache::tests::{create_signal_cache, create_small_infra_cache};
+    use super::{
+        check_invalid_ref_linked_detector, check_linked_detector_distance, check_overlapping,
+        check_route_entry, check_unknown_signaling_systems, Context, InfraError, OBJECT_GENERATORS,
+    };
+    use crate::client::ValidationConfig;
+    use crate::generated_data::error::generate_errors;
+    use crate::generated_data::error::GeneratorContext;
+    use crate::infra_cache::tests::{
+        create_detector_cache, create_signal_cache, create_small_infra_cache,
+    };
     use crate::infra_cache::Graph;
-    use crate::schema::{ObjectRef, ObjectType};
+    use crate::schema::{Direction, ObjectRef, ObjectType};
+    use serde_json::{from_value, json};
 
     #[test]
     fn invalid_ref() {
@@ -87,4 +326,161 @@ mod tests {
         let infra_error = InfraError::new_out_of_range(&signal, "position", 530., [0.0, 500.]);
         assert_eq!(infra_error, errors[0]);
     }
+
+    #[test]
+    fn invalid_ref_linked_detector() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut signal = create_signal_cache("S_error", "B", 240.);
+        signal.linked_detector = Some("D_error".into());
+        infra_cache.add(signal.clone());
+        let errors = check_invalid_ref_linked_detector(
+            &signal.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        assert_eq!(1, errors.len());
+        let obj_ref = ObjectRef::new(ObjectType::Detector, "D_error");
+        let infra_error = InfraError::new_invalid_reference(&signal, "linked_detector", obj_ref);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn linked_detector_distance() {
+        let mut infra_cache = create_small_infra_cache();
+        // D1 is located on B at 250, A and B are linked
+        let mut signal = create_signal_cache("S", "A", 490.);
+        signal.linked_detector = Some("D1".into());
+        infra_cache.add(signal.clone());
+        let graph = Graph::load(&infra_cache);
+        let config = ValidationConfig::default();
+        let (errors, context) = check_linked_detector_distance(
+            &signal.clone().into(),
+            &infra_cache,
+            &graph,
+            Context::new(&config),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_linked_detector_too_far(
+            &signal,
+            "D1",
+            config.linked_detector_max_distance,
+        );
+        assert_eq!(infra_error, errors[0]);
+
+        signal.track = "B".into();
+        signal.position = 230.;
+        let (errors, _) =
+            check_linked_detector_distance(&signal.into(), &infra_cache, &graph, context);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn linked_detector_distance_through_link() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut signal = create_signal_cache("S", "A", 480.);
+        signal.linked_detector = Some("D2".into());
+        infra_cache.add(signal.clone());
+        infra_cache.add(create_detector_cache("D2", "B", 10.));
+        let graph = Graph::load(&infra_cache);
+        let (errors, _) = check_linked_detector_distance(
+            &signal.into(),
+            &infra_cache,
+            &graph,
+            Context::new(&ValidationConfig::default()),
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn no_route_entry() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut signal = create_signal_cache("S", "B", 240.);
+        signal.linked_detector = Some("D1".into());
+        signal.direction = Direction::StopToStart;
+        infra_cache.add(signal.clone());
+        let (errors, _) = check_route_entry(
+            &signal.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            Context::new(&ValidationConfig::default()),
+        );
+        assert_eq!(1, errors.len());
+        let obj_ref = ObjectRef::new(ObjectType::Detector, "D1");
+        let infra_error = InfraError::new_no_route_entry(&signal, "direction", obj_ref);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn overlapping_signals() {
+        let infra_cache = create_small_infra_cache();
+        let graph = Graph::load(&infra_cache);
+        let signal = create_signal_cache("S1", "B", 240.);
+        let (errors, context) = check_overlapping(
+            &signal.into(),
+            &infra_cache,
+            &graph,
+            Context::new(&ValidationConfig::default()),
+        );
+        assert!(errors.is_empty());
+        let signal = create_signal_cache("S2", "B", 240.);
+        let (errors, _) = check_overlapping(&signal.clone().into(), &infra_cache, &graph, context);
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_overlapping_signals(&signal, "S1");
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn unknown_signaling_system() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut signal = create_signal_cache("S", "B", 240.);
+        signal.logical_signals = from_value(json!([{
+            "signaling_system": "BAL",
+            "next_signaling_systems": ["BAL", "TVM"],
+            "settings": {},
+        }]))
+        .unwrap();
+        infra_cache.add(signal.clone());
+        let (errors, _) = check_unknown_signaling_systems(
+            &signal.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            Context::new(&ValidationConfig::default()),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_unknown_signaling_system(
+            &signal,
+            "logical_signals.0.next_signaling_systems.1",
+            "TVM",
+        );
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn unknown_signaling_system_overlapping() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut signal = create_signal_cache("S1", "B", 240.);
+        signal.logical_signals = from_value(json!([{
+            "signaling_system": "BAL",
+            "next_signaling_systems": ["TVM"],
+            "settings": {},
+        }]))
+        .unwrap();
+        infra_cache.add(signal);
+        infra_cache.add(create_signal_cache("S2", "B", 240.));
+        let errors = generate_errors(
+            ObjectType::Signal,
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            &Default::default(),
+            &Default::default(),
+            &OBJECT_GENERATORS,
+            &[],
+        );
+        assert!(errors
+            .iter()
+            .any(|error| error.get_error_type() == "unknown_signaling_system"));
+        assert!(errors
+            .iter()
+            .any(|error| error.get_error_type() == "overlapping_signals"));
+    }
 }
@@ -1,14 +1,37 @@
-use super::NoContext;
+use std::collections::HashMap;
+
+use super::GeneratorContext;
+use crate::client::ValidationConfig;
 use crate::generated_data::error::ObjectErrorGenerator;
 use crate::infra_cache::Graph;
 use crate::infra_cache::{InfraCache, ObjectCache};
-use crate::schema::{InfraError, ObjectRef, ObjectType};
+use crate::schema::{ApplicableDirections, InfraError, ObjectRef, ObjectType, SpeedSection};
 
-pub const OBJECT_GENERATORS: [ObjectErrorGenerator<NoContext>; 2] = [
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<Context>; 5] = [
     ObjectErrorGenerator::new(1, check_empty),
     ObjectErrorGenerator::new(1, check_speed_section_track_ranges),
+    ObjectErrorGenerator::new(2, check_lpv_panels),
+    ObjectErrorGenerator::new_ctx(2, check_overlapping),
+    ObjectErrorGenerator::new_ctx(2, check_speed_limits),
 ];
 
+/// Context for the speed section error generators
+#[derive(Debug)]
+pub struct Context {
+    max_speed_limit: f64,
+    /// Track to the speed sections ranges already checked (speed section id, begin, end, directions)
+    track_ranges: HashMap<String, Vec<(String, f64, f64, ApplicableDirections)>>,
+}
+
+impl GeneratorContext for Context {
+    fn new(config: &ValidationConfig) -> Self {
+        Self {
+            max_speed_limit: config.max_speed_limit,
+            track_ranges: Default::default(),
+        }
+    }
+}
+
 /// Check if a track section has empty speed section
 pub fn check_empty(speed_section: &ObjectCache, _: &InfraCache, _: &Graph) -> Vec<InfraError> {
     let speed_section = speed_section.unwrap_speed_section();
@@ -59,13 +82,137 @@ pub fn check_speed_section_track_ranges(
     infra_errors
 }
 
+/// Check that speed sections have a speed limit between 0 and the configured maximum
+pub fn check_speed_limits(
+    speed_section: &ObjectCache,
+    _: &InfraCache,
+    _: &Graph,
+    context: Context,
+) -> (Vec<InfraError>, Context) {
+    let speed_section = speed_section.unwrap_speed_section();
+    if speed_section.speed_limit.is_none() && speed_section.speed_limit_by_tag.is_empty() {
+        return (
+            vec![InfraError::new_missing_speed_limit(speed_section)],
+            context,
+        );
+    }
+
+    let expected_range = [0., context.max_speed_limit];
+    let speed_limits = speed_section
+        .speed_limit
+        .map(|speed_limit| ("speed_limit".to_string(), speed_limit))
+        .into_iter()
+        .chain(
+            speed_section
+                .speed_limit_by_tag
+                .iter()
+                .map(|(tag, speed_limit)| (format!("speed_limit_by_tag.{}", tag.0), *speed_limit)),
+        );
+    let infra_errors = speed_limits
+        .filter(|(_, speed_limit)| !(0.0..=context.max_speed_limit).contains(speed_limit))
+        .map(|(field, speed_limit)| {
+            InfraError::new_invalid_speed_limit(speed_section, field, speed_limit, expected_range)
+        })
+        .collect();
+    (infra_errors, context)
+}
+
+/// Check that the execution (`z`) and resume (`r`) LPV panels are located on the speed section.
+/// Announcement panels are located upstream the speed section and aren't checked.
+pub fn check_lpv_panels(speed_section: &ObjectCache, _: &InfraCache, _: &Graph) -> Vec<InfraError> {
+    let speed_section = speed_section.unwrap_speed_section();
+    let lpv = match &speed_section.extensions.lpv_sncf {
+        Some(lpv) => lpv,
+        None => return vec![],
+    };
+    let panels = std::iter::once(("extensions.lpv_sncf.z".to_string(), &lpv.z)).chain(
+        lpv.r
+            .iter()
+            .enumerate()
+            .map(|(index, panel)| (format!("extensions.lpv_sncf.r.{index}"), panel)),
+    );
+    panels
+        .filter(|(_, panel)| {
+            !speed_section.track_ranges.iter().any(|range| {
+                range.track == panel.track && (range.begin..=range.end).contains(&panel.position)
+            })
+        })
+        .map(|(field, _)| InfraError::new_out_of_speed_section(speed_section, field))
+        .collect()
+}
+
+/// Check that speed sections applying on the same track range and direction don't have conflicting limits
+pub fn check_overlapping(
+    speed_section: &ObjectCache,
+    infra_cache: &InfraCache,
+    _: &Graph,
+    mut context: Context,
+) -> (Vec<InfraError>, Context) {
+    let speed_section = speed_section.unwrap_speed_section();
+    let mut infra_errors = vec![];
+    for (index, track_range) in speed_section.track_ranges.iter().enumerate() {
+        let checked_ranges = context
+            .track_ranges
+            .entry(track_range.track.0.clone())
+            .or_default();
+        let overlapping = checked_ranges
+            .iter()
+            .find(|(other, begin, end, directions)| {
+                track_range.begin < *end
+                    && *begin < track_range.end
+                    && directions_intersect(track_range.applicable_directions, *directions)
+                    && has_conflicting_limits(
+                        speed_section,
+                        infra_cache
+                            .speed_sections()
+                            .get(other)
+                            .unwrap()
+                            .unwrap_speed_section(),
+                    )
+            });
+        if let Some((other, ..)) = overlapping {
+            infra_errors.push(InfraError::new_overlapping_speed_sections(
+                speed_section,
+                format!("track_ranges.{index}"),
+                other,
+            ));
+        }
+        checked_ranges.push((
+            speed_section.id.0.clone(),
+            track_range.begin,
+            track_range.end,
+            track_range.applicable_directions,
+        ));
+    }
+    (infra_errors, context)
+}
+
+fn directions_intersect(a: ApplicableDirections, b: ApplicableDirections) -> bool {
+    a == b || a == ApplicableDirections::Both || b == ApplicableDirections::Both
+}
+
+/// Two speed sections conflict if their speed limits or the limits of a common tag differ
+fn has_conflicting_limits(a: &SpeedSection, b: &SpeedSection) -> bool {
+    a.speed_limit != b.speed_limit
+        || a.speed_limit_by_tag.iter().any(|(tag, speed_limit)| {
+            b.speed_limit_by_tag
+                .get(tag)
+                .is_some_and(|other| other != speed_limit)
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::check_speed_section_track_ranges;
-    use super::InfraError;
+    use super::{
+        check_lpv_panels, check_overlapping, check_speed_limits, Context, GeneratorContext,
+        InfraError, OBJECT_GENERATORS,
+    };
+    use crate::generated_data::error::generate_errors;
     use crate::infra_cache::tests::{create_small_infra_cache, create_speed_section_cache};
     use crate::infra_cache::Graph;
     use crate::schema::{ObjectRef, ObjectType};
+    use serde_json::{from_value, json};
 
     #[test]
     fn invalid_ref() {
@@ -101,4 +248,116 @@ mod tests {
             InfraError::new_out_of_range(&speed_section, "track_ranges.0.end", 530., [0.0, 500.]);
         assert_eq!(infra_error, errors[0]);
     }
+
+    #[test]
+    fn missing_speed_limit() {
+        let infra_cache = create_small_infra_cache();
+        let speed_section = create_speed_section_cache("SP_error", vec![("A", 20., 500.)]);
+        let (errors, _) = check_speed_limits(
+            &speed_section.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            Context::new(&Default::default()),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_missing_speed_limit(&speed_section);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn invalid_speed_limit() {
+        let infra_cache = create_small_infra_cache();
+        let mut speed_section = create_speed_section_cache("SP_error", vec![("A", 20., 500.)]);
+        speed_section.speed_limit = Some(-10.);
+        speed_section.speed_limit_by_tag = [("MA100".into(), 30.)].into();
+        let context = Context::new(&Default::default());
+        let expected_range = [0., context.max_speed_limit];
+        let (errors, _) = check_speed_limits(
+            &speed_section.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            context,
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_invalid_speed_limit(
+            &speed_section,
+            "speed_limit",
+            -10.,
+            expected_range,
+        );
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn lpv_panel_out_of_speed_section() {
+        let infra_cache = create_small_infra_cache();
+        let mut speed_section = create_speed_section_cache("SP_error", vec![("A", 20., 500.)]);
+        speed_section.extensions = from_value(json!({
+            "lpv_sncf": {
+                "announcement": [],
+                "z": { "track": "A", "position": 20., "angle_geo": 0., "angle_sch": 0., "side": "LEFT", "type": "Z" },
+                "r": [{ "track": "B", "position": 20., "angle_geo": 0., "angle_sch": 0., "side": "LEFT", "type": "R" }],
+            }
+        }))
+        .unwrap();
+        let errors = check_lpv_panels(
+            &speed_section.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error =
+            InfraError::new_out_of_speed_section(&speed_section, "extensions.lpv_sncf.r.0");
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn overlapping_speed_sections() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut first = create_speed_section_cache("SP_1", vec![("A", 0., 300.)]);
+        first.speed_limit = Some(30.);
+        let mut second =
+            create_speed_section_cache("SP_2", vec![("B", 0., 100.), ("A", 200., 500.)]);
+        second.speed_limit = Some(20.);
+        infra_cache.add(first.clone());
+        infra_cache.add(second.clone());
+        let graph = Graph::load(&infra_cache);
+
+        let (errors, context) = check_overlapping(
+            &first.into(),
+            &infra_cache,
+            &graph,
+            Context::new(&Default::default()),
+        );
+        assert!(errors.is_empty());
+        let (errors, _) = check_overlapping(&second.clone().into(), &infra_cache, &graph, context);
+        assert_eq!(1, errors.len());
+        let infra_error =
+            InfraError::new_overlapping_speed_sections(&second, "track_ranges.1", "SP_1");
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn overlapping_speed_section_without_speed_limit() {
+        let mut infra_cache = create_small_infra_cache();
+        infra_cache.add(create_speed_section_cache("SP_1", vec![("A", 0., 300.)]));
+        let mut second = create_speed_section_cache("SP_2", vec![("A", 200., 500.)]);
+        second.speed_limit = Some(20.);
+        infra_cache.add(second);
+        let errors = generate_errors(
+            ObjectType::SpeedSection,
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            &Default::default(),
+            &Default::default(),
+            &OBJECT_GENERATORS,
+            &[],
+        );
+        assert!(errors
+            .iter()
+            .any(|error| error.get_error_type() == "missing_speed_limit"));
+        assert!(errors
+            .iter()
+            .any(|error| error.get_error_type() == "overlapping_speed_sections"));
+    }
 }
@@ -1,21 +1,37 @@
+use crate::client::ValidationConfig;
 use crate::infra_cache::{Graph, InfraCache, ObjectCache};
-use crate::schema::{InfraError, ObjectRef, ObjectType, TrackEndpoint};
+use crate::schema::{
+    haversine_distance, Endpoint, InfraError, ObjectRef, ObjectType, TrackEndpoint,
+    TrackSectionCache,
+};
 use std::collections::{HashMap, HashSet};
 
-use super::ObjectErrorGenerator;
+use super::{GeneratorContext, ObjectErrorGenerator};
 
-pub const OBJECT_GENERATORS: [ObjectErrorGenerator<Context>; 4] = [
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<Context>; 6] = [
     ObjectErrorGenerator::new(1, check_invalid_ref_ports),
     ObjectErrorGenerator::new(1, check_invalid_ref_switch_type),
     ObjectErrorGenerator::new(2, check_match_ports_type),
     ObjectErrorGenerator::new_ctx(3, check_overlapping),
+    ObjectErrorGenerator::new_ctx(3, check_ports_location),
+    ObjectErrorGenerator::new(3, check_dangling_ports),
 ];
 
 /// Context for the switch error generators
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Context {
     /// Track endpoint to their switch
     endpoint_to_switch: HashMap<TrackEndpoint, String>,
+    port_max_distance: f64,
+}
+
+impl GeneratorContext for Context {
+    fn new(config: &ValidationConfig) -> Self {
+        Self {
+            endpoint_to_switch: Default::default(),
+            port_max_distance: config.switch_port_max_distance,
+        }
+    }
 }
 
 /// Check that ports (track endpoints) exists
@@ -108,11 +124,105 @@ fn check_overlapping(
     }
 }
 
+/// Check that the track endpoints of the switch ports are geometrically co-located.
+/// A port is misplaced if it is far from every other port of the switch.
+/// Ports on tracks without geometry are ignored, their tracks being reported as degenerate.
+fn check_ports_location(
+    switch: &ObjectCache,
+    infra_cache: &InfraCache,
+    _: &Graph,
+    context: Context,
+) -> (Vec<InfraError>, Context) {
+    let switch = switch.unwrap_switch();
+    let mut locations: Vec<_> = switch
+        .ports
+        .iter()
+        .filter_map(|(port_name, port)| {
+            let track = infra_cache
+                .track_sections()
+                .get::<String>(&port.track)
+                .unwrap()
+                .unwrap_track_section();
+            Some((port_name, track.get_endpoint_location(port.endpoint)?))
+        })
+        .collect();
+    locations.sort_by_key(|(port_name, _)| *port_name);
+
+    let mut infra_errors = vec![];
+    for (port_name, location) in locations.iter() {
+        let distance = locations
+            .iter()
+            .filter(|(other_name, _)| other_name != port_name)
+            .map(|(_, other)| haversine_distance(location, other))
+            .min_by(f64::total_cmp);
+        if let Some(distance) = distance.filter(|distance| *distance > context.port_max_distance) {
+            infra_errors.push(InfraError::new_misplaced_switch_port(
+                switch,
+                port_name,
+                distance,
+                context.port_max_distance,
+            ));
+        }
+    }
+    (infra_errors, context)
+}
+
+/// Check that the tracks of the switch ports are connected or end with a buffer stop at their other endpoint
+pub fn check_dangling_ports(
+    switch: &ObjectCache,
+    infra_cache: &InfraCache,
+    graph: &Graph,
+) -> Vec<InfraError> {
+    let switch = switch.unwrap_switch();
+    let mut ports: Vec<_> = switch.ports.iter().collect();
+    ports.sort_by_key(|(port_name, _)| *port_name);
+    ports
+        .into_iter()
+        .filter(|(_, port)| {
+            let track = infra_cache
+                .track_sections()
+                .get::<String>(&port.track)
+                .unwrap()
+                .unwrap_track_section();
+            let other_endpoint = match port.endpoint {
+                Endpoint::Begin => track.get_end(),
+                Endpoint::End => track.get_begin(),
+            };
+            !graph.has_neighbour(&other_endpoint)
+                && !has_buffer_stop_at(infra_cache, track, other_endpoint.endpoint)
+        })
+        .map(|(port_name, _)| InfraError::new_dangling_switch_port(switch, port_name))
+        .collect()
+}
+
+/// Check if a buffer stop is located on the half of the track next to the given endpoint
+fn has_buffer_stop_at(
+    infra_cache: &InfraCache,
+    track: &TrackSectionCache,
+    endpoint: Endpoint,
+) -> bool {
+    let refs = match infra_cache.track_sections_refs.get(&track.obj_id) {
+        Some(refs) => refs,
+        None => return false,
+    };
+    refs.iter()
+        .filter(|obj_ref| obj_ref.obj_type == ObjectType::BufferStop)
+        .filter_map(|obj_ref| infra_cache.buffer_stops().get(&obj_ref.obj_id))
+        .map(|buffer_stop| buffer_stop.unwrap_buffer_stop().position)
+        .any(|position| match endpoint {
+            Endpoint::Begin => position <= track.length / 2.,
+            Endpoint::End => position >= track.length / 2.,
+        })
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::client::ValidationConfig;
     use crate::generated_data::error::switches::Context;
+    use crate::generated_data::error::GeneratorContext;
     use crate::infra_cache::tests::{
-        create_small_infra_cache, create_switch_cache_point, create_track_endpoint,
+        create_buffer_stop_cache, create_small_infra_cache, create_switch_cache_point,
+        create_track_endpoint, create_track_section_cache,
     };
     use crate::schema::{Endpoint, ObjectRef, ObjectType};
 
@@ -121,6 +231,9 @@ mod tests {
     use super::check_match_ports_type;
     use super::check_overlapping;
     use super::InfraError;
+    use super::{check_dangling_ports, check_ports_location};
+    use crate::infra_cache::Graph;
+    use crate::schema::{haversine_distance, LineString};
 
     #[test]
     fn invalid_ref_track() {
@@ -184,7 +297,7 @@ mod tests {
     #[test]
     fn overlapping_switches() {
         let mut infra_cache = create_small_infra_cache();
-        let mut context = Context::default();
+        let mut context = Context::new(&ValidationConfig::default());
         let switch = create_switch_cache_point(
             "SW_error".into(),
             ("BASE", create_track_endpoint(Endpoint::End, "B")),
@@ -202,4 +315,97 @@ mod tests {
             check_overlapping(&switch.into(), &infra_cache, &Default::default(), context);
         assert_eq!(1, errors.len());
     }
+
+    #[test]
+    fn misplaced_port() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("E", 500.);
+        track.geo = LineString::LineString {
+            coordinates: vec![[1., 1.], [2., 2.]],
+        };
+        infra_cache.add(track);
+        let switch = create_switch_cache_point(
+            "SW_error".into(),
+            ("BASE", create_track_endpoint(Endpoint::End, "B")),
+            ("LEFT", create_track_endpoint(Endpoint::Begin, "C")),
+            ("RIGHT", create_track_endpoint(Endpoint::Begin, "E")),
+            "point".into(),
+        );
+        let config = ValidationConfig::default();
+        let (errors, _) = check_ports_location(
+            &switch.clone().into(),
+            &infra_cache,
+            &Default::default(),
+            Context::new(&config),
+        );
+        assert_eq!(1, errors.len());
+        let base = infra_cache.track_sections().get("B").unwrap();
+        let base = base
+            .unwrap_track_section()
+            .get_endpoint_location(Endpoint::End)
+            .unwrap();
+        let distance = haversine_distance(&[1., 1.], &base);
+        let infra_error = InfraError::new_misplaced_switch_port(
+            &switch,
+            "RIGHT",
+            distance,
+            config.switch_port_max_distance,
+        );
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn port_without_geometry() {
+        let mut infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("E", 500.);
+        track.geo = LineString::LineString {
+            coordinates: vec![],
+        };
+        infra_cache.add(track);
+        let switch = create_switch_cache_point(
+            "SW_error".into(),
+            ("BASE", create_track_endpoint(Endpoint::End, "B")),
+            ("LEFT", create_track_endpoint(Endpoint::Begin, "C")),
+            ("RIGHT", create_track_endpoint(Endpoint::Begin, "E")),
+            "point".into(),
+        );
+        let (errors, _) = check_ports_location(
+            &switch.into(),
+            &infra_cache,
+            &Default::default(),
+            Context::new(&ValidationConfig::default()),
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn dangling_port() {
+        let mut infra_cache = create_small_infra_cache();
+        infra_cache.add(create_track_section_cache("E", 500.));
+        infra_cache.add(create_track_section_cache("F", 500.));
+        // The buffer stop of F is located at the switch side
+        infra_cache.add(create_buffer_stop_cache("BF_F", "F", 20.));
+        let switch = create_switch_cache_point(
+            "SW_error".into(),
+            ("BASE", create_track_endpoint(Endpoint::Begin, "A")),
+            ("LEFT", create_track_endpoint(Endpoint::Begin, "E")),
+            ("RIGHT", create_track_endpoint(Endpoint::Begin, "F")),
+            "point".into(),
+        );
+        infra_cache.add(switch.clone());
+        let graph = Graph::load(&infra_cache);
+        let errors = check_dangling_ports(&switch.clone().into(), &infra_cache, &graph);
+        assert_eq!(2, errors.len());
+        assert_eq!(
+            InfraError::new_dangling_switch_port(&switch, "LEFT"),
+            errors[0]
+        );
+        assert_eq!(
+            InfraError::new_dangling_switch_port(&switch, "RIGHT"),
+            errors[1]
+        );
+
+        let switch = infra_cache.switches().get("switch").unwrap().clone();
+        assert!(check_dangling_ports(&switch, &infra_cache, &graph).is_empty());
+    }
 }
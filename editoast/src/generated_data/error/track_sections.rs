@@ -0,0 +1,277 @@
+use super::GeneratorContext;
+use crate::client::ValidationConfig;
+use crate::generated_data::error::ObjectErrorGenerator;
+use crate::infra_cache::Graph;
+use crate::infra_cache::{InfraCache, ObjectCache};
+use crate::schema::{InfraError, TrackSectionCache};
+
+pub struct TrackSectionContext {
+    length_tolerance: f64,
+}
+
+impl GeneratorContext for TrackSectionContext {
+    fn new(config: &ValidationConfig) -> Self {
+        Self {
+            length_tolerance: config.track_length_tolerance,
+        }
+    }
+}
+
+pub const OBJECT_GENERATORS: [ObjectErrorGenerator<TrackSectionContext>; 4] = [
+    ObjectErrorGenerator::new(1, check_degenerate_geometry),
+    ObjectErrorGenerator::new(1, check_ranges),
+    ObjectErrorGenerator::new_ctx(1, check_length),
+    ObjectErrorGenerator::new(1, check_self_intersection),
+];
+
+/// Check that the geometry of a track section has at least two distinct points
+pub fn check_degenerate_geometry(
+    track_section: &ObjectCache,
+    _: &InfraCache,
+    _: &Graph,
+) -> Vec<InfraError> {
+    let track_section = track_section.unwrap_track_section();
+    if track_section.geo.is_degenerate() {
+        vec![InfraError::new_degenerate_geometry(track_section, "geo")]
+    } else {
+        vec![]
+    }
+}
+
+/// Check that slopes, curves and loading gauge limits are within the track section
+/// and that slopes and curves don't overlap
+pub fn check_ranges(track_section: &ObjectCache, _: &InfraCache, _: &Graph) -> Vec<InfraError> {
+    let track_section = track_section.unwrap_track_section();
+    let slopes: Vec<_> = track_section
+        .slopes
+        .iter()
+        .map(|slope| (slope.begin, slope.end))
+        .collect();
+    let curves: Vec<_> = track_section
+        .curves
+        .iter()
+        .map(|curve| (curve.begin, curve.end))
+        .collect();
+    let loading_gauge_limits: Vec<_> = track_section
+        .loading_gauge_limits
+        .iter()
+        .map(|limit| (limit.begin, limit.end))
+        .collect();
+
+    let mut infra_errors = vec![];
+    for (field, ranges) in [
+        ("slopes", &slopes),
+        ("curves", &curves),
+        ("loading_gauge_limits", &loading_gauge_limits),
+    ] {
+        infra_errors.extend(check_ranges_bounds(track_section, field, ranges));
+    }
+    for (field, ranges) in [("slopes", &slopes), ("curves", &curves)] {
+        infra_errors.extend(check_ranges_overlap(track_section, field, ranges));
+    }
+    infra_errors
+}
+
+fn check_ranges_bounds(
+    track_section: &TrackSectionCache,
+    field: &str,
+    ranges: &[(f64, f64)],
+) -> Vec<InfraError> {
+    let mut infra_errors = vec![];
+    for (index, (begin, end)) in ranges.iter().enumerate() {
+        if !(0.0..=track_section.length).contains(begin) {
+            infra_errors.push(InfraError::new_out_of_range(
+                track_section,
+                format!("{field}.{index}.begin"),
+                *begin,
+                [0.0, track_section.length],
+            ));
+        }
+        let expected_range = [begin.clamp(0.0, track_section.length), track_section.length];
+        if !(expected_range[0]..=expected_range[1]).contains(end) {
+            infra_errors.push(InfraError::new_out_of_range(
+                track_section,
+                format!("{field}.{index}.end"),
+                *end,
+                expected_range,
+            ));
+        }
+    }
+    infra_errors
+}
+
+fn check_ranges_overlap(
+    track_section: &TrackSectionCache,
+    field: &str,
+    ranges: &[(f64, f64)],
+) -> Vec<InfraError> {
+    let mut indexes: Vec<_> = (0..ranges.len()).collect();
+    indexes.sort_by(|a, b| ranges[*a].0.total_cmp(&ranges[*b].0));
+
+    let mut infra_errors = vec![];
+    // Range reaching the furthest among the ones already visited
+    let mut furthest: Option<usize> = None;
+    for index in indexes {
+        let (begin, end) = ranges[index];
+        match furthest {
+            Some(other) if ranges[other].1 > begin => {
+                infra_errors.push(InfraError::new_overlapping_ranges(
+                    track_section,
+                    format!("{field}.{index}"),
+                    format!("{field}.{other}"),
+                ));
+                if end > ranges[other].1 {
+                    furthest = Some(index);
+                }
+            }
+            _ => furthest = Some(index),
+        }
+    }
+    infra_errors
+}
+
+/// Check that the length of a track section matches the geodesic length of its geometry
+/// Degenerate geometries are reported by `check_degenerate_geometry`
+pub fn check_length(
+    track_section: &ObjectCache,
+    _: &InfraCache,
+    _: &Graph,
+    context: TrackSectionContext,
+) -> (Vec<InfraError>, TrackSectionContext) {
+    let track_section = track_section.unwrap_track_section();
+    if track_section.geo.is_degenerate() {
+        return (vec![], context);
+    }
+    let geo_length = track_section.geo.geodesic_length();
+    let delta = (geo_length - track_section.length).abs();
+    if delta > context.length_tolerance * track_section.length {
+        let error = InfraError::new_inconsistent_length(track_section, geo_length);
+        (vec![error], context)
+    } else {
+        (vec![], context)
+    }
+}
+
+/// Check that the geometry of a track section doesn't cross itself
+pub fn check_self_intersection(
+    track_section: &ObjectCache,
+    _: &InfraCache,
+    _: &Graph,
+) -> Vec<InfraError> {
+    let track_section = track_section.unwrap_track_section();
+    match track_section.geo.find_self_intersection() {
+        Some(point) => vec![InfraError::new_self_intersecting_geometry(
+            track_section,
+            "geo",
+            point,
+        )],
+        None => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InfraError;
+    use super::{
+        check_degenerate_geometry, check_length, check_ranges, check_self_intersection,
+        GeneratorContext, TrackSectionContext,
+    };
+    use crate::infra_cache::tests::{create_small_infra_cache, create_track_section_cache};
+    use crate::infra_cache::Graph;
+    use crate::schema::LineString;
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn degenerate_geometry() {
+        let infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("E", 500.);
+        track.geo = LineString::LineString {
+            coordinates: vec![[1., 1.], [1., 1.]],
+        };
+        let errors = check_degenerate_geometry(
+            &track.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_degenerate_geometry(&track, "geo");
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("E", 500.);
+        track.slopes = from_value(json!([{ "gradient": 5., "begin": 100., "end": 530. }])).unwrap();
+        let errors = check_ranges(
+            &track.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_out_of_range(&track, "slopes.0.end", 530., [100., 500.]);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+        let infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("E", 500.);
+        track.curves = from_value(json!([
+            { "radius": 1000., "begin": 200., "end": 300. },
+            { "radius": 1000., "begin": 0., "end": 250. },
+            { "radius": 1000., "begin": 300., "end": 500. },
+        ]))
+        .unwrap();
+        let errors = check_ranges(
+            &track.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_overlapping_ranges(&track, "curves.0", "curves.1");
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn inconsistent_length() {
+        let infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("E", 500.);
+        let (errors, _) = check_length(
+            &track.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            TrackSectionContext::new(&Default::default()),
+        );
+        assert!(errors.is_empty());
+
+        track.length = 1000.;
+        let (errors, _) = check_length(
+            &track.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+            TrackSectionContext::new(&Default::default()),
+        );
+        assert_eq!(1, errors.len());
+        let geo_length = track.geo.geodesic_length();
+        let infra_error = InfraError::new_inconsistent_length(&track, geo_length);
+        assert_eq!(infra_error, errors[0]);
+    }
+
+    #[test]
+    fn self_intersection() {
+        let infra_cache = create_small_infra_cache();
+        let mut track = create_track_section_cache("E", 500.);
+        track.geo = LineString::LineString {
+            coordinates: vec![[0., 0.], [2., 2.], [2., 0.], [0., 2.]],
+        };
+        let errors = check_self_intersection(
+            &track.clone().into(),
+            &infra_cache,
+            &Graph::load(&infra_cache),
+        );
+        assert_eq!(1, errors.len());
+        let infra_error = InfraError::new_self_intersecting_geometry(&track, "geo", [1., 1.]);
+        assert_eq!(infra_error, errors[0]);
+    }
+}
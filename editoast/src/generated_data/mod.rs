@@ -16,6 +16,7 @@ use buffer_stop::BufferStopLayer;
 use catenary::CatenaryLayer;
 use detector::DetectorLayer;
 use error::ErrorLayer;
+pub use error::{ErrorSetting, ErrorSettings};
 use lpv_panel::LPVPanelLayer;
 use operational_point::OperationalPointLayer;
 use signal::SignalLayer;
@@ -24,6 +25,7 @@ use switch::SwitchLayer;
 use track_section::TrackSectionLayer;
 use track_section_link::TrackSectionLinkLayer;
 
+use crate::client::ValidationConfig;
 use crate::error::Result;
 use crate::infra_cache::InfraCache;
 use crate::schema::operation::OperationResult;
@@ -34,7 +36,12 @@ use diesel::sql_types::BigInt;
 /// This trait define how a generated data table should be handled
 pub trait GeneratedData {
     fn table_name() -> &'static str;
-    fn generate(conn: &mut PgConnection, infra: i64, infra_cache: &InfraCache) -> Result<()>;
+    fn generate(
+        conn: &mut PgConnection,
+        infra: i64,
+        infra_cache: &InfraCache,
+        config: &ValidationConfig,
+    ) -> Result<()>;
 
     fn clear(conn: &mut PgConnection, infra: i64) -> Result<()> {
         sql_query(format!(
@@ -46,9 +53,14 @@ pub trait GeneratedData {
         Ok(())
     }
 
-    fn refresh(conn: &mut PgConnection, infra: i64, infra_cache: &InfraCache) -> Result<()> {
+    fn refresh(
+        conn: &mut PgConnection,
+        infra: i64,
+        infra_cache: &InfraCache,
+        config: &ValidationConfig,
+    ) -> Result<()> {
         Self::clear(conn, infra)?;
-        Self::generate(conn, infra, infra_cache)
+        Self::generate(conn, infra, infra_cache, config)
     }
 
     /// Search and update all objects that needs to be refreshed given a list of operation.
@@ -57,25 +69,41 @@ pub trait GeneratedData {
         infra: i64,
         operations: &[OperationResult],
         infra_cache: &InfraCache,
+        config: &ValidationConfig,
     ) -> Result<()>;
 }
 
 /// Refresh all the generated data of a given infra
-pub fn refresh_all(conn: &mut PgConnection, infra: i64, infra_cache: &InfraCache) -> Result<()> {
-    TrackSectionLayer::refresh(conn, infra, infra_cache)?;
-    SpeedSectionLayer::refresh(conn, infra, infra_cache)?;
-    SignalLayer::refresh(conn, infra, infra_cache)?;
-    SwitchLayer::refresh(conn, infra, infra_cache)?;
-    BufferStopLayer::refresh(conn, infra, infra_cache)?;
-    CatenaryLayer::refresh(conn, infra, infra_cache)?;
-    DetectorLayer::refresh(conn, infra, infra_cache)?;
-    OperationalPointLayer::refresh(conn, infra, infra_cache)?;
-    TrackSectionLinkLayer::refresh(conn, infra, infra_cache)?;
-    LPVPanelLayer::refresh(conn, infra, infra_cache)?;
-    ErrorLayer::refresh(conn, infra, infra_cache)?;
+pub fn refresh_all(
+    conn: &mut PgConnection,
+    infra: i64,
+    infra_cache: &InfraCache,
+    config: &ValidationConfig,
+) -> Result<()> {
+    TrackSectionLayer::refresh(conn, infra, infra_cache, config)?;
+    SpeedSectionLayer::refresh(conn, infra, infra_cache, config)?;
+    SignalLayer::refresh(conn, infra, infra_cache, config)?;
+    SwitchLayer::refresh(conn, infra, infra_cache, config)?;
+    BufferStopLayer::refresh(conn, infra, infra_cache, config)?;
+    CatenaryLayer::refresh(conn, infra, infra_cache, config)?;
+    DetectorLayer::refresh(conn, infra, infra_cache, config)?;
+    OperationalPointLayer::refresh(conn, infra, infra_cache, config)?;
+    TrackSectionLinkLayer::refresh(conn, infra, infra_cache, config)?;
+    LPVPanelLayer::refresh(conn, infra, infra_cache, config)?;
+    ErrorLayer::refresh(conn, infra, infra_cache, config)?;
     Ok(())
 }
 
+/// Refresh the errors of a given infra, needed when its validation settings change
+pub fn refresh_errors(
+    conn: &mut PgConnection,
+    infra: i64,
+    infra_cache: &InfraCache,
+    config: &ValidationConfig,
+) -> Result<()> {
+    ErrorLayer::refresh(conn, infra, infra_cache, config)
+}
+
 /// Clear all the generated data of a given infra
 pub fn clear_all(conn: &mut PgConnection, infra: i64) -> Result<()> {
     TrackSectionLayer::clear(conn, infra)?;
@@ -98,18 +126,19 @@ pub fn update_all(
     infra: i64,
     operations: &[OperationResult],
     infra_cache: &InfraCache,
+    config: &ValidationConfig,
 ) -> Result<()> {
-    TrackSectionLayer::update(conn, infra, operations, infra_cache)?;
-    SpeedSectionLayer::update(conn, infra, operations, infra_cache)?;
-    SignalLayer::update(conn, infra, operations, infra_cache)?;
-    SwitchLayer::update(conn, infra, operations, infra_cache)?;
-    BufferStopLayer::update(conn, infra, operations, infra_cache)?;
-    CatenaryLayer::update(conn, infra, operations, infra_cache)?;
-    DetectorLayer::update(conn, infra, operations, infra_cache)?;
-    OperationalPointLayer::update(conn, infra, operations, infra_cache)?;
-    TrackSectionLinkLayer::update(conn, infra, operations, infra_cache)?;
-    LPVPanelLayer::update(conn, infra, operations, infra_cache)?;
-    ErrorLayer::update(conn, infra, operations, infra_cache)?;
+    TrackSectionLayer::update(conn, infra, operations, infra_cache, config)?;
+    SpeedSectionLayer::update(conn, infra, operations, infra_cache, config)?;
+    SignalLayer::update(conn, infra, operations, infra_cache, config)?;
+    SwitchLayer::update(conn, infra, operations, infra_cache, config)?;
+    BufferStopLayer::update(conn, infra, operations, infra_cache, config)?;
+    CatenaryLayer::update(conn, infra, operations, infra_cache, config)?;
+    DetectorLayer::update(conn, infra, operations, infra_cache, config)?;
+    OperationalPointLayer::update(conn, infra, operations, infra_cache, config)?;
+    TrackSectionLinkLayer::update(conn, infra, operations, infra_cache, config)?;
+    LPVPanelLayer::update(conn, infra, operations, infra_cache, config)?;
+    ErrorLayer::update(conn, infra, operations, infra_cache, config)?;
     Ok(())
 }
 
@@ -123,14 +152,21 @@ pub mod tests {
     #[test]
     fn refresh_all_test() {
         test_infra_transaction(|conn: &mut PgConnection, infra: Infra| {
-            assert!(refresh_all(conn, infra.id, &Default::default()).is_ok());
+            assert!(refresh_all(conn, infra.id, &Default::default(), &Default::default()).is_ok());
         })
     }
 
     #[test]
     fn update_all_test() {
         test_infra_transaction(|conn: &mut PgConnection, infra: Infra| {
-            assert!(update_all(conn, infra.id, &[], &Default::default()).is_ok());
+            assert!(update_all(
+                conn,
+                infra.id,
+                &[],
+                &Default::default(),
+                &Default::default()
+            )
+            .is_ok());
         })
     }
 
@@ -1,3 +1,4 @@
+use crate::client::ValidationConfig;
 use crate::error::Result;
 use crate::infra_cache::InfraCache;
 use crate::schema::ObjectType;
@@ -14,7 +15,12 @@ impl GeneratedData for SwitchLayer {
         "osrd_infra_switchlayer"
     }
 
-    fn generate(conn: &mut PgConnection, infra: i64, _infra_cache: &InfraCache) -> Result<()> {
+    fn generate(
+        conn: &mut PgConnection,
+        infra: i64,
+        _infra_cache: &InfraCache,
+        _config: &ValidationConfig,
+    ) -> Result<()> {
         sql_query(include_str!("sql/generate_switch_layer.sql"))
             .bind::<BigInt, _>(infra)
             .execute(conn)?;
@@ -26,6 +32,7 @@ impl GeneratedData for SwitchLayer {
         infra: i64,
         operations: &[crate::schema::operation::OperationResult],
         infra_cache: &crate::infra_cache::InfraCache,
+        _config: &ValidationConfig,
     ) -> Result<()> {
         let involved_objects =
             InvolvedObjects::from_operations(operations, infra_cache, ObjectType::Switch);
@@ -1,3 +1,4 @@
+use crate::client::ValidationConfig;
 use crate::error::Result;
 use crate::generated_data;
 use crate::infra_cache::InfraCache;
@@ -11,6 +12,7 @@ use diesel::{delete, sql_query, update, PgConnection, QueryDsl, RunQueryDsl};
 use editoast_derive::EditoastError;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
 pub const RAILJSON_VERSION: &str = "3.1.0";
@@ -118,9 +120,13 @@ impl Infra {
         }
     }
 
+    /// The modification date is updated too since the generated data is served as map tiles
     pub fn bump_generated_version(&self, conn: &mut PgConnection) -> Result<Self> {
         match update(dsl::osrd_infra_infra.filter(dsl::id.eq(self.id)))
-            .set(dsl::generated_version.eq(&self.version))
+            .set((
+                dsl::generated_version.eq(&self.version),
+                dsl::modified.eq(Utc::now().naive_utc()),
+            ))
             .get_result::<Infra>(conn)
         {
             Ok(infra) => Ok(infra),
@@ -131,7 +137,10 @@ impl Infra {
 
     pub fn downgrade_generated_version(&self, conn: &mut PgConnection) -> Result<Self> {
         match update(dsl::osrd_infra_infra.filter(dsl::id.eq(self.id)))
-            .set(dsl::generated_version.eq::<Option<String>>(None))
+            .set((
+                dsl::generated_version.eq::<Option<String>>(None),
+                dsl::modified.eq(Utc::now().naive_utc()),
+            ))
             .get_result::<Infra>(conn)
         {
             Ok(infra) => Ok(infra),
@@ -140,6 +149,11 @@ impl Infra {
         }
     }
 
+    /// Modification date of the infra, truncated to the second
+    pub fn modified_time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.modified.timestamp().max(0) as u64)
+    }
+
     pub fn update_modified_timestamp_to_now(&self, conn: &mut PgConnection) -> Result<Self> {
         match update(dsl::osrd_infra_infra.filter(dsl::id.eq(self.id)))
             .set(dsl::modified.eq(Utc::now().naive_utc()))
@@ -218,6 +232,7 @@ impl Infra {
         conn: &mut PgConnection,
         force: bool,
         infra_cache: &InfraCache,
+        config: &ValidationConfig,
     ) -> Result<bool> {
         // Check if refresh is needed
         if !force
@@ -227,7 +242,7 @@ impl Infra {
             return Ok(false);
         }
 
-        generated_data::refresh_all(conn, self.id, infra_cache)?;
+        generated_data::refresh_all(conn, self.id, infra_cache, config)?;
 
         // Update generated infra version
         self.bump_generated_version(conn)?;
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::infra_cache::{Graph, InfraCache};
+use crate::schema::{Endpoint, OSRDIdentified, ObjectType, TrackRange, Waypoint};
+
+/// A train detection section: track ranges delimited by detectors or buffer stops
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DetectionSection {
+    pub track_ranges: Vec<TrackRange>,
+    /// Detectors and buffer stops bounding the section
+    pub waypoints: Vec<Waypoint>,
+}
+
+impl DetectionSection {
+    /// Sum of the lengths of the section track ranges
+    pub fn length(&self) -> f64 {
+        self.track_ranges
+            .iter()
+            .map(|range| range.end - range.begin)
+            .sum()
+    }
+}
+
+/// Piece of track between two consecutive waypoints (or track ends)
+struct Segment<'a> {
+    track: &'a String,
+    begin: f64,
+    end: f64,
+    waypoints: Vec<Waypoint>,
+}
+
+/// Compute the detection sections of an infra.
+/// Tracks are split at each detector and buffer stop, pieces meeting at a track link or switch are merged.
+pub fn compute_detection_sections(
+    infra_cache: &InfraCache,
+    graph: &Graph,
+) -> Vec<DetectionSection> {
+    let mut segments = vec![];
+    // Track to the index of its first and last segments
+    let mut track_segments: HashMap<&String, (usize, usize)> = HashMap::new();
+
+    let mut tracks: Vec<_> = infra_cache.track_sections().iter().collect();
+    tracks.sort_by_key(|(track_id, _)| *track_id);
+    for (track_id, track) in tracks {
+        let track = track.unwrap_track_section();
+        let mut waypoints = track_waypoints(infra_cache, track_id, track.length);
+        waypoints.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let first = segments.len();
+        let mut begin = 0.;
+        let mut previous = None;
+        for (position, waypoint) in waypoints {
+            segments.push(Segment {
+                track: track_id,
+                begin,
+                end: position,
+                waypoints: previous.into_iter().chain([waypoint.clone()]).collect(),
+            });
+            begin = position;
+            previous = Some(waypoint);
+        }
+        segments.push(Segment {
+            track: track_id,
+            begin,
+            end: track.length,
+            waypoints: previous.into_iter().collect(),
+        });
+        track_segments.insert(track_id, (first, segments.len() - 1));
+    }
+
+    // Merge the segments linked at track endpoints
+    let mut parents: Vec<_> = (0..segments.len()).collect();
+    for (track_id, (first, last)) in track_segments.iter() {
+        let track = infra_cache
+            .track_sections()
+            .get(*track_id)
+            .unwrap()
+            .unwrap_track_section();
+        for (endpoint, segment) in [(track.get_begin(), *first), (track.get_end(), *last)] {
+            for neighbour in graph.get_neighbours(&endpoint) {
+                let neighbour_segment = match track_segments.get::<String>(&neighbour.track) {
+                    Some((first, _)) if neighbour.endpoint == Endpoint::Begin => *first,
+                    Some((_, last)) => *last,
+                    None => continue,
+                };
+                union(&mut parents, segment, neighbour_segment);
+            }
+        }
+    }
+
+    // Group segments by section, sections are ordered by their first segment
+    let mut sections: BTreeMap<usize, Vec<&Segment>> = BTreeMap::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let root = find(&mut parents, index);
+        sections.entry(root).or_default().push(segment);
+    }
+    sections
+        .into_values()
+        .map(|segments| {
+            let mut waypoints: Vec<_> = segments
+                .iter()
+                .flat_map(|segment| segment.waypoints.iter().cloned())
+                .collect();
+            waypoints.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+            waypoints.dedup();
+            let track_ranges = segments
+                .iter()
+                .filter(|segment| segment.end > segment.begin)
+                .map(|segment| TrackRange {
+                    track: segment.track.clone().into(),
+                    begin: segment.begin,
+                    end: segment.end,
+                })
+                .collect();
+            DetectionSection {
+                track_ranges,
+                waypoints,
+            }
+        })
+        .filter(|section| !section.track_ranges.is_empty())
+        .collect()
+}
+
+/// Detectors and buffer stops located on a track with their position
+fn track_waypoints(
+    infra_cache: &InfraCache,
+    track_id: &String,
+    length: f64,
+) -> Vec<(f64, Waypoint)> {
+    let refs = match infra_cache.track_sections_refs.get(track_id) {
+        Some(refs) => refs,
+        None => return vec![],
+    };
+    refs.iter()
+        .filter_map(|obj_ref| match obj_ref.obj_type {
+            ObjectType::Detector => {
+                let detector = infra_cache.detectors().get(&obj_ref.obj_id)?;
+                let position = detector.unwrap_detector().position;
+                Some((position, Waypoint::new_detector(&obj_ref.obj_id)))
+            }
+            ObjectType::BufferStop => {
+                let buffer_stop = infra_cache.buffer_stops().get(&obj_ref.obj_id)?;
+                let position = buffer_stop.unwrap_buffer_stop().position;
+                Some((position, Waypoint::new_buffer_stop(&obj_ref.obj_id)))
+            }
+            _ => None,
+        })
+        .filter(|(position, _)| (0.0..=length).contains(position))
+        .collect()
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    // Path compression
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (root_a, root_b) = (find(parents, a), find(parents, b));
+    // Keep the smallest index as root to get a stable ordering of the sections
+    parents[root_a.max(root_b)] = root_a.min(root_b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_detection_sections;
+    use crate::infra_cache::tests::create_small_infra_cache;
+    use crate::infra_cache::Graph;
+    use crate::schema::Waypoint;
+
+    #[test]
+    fn small_infra_detection_sections() {
+        let infra_cache = create_small_infra_cache();
+        let graph = Graph::load(&infra_cache);
+        let sections = compute_detection_sections(&infra_cache, &graph);
+        let lengths: Vec<_> = sections.iter().map(|section| section.length()).collect();
+        assert_eq!(lengths, vec![20., 730., 1210., 20., 20.]);
+        assert_eq!(
+            sections[1].waypoints,
+            vec![
+                Waypoint::new_buffer_stop("BF1"),
+                Waypoint::new_detector("D1")
+            ]
+        );
+        assert_eq!(sections[2].track_ranges.len(), 3);
+    }
+}
@@ -82,6 +82,14 @@ impl<'a> Graph<'a> {
             .and_then(|groups| groups.get(&group).copied())
     }
 
+    /// Given an endpoint return all its neighbour endpoints whatever the group.
+    pub fn get_neighbours(&self, track_endpoint: &TrackEndpoint) -> Vec<&'a TrackEndpoint> {
+        self.links
+            .get(track_endpoint)
+            .map(|groups| groups.values().copied().collect())
+            .unwrap_or_default()
+    }
+
     /// Given an endpoint return a list of groups.
     /// If the endpoint has no neightbours return an empty `Vec`.
     /// If the endpoint has as simple track section link return a `Vec` with a single `None` element.
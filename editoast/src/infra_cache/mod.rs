@@ -1,3 +1,4 @@
+mod detection_sections;
 mod graph;
 
 use crate::error::Result;
@@ -5,12 +6,13 @@ use crate::infra::Infra;
 use crate::schema::operation::{OperationResult, RailjsonObject};
 use crate::schema::*;
 use chashmap::{CHashMap, ReadGuard, WriteGuard};
-use diesel::sql_types::{BigInt, Double, Text};
+use diesel::sql_types::{BigInt, Double, Nullable, Text};
 use diesel::PgConnection;
 use diesel::{sql_query, QueryableByName, RunQueryDsl};
 use enum_map::EnumMap;
 use std::collections::{HashMap, HashSet};
 
+pub use detection_sections::{compute_detection_sections, DetectionSection};
 pub use graph::Graph;
 
 /// Contains infra cached data used to generate layers and errors
@@ -205,6 +207,12 @@ pub struct TrackQueryable {
     pub geo: String,
     #[diesel(sql_type = Text)]
     pub sch: String,
+    #[diesel(sql_type = Text)]
+    pub slopes: String,
+    #[diesel(sql_type = Text)]
+    pub curves: String,
+    #[diesel(sql_type = Text)]
+    pub loading_gauge_limits: String,
 }
 
 impl From<TrackQueryable> for TrackSectionCache {
@@ -216,6 +224,39 @@ impl From<TrackQueryable> for TrackSectionCache {
             length: track.length,
             bbox_geo: geo.get_bbox(),
             bbox_sch: sch.get_bbox(),
+            geo,
+            slopes: serde_json::from_str(&track.slopes).unwrap(),
+            curves: serde_json::from_str(&track.curves).unwrap(),
+            loading_gauge_limits: serde_json::from_str(&track.loading_gauge_limits).unwrap(),
+        }
+    }
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+pub struct SignalQueryable {
+    #[diesel(sql_type = Text)]
+    pub obj_id: String,
+    #[diesel(sql_type = Text)]
+    pub track: String,
+    #[diesel(sql_type = Double)]
+    pub position: f64,
+    #[diesel(sql_type = Text)]
+    pub direction: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub linked_detector: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub logical_signals: String,
+}
+
+impl From<SignalQueryable> for SignalCache {
+    fn from(signal: SignalQueryable) -> Self {
+        Self {
+            obj_id: signal.obj_id,
+            track: signal.track,
+            position: signal.position,
+            direction: serde_json::from_str(&signal.direction).unwrap(),
+            linked_detector: signal.linked_detector,
+            logical_signals: serde_json::from_str(&signal.logical_signals).unwrap(),
         }
     }
 }
@@ -339,7 +380,7 @@ impl InfraCache {
 
         // Load track sections list
         sql_query(
-            "SELECT obj_id, (data->>'length')::float as length, data->>'geo' as geo, data->>'sch' as sch FROM osrd_infra_tracksectionmodel WHERE infra_id = $1",
+            "SELECT obj_id, (data->>'length')::float as length, data->>'geo' as geo, data->>'sch' as sch, data->>'slopes' as slopes, data->>'curves' as curves, COALESCE(data->>'loading_gauge_limits', '[]') as loading_gauge_limits FROM osrd_infra_tracksectionmodel WHERE infra_id = $1",
         )
         .bind::<BigInt, _>(infra_id)
         .load::<TrackQueryable>(conn)?
@@ -347,10 +388,10 @@ impl InfraCache {
 
         // Load signal tracks references
         sql_query(
-            "SELECT obj_id, data->>'track' AS track, (data->>'position')::float AS position FROM osrd_infra_signalmodel WHERE infra_id = $1")
+            "SELECT obj_id, data->>'track' AS track, (data->>'position')::float AS position, (data->'direction')::text AS direction, data->>'linked_detector' AS linked_detector, COALESCE(data->>'logical_signals', '[]') AS logical_signals FROM osrd_infra_signalmodel WHERE infra_id = $1")
         .bind::<BigInt, _>(infra_id)
-        .load::<SignalCache>(conn)?.into_iter().for_each(|signal|
-            infra_cache.add(signal)
+        .load::<SignalQueryable>(conn)?.into_iter().for_each(|signal|
+            infra_cache.add::<SignalCache>(signal.into())
         );
 
         // Load speed sections tracks references
@@ -533,8 +574,8 @@ pub mod tests {
     use crate::schema::utils::Identifier;
     use crate::schema::{
         ApplicableDirections, ApplicableDirectionsTrackRange, Catenary, Direction, Endpoint,
-        OSRDIdentified, OperationalPoint, OperationalPointPart, Route, SpeedSection, Switch,
-        SwitchPortConnection, SwitchType, TrackEndpoint, TrackSectionLink, Waypoint,
+        LineString, OSRDIdentified, OperationalPoint, OperationalPointPart, Route, SpeedSection,
+        Switch, SwitchPortConnection, SwitchType, TrackEndpoint, TrackSectionLink, Waypoint,
     };
 
     use super::{
@@ -691,12 +732,20 @@ pub mod tests {
         })
     }
 
+    /// Create a track section cache whose geometry lies on the equator and matches its length
     pub fn create_track_section_cache<T: AsRef<str>>(obj_id: T, length: f64) -> TrackSectionCache {
+        let longitude = (length / 6_371_008.8).to_degrees();
         TrackSectionCache {
             obj_id: obj_id.as_ref().into(),
             length,
             bbox_geo: BoundingBox::default(),
             bbox_sch: BoundingBox::default(),
+            geo: LineString::LineString {
+                coordinates: vec![[0., 0.], [longitude, 0.]],
+            },
+            slopes: vec![],
+            curves: vec![],
+            loading_gauge_limits: vec![],
         }
     }
 
@@ -712,6 +761,9 @@ pub mod tests {
             obj_id: obj_id.as_ref().into(),
             track: track.as_ref().into(),
             position,
+            direction: Direction::StartToStop,
+            linked_detector: None,
+            logical_signals: vec![],
         }
     }
 
@@ -854,8 +906,20 @@ pub mod tests {
     pub fn create_small_infra_cache() -> InfraCache {
         let mut infra_cache = InfraCache::default();
 
-        for id in 'A'..='D' {
-            infra_cache.add(create_track_section_cache(id.to_string(), 500.))
+        // Geometries are set so that linked track endpoints are co-located:
+        // A and B are aligned, followed by C going east and D going north
+        let degrees = (500. / 6_371_008.8_f64).to_degrees();
+        for (id, begin, end) in [
+            ("A", [0., 0.], [degrees, 0.]),
+            ("B", [degrees, 0.], [2. * degrees, 0.]),
+            ("C", [2. * degrees, 0.], [3. * degrees, 0.]),
+            ("D", [2. * degrees, 0.], [2. * degrees, degrees]),
+        ] {
+            let mut track = create_track_section_cache(id, 500.);
+            track.geo = LineString::LineString {
+                coordinates: vec![begin, end],
+            };
+            infra_cache.add(track);
         }
 
         infra_cache.add(create_detector_cache("D1", "B", 250.));
@@ -8,10 +8,13 @@ mod infra;
 mod infra_cache;
 mod map;
 mod schema;
+mod schematic;
 mod tables;
 mod views;
 
-use crate::schema::RailJson;
+use crate::error::InternalError;
+use crate::schema::{migrate_railjson, persist_railjson_file, RailJson};
+use crate::schematic::schematic_operations;
 use actix_cors::Cors;
 use actix_web::middleware::{Logger, NormalizePath};
 use actix_web::web::{Data, JsonConfig};
@@ -19,18 +22,22 @@ use actix_web::{App, HttpServer};
 use chashmap::CHashMap;
 use clap::Parser;
 use client::{
-    ClearArgs, Client, Commands, GenerateArgs, ImportRailjsonArgs, PostgresConfig, RedisConfig,
-    RunserverArgs,
+    ClearArgs, Client, Commands, ExportTilesArgs, GenerateArgs, GenerateSchematicArgs,
+    ImportRailjsonArgs, MigrateRailjsonArgs, PostgresConfig, RunserverArgs, SeedTilesArgs,
+    TilesArchiveFormat, ValidationConfig,
 };
 use colored::*;
 use diesel::r2d2::{self, ConnectionManager, Pool};
 use diesel::{Connection, PgConnection};
 use infra::Infra;
 use infra_cache::InfraCache;
-use map::MapLayers;
+use map::{
+    MapLayers, MbtilesWriter, PmtilesWriter, SeedOptions, TileCache, TileCacheStats, TileWriter,
+};
 use std::error::Error;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
+use std::ops::RangeInclusive;
 use std::process::exit;
 use views::search::config::Config as SearchConfig;
 
@@ -50,13 +57,22 @@ async fn main() {
 async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
     let client = Client::parse();
     let pg_config = client.postgres_config;
-    let redis_config = client.redis_config;
+    let tile_cache = TileCache::new(&client.tile_cache_config, &client.redis_config)?;
+    let validation_config = client.validation_config;
 
     match client.command {
-        Commands::Runserver(args) => runserver(args, pg_config, redis_config).await,
-        Commands::Generate(args) => generate(args, pg_config, redis_config).await,
-        Commands::Clear(args) => clear(args, pg_config, redis_config).await,
-        Commands::ImportRailjson(args) => import_railjson(args, pg_config),
+        Commands::Runserver(args) => {
+            runserver(args, pg_config, tile_cache, validation_config).await
+        }
+        Commands::Generate(args) => generate(args, pg_config, tile_cache, validation_config).await,
+        Commands::Clear(args) => clear(args, pg_config, tile_cache).await,
+        Commands::ImportRailjson(args) => import_railjson(args, pg_config, validation_config),
+        Commands::MigrateRailjson(args) => migrate_railjson_file(args),
+        Commands::GenerateSchematic(args) => {
+            generate_schematic(args, pg_config, tile_cache, validation_config).await
+        }
+        Commands::SeedTiles(args) => seed_tiles(args, pg_config, tile_cache).await,
+        Commands::ExportTiles(args) => export_tiles(args, pg_config).await,
     }
 }
 
@@ -64,7 +80,8 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
 async fn runserver(
     args: RunserverArgs,
     pg_config: PostgresConfig,
-    redis_config: RedisConfig,
+    tile_cache: TileCache,
+    validation_config: ValidationConfig,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     println!("Building server...");
     // Config databases
@@ -73,7 +90,6 @@ async fn runserver(
         .max_size(pg_config.pool_size)
         .build(manager)
         .expect("Failed to create pool.");
-    let redis = redis::Client::open(redis_config.redis_url.clone()).unwrap();
 
     // Custom Json extractor configuration
     let json_cfg = JsonConfig::default()
@@ -85,6 +101,7 @@ async fn runserver(
 
     // Setup shared states
     let infra_caches = Data::new(CHashMap::<i64, InfraCache>::default());
+    let tile_cache_stats = Data::new(TileCacheStats::default());
 
     let server = HttpServer::new(move || {
         // Build CORS
@@ -99,10 +116,13 @@ async fn runserver(
             .wrap(Logger::default())
             .app_data(json_cfg.clone())
             .app_data(Data::new(pool.clone()))
-            .app_data(Data::new(redis.clone()))
+            .app_data(Data::new(tile_cache.clone()))
+            .app_data(tile_cache_stats.clone())
             .app_data(infra_caches.clone())
             .app_data(Data::new(MapLayers::parse()))
             .app_data(Data::new(args.map_layers_config.clone()))
+            .app_data(Data::new(args.railjson_config.clone()))
+            .app_data(Data::new(validation_config.clone()))
             .app_data(Data::new(SearchConfig::parse()))
             .service(views::routes())
     });
@@ -116,15 +136,13 @@ async fn runserver(
     Ok(())
 }
 
-async fn build_redis_pool_and_invalidate_all_cache(redis_url: &str, infra_id: i64) {
-    let redis = redis::Client::open(redis_url).unwrap();
-    let mut conn = redis.get_tokio_connection_manager().await.unwrap();
+async fn invalidate_all_cache(tile_cache: &TileCache, infra_id: i64) -> Result<(), InternalError> {
     map::invalidate_all(
-        &mut conn,
+        tile_cache,
         &MapLayers::parse().layers.keys().cloned().collect(),
         infra_id,
     )
-    .await;
+    .await
 }
 
 /// Run the generate sub command
@@ -132,7 +150,8 @@ async fn build_redis_pool_and_invalidate_all_cache(redis_url: &str, infra_id: i6
 async fn generate(
     args: GenerateArgs,
     pg_config: PostgresConfig,
-    redis_config: RedisConfig,
+    tile_cache: TileCache,
+    validation_config: ValidationConfig,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut conn = PgConnection::establish(&pg_config.url()).expect("Error while connecting DB");
 
@@ -157,8 +176,8 @@ async fn generate(
             infra.id
         );
         let infra_cache = InfraCache::load(&mut conn, &infra)?;
-        if infra.refresh(&mut conn, args.force, &infra_cache)? {
-            build_redis_pool_and_invalidate_all_cache(&redis_config.redis_url, infra.id).await;
+        if infra.refresh(&mut conn, args.force, &infra_cache, &validation_config)? {
+            invalidate_all_cache(&tile_cache, infra.id).await?;
             println!("✅ Infra {}[{}] generated!", infra.name.bold(), infra.id);
         } else {
             println!(
@@ -174,20 +193,19 @@ async fn generate(
 fn import_railjson(
     args: ImportRailjsonArgs,
     pg_config: PostgresConfig,
+    validation_config: ValidationConfig,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let railjson_file = File::open(args.railjson_path)?;
     let conn = &mut PgConnection::establish(&pg_config.url()).expect("Error while connecting DB");
 
-    let railjson: RailJson = serde_json::from_reader(BufReader::new(railjson_file))?;
-
-    let infra = railjson.persist(args.infra_name, conn)?;
+    let infra = persist_railjson_file(railjson_file, args.infra_name, conn)?;
     let infra = infra.bump_version(conn)?;
 
     println!("✅ Infra {}[{}] saved!", infra.name.bold(), infra.id);
     // Generate only if the was set
     if args.generate {
         let infra_cache = InfraCache::load(conn, &infra)?;
-        infra.refresh(conn, true, &infra_cache)?;
+        infra.refresh(conn, true, &infra_cache, &validation_config)?;
         println!(
             "✅ Infra {}[{}] generated data refreshed!",
             infra.name.bold(),
@@ -198,12 +216,75 @@ fn import_railjson(
     Ok(())
 }
 
+/// Run the migrate-railjson subcommand
+/// This command upgrades a railjson file in place to the current version
+fn migrate_railjson_file(args: MigrateRailjsonArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let railjson_file = File::open(&args.railjson_path)?;
+    let mut railjson = serde_json::from_reader(BufReader::new(railjson_file))?;
+
+    let reports = migrate_railjson(&mut railjson)?;
+    if reports.is_empty() {
+        println!("✅ Railjson is already up to date!");
+        return Ok(());
+    }
+
+    // Make sure the migrated railjson is valid before overwriting the file
+    let railjson = RailJson::from_value(railjson)?;
+    let railjson_file = File::create(&args.railjson_path)?;
+    serde_json::to_writer(BufWriter::new(railjson_file), &railjson)?;
+
+    for report in reports {
+        println!("🍞 Migrated from {} to {}:", report.from, report.to.bold());
+        for change in report.changes {
+            println!("  - {change}");
+        }
+    }
+    println!("✅ Railjson migrated!");
+    Ok(())
+}
+
+/// Run the generate-schematic subcommand
+/// This command computes the schematic geometry of the tracks of the given infras and refreshes their generated data
+async fn generate_schematic(
+    args: GenerateSchematicArgs,
+    pg_config: PostgresConfig,
+    tile_cache: TileCache,
+    validation_config: ValidationConfig,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut conn = PgConnection::establish(&pg_config.url()).expect("Error while connecting DB");
+
+    for id in args.infra_ids {
+        let infra = Infra::retrieve(&mut conn, id as i64)?;
+        println!(
+            "🍞 Infra {}[{}] schematic is generating:",
+            infra.name.bold(),
+            infra.id
+        );
+        let infra = conn.transaction::<_, InternalError, _>(|conn| {
+            let infra = Infra::retrieve_for_update(conn, infra.id)?;
+            for operation in schematic_operations(conn, infra.id) {
+                operation.apply(infra.id, conn)?;
+            }
+            infra.bump_version(conn)
+        })?;
+        let infra_cache = InfraCache::load(&mut conn, &infra)?;
+        infra.refresh(&mut conn, true, &infra_cache, &validation_config)?;
+        invalidate_all_cache(&tile_cache, infra.id).await?;
+        println!(
+            "✅ Infra {}[{}] schematic generated!",
+            infra.name.bold(),
+            infra.id
+        );
+    }
+    Ok(())
+}
+
 /// Run the clear subcommand
 /// This command clear all generated data for the given infra
 async fn clear(
     args: ClearArgs,
     pg_config: PostgresConfig,
-    redis_config: RedisConfig,
+    tile_cache: TileCache,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut conn = PgConnection::establish(&pg_config.url()).expect("Error while connecting DB");
     let mut infras = vec![];
@@ -221,13 +302,179 @@ async fn clear(
 
     for infra in infras {
         println!("🍞 Infra {}[{}] is clearing:", infra.name.bold(), infra.id);
-        build_redis_pool_and_invalidate_all_cache(&redis_config.redis_url, infra.id).await;
+        invalidate_all_cache(&tile_cache, infra.id).await?;
         infra.clear(&mut conn)?;
         println!("✅ Infra {}[{}] cleared!", infra.name.bold(), infra.id);
     }
     Ok(())
 }
 
+/// Run the seed-tiles subcommand
+/// This command renders the map tiles covering the given infras (all of them if none given) and stores them in the cache
+async fn seed_tiles(
+    args: SeedTilesArgs,
+    pg_config: PostgresConfig,
+    tile_cache: TileCache,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let manager = ConnectionManager::<PgConnection>::new(pg_config.url());
+    let pool = Pool::builder()
+        .max_size(pg_config.pool_size)
+        .build(manager)
+        .expect("Failed to create pool.");
+
+    let map_layers = MapLayers::parse();
+    if let Some(layer) = args
+        .layers
+        .iter()
+        .find(|layer| !map_layers.layers.contains_key(*layer))
+    {
+        return Err(format!("Unknown layer '{layer}'").into());
+    }
+
+    let mut conn = pool.get()?;
+    let infras = if args.infra_ids.is_empty() {
+        Infra::list(&mut conn)
+    } else {
+        let mut infras = vec![];
+        for id in args.infra_ids {
+            infras.push(Infra::retrieve(&mut conn, id as i64)?);
+        }
+        infras
+    };
+    drop(conn);
+
+    let options = SeedOptions {
+        layers: args.layers,
+        zoom_range: args.min_zoom..=args.max_zoom,
+        concurrency: args.concurrency,
+    };
+    for infra in infras {
+        println!(
+            "🍞 Infra {}[{}] tiles are seeding:",
+            infra.name.bold(),
+            infra.id
+        );
+        let seeded = map::seed_infra(
+            &pool,
+            &tile_cache,
+            &map_layers,
+            infra.id,
+            &options,
+            |progress| {
+                // Report every 1000 tiles and when a zoom level of a layer view is done
+                if progress.seeded % 1000 == 0 || progress.seeded == progress.total {
+                    println!(
+                        "  - {}/{} z{}: {}/{} tiles",
+                        progress.layer_name,
+                        progress.view_name,
+                        progress.zoom,
+                        progress.seeded,
+                        progress.total
+                    );
+                }
+            },
+        )
+        .await?;
+        println!(
+            "✅ Infra {}[{}] {} tiles seeded!",
+            infra.name.bold(),
+            infra.id,
+            seeded
+        );
+    }
+    Ok(())
+}
+
+/// Run the export-tiles subcommand
+/// This command renders all the map layers of an infra and writes them in a MBTiles or PMTiles archive
+async fn export_tiles(
+    args: ExportTilesArgs,
+    pg_config: PostgresConfig,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let manager = ConnectionManager::<PgConnection>::new(pg_config.url());
+    let pool = Pool::builder()
+        .max_size(pg_config.pool_size)
+        .build(manager)
+        .expect("Failed to create pool.");
+    let mut conn = pool.get()?;
+    let infra = Infra::retrieve(&mut conn, args.infra as i64)?;
+    drop(conn);
+    let output = args.output.unwrap_or_else(|| match args.format {
+        TilesArchiveFormat::Mbtiles => format!("infra_{}.mbtiles", infra.id).into(),
+        TilesArchiveFormat::Pmtiles => format!("infra_{}.pmtiles", infra.id).into(),
+    });
+
+    println!(
+        "🍞 Infra {}[{}] tiles are rendering:",
+        infra.name.bold(),
+        infra.id
+    );
+    let map_layers = MapLayers::parse();
+    let exported_tiles = match args.format {
+        TilesArchiveFormat::Mbtiles => {
+            let writer = MbtilesWriter::create(&output)?;
+            export_tiles_archive(
+                &pool,
+                &map_layers,
+                infra.id,
+                args.zoom,
+                args.concurrency,
+                writer,
+            )
+            .await?
+        }
+        TilesArchiveFormat::Pmtiles => {
+            let writer = PmtilesWriter::create(&output)?;
+            export_tiles_archive(
+                &pool,
+                &map_layers,
+                infra.id,
+                args.zoom,
+                args.concurrency,
+                writer,
+            )
+            .await?
+        }
+    };
+    println!(
+        "✅ Infra {}[{}] {} tiles exported to {}",
+        infra.name.bold(),
+        infra.id,
+        exported_tiles,
+        output.display()
+    );
+    Ok(())
+}
+
+/// Renders the tiles of an infra in an archive, printing the rendering progress
+async fn export_tiles_archive<W: TileWriter>(
+    pool: &DbPool,
+    map_layers: &MapLayers,
+    infra_id: i64,
+    zoom_range: RangeInclusive<u64>,
+    concurrency: usize,
+    writer: W,
+) -> error::Result<usize> {
+    map::export_infra(
+        pool,
+        map_layers,
+        infra_id,
+        zoom_range,
+        concurrency,
+        writer,
+        |progress| {
+            // Report every 1000 tiles and when a zoom level is done
+            if progress.rendered % 1000 == 0 || progress.rendered == progress.total {
+                println!(
+                    "  - zoom {}: {}/{} tiles",
+                    progress.zoom, progress.rendered, progress.total
+                );
+            }
+        },
+    )
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use crate::client::{ImportRailjsonArgs, PostgresConfig};
@@ -261,7 +508,7 @@ mod tests {
         };
 
         // WHEN
-        let result = import_railjson(args, pg_config);
+        let result = import_railjson(args, pg_config, Default::default());
 
         // THEN
         assert!(result.is_err())
@@ -287,7 +534,7 @@ mod tests {
         };
 
         // WHEN
-        let result = import_railjson(args, pg_config.clone());
+        let result = import_railjson(args, pg_config.clone(), Default::default());
 
         // THEN
         assert!(result.is_ok());
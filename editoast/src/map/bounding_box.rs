@@ -36,9 +36,9 @@ pub struct InvalidationZone {
 }
 
 impl InvalidationZone {
-    /// Whether zones are valid
+    /// Whether one of the zones is valid
     pub fn is_valid(&self) -> bool {
-        self.geo.is_valid()
+        self.geo.is_valid() || self.sch.is_valid()
     }
 }
 
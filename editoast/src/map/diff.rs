@@ -0,0 +1,111 @@
+use diesel::sql_types::Integer;
+use diesel::{sql_query, PgConnection, RunQueryDsl};
+use strum::IntoEnumIterator;
+
+use super::mvt_utils::{create_and_fill_mvt_tile, GeoJsonAndData};
+use super::Tile;
+use crate::error::Result;
+use crate::schema::ObjectType;
+
+/// Name of the MVT layer of the comparison tiles
+pub const DIFF_LAYER_NAME: &str = "diff";
+
+/// Creates an SQL query selecting the objects that differ between an infra (`$4`) and a base infra (`$5`)
+/// in a tile (`$1`, `$2`, `$3`)
+///
+/// Objects are compared by `obj_id` on their `data`. The `change` property of a record is `added`,
+/// `removed` or `modified`. Removed objects use the geometry of the base infra, the others the one
+/// of the compared infra.
+///
+/// # Arguments
+///
+/// * `on_field` - Geometry field of the layer tables, `geographic` or `schematic`
+pub fn get_diff_sql_query(on_field: &str) -> String {
+    let object_queries: Vec<_> = ObjectType::iter()
+        .filter_map(|obj_type| {
+            let layer_table = obj_type.get_geometry_layer_table()?;
+            Some(format!(
+                "
+            SELECT ST_AsGeoJson(layer.{on_field}) AS geo_json,
+                jsonb_build_object(
+                    'id', layer.obj_id,
+                    'obj_type', '{obj_type}',
+                    'change', CASE
+                        WHEN base.obj_id IS NULL THEN 'added'
+                        WHEN infra.obj_id IS NULL THEN 'removed'
+                        ELSE 'modified'
+                    END
+                ) AS data
+            FROM {layer_table} layer
+                CROSS JOIN bbox
+                LEFT JOIN {model_table} infra ON infra.infra_id = $4 AND infra.obj_id = layer.obj_id
+                LEFT JOIN {model_table} base ON base.infra_id = $5 AND base.obj_id = layer.obj_id
+            WHERE layer.infra_id IN ($4, $5)
+                AND (layer.infra_id = $4 OR infra.obj_id IS NULL)
+                AND infra.data IS DISTINCT FROM base.data
+                AND layer.{on_field} && bbox.geom
+                AND ST_GeometryType(layer.{on_field}) != 'ST_GeometryCollection'",
+                model_table = obj_type.get_table(),
+            ))
+        })
+        .collect();
+    format!(
+        "
+        WITH bbox AS (
+            SELECT TileBBox($1, $2, $3, 3857) AS geom
+        )
+        {}
+        ",
+        object_queries.join("\n            UNION ALL")
+    )
+}
+
+/// Renders the objects that differ between an infra and a base infra in a MVT tile
+///
+/// # Arguments
+///
+/// * `conn` - Database connection
+/// * `on_field` - Geometry field of the layer tables, `geographic` or `schematic`
+/// * `infra_id` - Compared infra
+/// * `base_infra_id` - Infra the compared infra is compared to
+/// * `tile` - Tile to render
+pub fn render_diff_mvt_tile(
+    conn: &mut PgConnection,
+    on_field: &str,
+    infra_id: i64,
+    base_infra_id: i64,
+    tile: &Tile,
+) -> Result<Vec<u8>> {
+    let records = sql_query(get_diff_sql_query(on_field))
+        .bind::<Integer, _>(tile.z as i32)
+        .bind::<Integer, _>(tile.x as i32)
+        .bind::<Integer, _>(tile.y as i32)
+        .bind::<Integer, _>(infra_id as i32)
+        .bind::<Integer, _>(base_infra_id as i32)
+        .get_results::<GeoJsonAndData>(conn)?;
+    Ok(
+        create_and_fill_mvt_tile(tile.z, tile.x, tile.y, DIFF_LAYER_NAME, records)
+            .to_bytes()
+            .unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_diff_sql_query;
+
+    #[test]
+    fn diff_query_creation() {
+        let query = get_diff_sql_query("schematic");
+        // Only object types with a geometry layer are compared
+        assert_eq!(query.matches("SELECT ST_AsGeoJson").count(), 9);
+        assert_eq!(query.matches("UNION ALL").count(), 8);
+        assert!(query.contains("FROM osrd_infra_signallayer layer"));
+        assert!(query.contains(
+            "LEFT JOIN osrd_infra_signalmodel base ON base.infra_id = $5 AND base.obj_id = layer.obj_id"
+        ));
+        assert!(query.contains("'obj_type', 'Signal'"));
+        assert!(query.contains("AND layer.schematic && bbox.geom"));
+        assert!(!query.contains("geographic"));
+    }
+}
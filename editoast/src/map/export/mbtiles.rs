@@ -0,0 +1,136 @@
+use std::fs::remove_file;
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use super::{TileArchive, TileWriter, TilesExportError};
+use crate::error::Result;
+use crate::map::Tile;
+
+/// Writes tiles in a MBTiles file as they are rendered
+///
+/// MBTiles specification: <https://github.com/mapbox/mbtiles-spec/blob/master/1.3/spec.md>
+pub struct MbtilesWriter {
+    conn: Connection,
+}
+
+impl MbtilesWriter {
+    /// Creates the MBTiles file, the file is replaced if it exists
+    pub fn create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            remove_file(path).map_err(|err| TilesExportError::WriteFailed(err.to_string()))?;
+        }
+        let conn =
+            create_database(path).map_err(|err| TilesExportError::WriteFailed(err.to_string()))?;
+        Ok(Self { conn })
+    }
+}
+
+impl TileWriter for MbtilesWriter {
+    fn write_tile(&mut self, tile: &Tile, data: &[u8]) -> Result<()> {
+        insert_tile(&self.conn, tile, data)
+            .map_err(|err| TilesExportError::WriteFailed(err.to_string()))?;
+        Ok(())
+    }
+
+    fn finish(self, archive: &TileArchive) -> Result<()> {
+        write_metadata(&self.conn, archive)
+            .map_err(|err| TilesExportError::WriteFailed(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Creates the tables of the archive, tiles are inserted in a single transaction
+fn create_database(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(
+        "CREATE TABLE metadata (name TEXT, value TEXT);
+        CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+        CREATE UNIQUE INDEX tile_index ON tiles (zoom_level, tile_column, tile_row);
+        BEGIN;",
+    )?;
+    Ok(conn)
+}
+
+fn insert_tile(conn: &Connection, tile: &Tile, data: &[u8]) -> rusqlite::Result<()> {
+    let mut insert_tile = conn.prepare_cached(
+        "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    // MBTiles rows follow the TMS scheme, y axis is flipped
+    let tile_row = (1 << tile.z) - 1 - tile.y;
+    insert_tile.execute(params![tile.z, tile.x, tile_row, data])?;
+    Ok(())
+}
+
+/// Inserts the metadata of the archive and commits the tiles
+fn write_metadata(conn: &Connection, archive: &TileArchive) -> rusqlite::Result<()> {
+    let mut insert_metadata = conn.prepare("INSERT INTO metadata (name, value) VALUES (?1, ?2)")?;
+    let metadata = archive.metadata();
+    for (name, value) in metadata.as_object().unwrap() {
+        let value = match (name.as_str(), value) {
+            ("vector_layers" | "tilejson", _) => continue,
+            ("bounds" | "center", serde_json::Value::Array(values)) => values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            (_, serde_json::Value::String(value)) => value.clone(),
+            (_, value) => value.to_string(),
+        };
+        insert_metadata.execute(params![name, value])?;
+    }
+    // Vector tiles layers are described in a json row
+    let json = serde_json::json!({
+        "vector_layers": metadata["vector_layers"],
+        "tilejson": metadata["tilejson"],
+    });
+    insert_metadata.execute(params!["json", json.to_string()])?;
+    conn.execute_batch("COMMIT")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use rusqlite::Connection;
+
+    use super::MbtilesWriter;
+    use crate::map::export::{TileArchive, TileWriter};
+    use crate::map::{BoundingBox, Tile};
+
+    #[test]
+    fn write_archive() {
+        let archive = TileArchive {
+            name: "infra_1".into(),
+            zoom_range: 0..=1,
+            bounds: Some(BoundingBox((2., 48.), (3., 49.))),
+            tilejsons: Default::default(),
+        };
+        let path = temp_dir().join("editoast_write_mbtiles_test.mbtiles");
+        let mut writer = MbtilesWriter::create(&path).unwrap();
+        writer
+            .write_tile(&Tile { x: 1, y: 0, z: 1 }, &[42])
+            .unwrap();
+        writer.finish(&archive).unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let (column, row, data): (u64, u64, Vec<u8>) = conn
+            .query_row(
+                "SELECT tile_column, tile_row, tile_data FROM tiles WHERE zoom_level = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((column, row, data), (1, 1, vec![42]));
+        let bounds: String = conn
+            .query_row(
+                "SELECT value FROM metadata WHERE name = 'bounds'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(bounds, "2.0,48.0,3.0,49.0");
+        drop(conn);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+mod mbtiles;
+mod pmtiles;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use actix_web::web::block;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::{stream, StreamExt};
+use serde_json::{json, Value as JsonValue};
+
+use super::seed::get_view_extent;
+use super::{
+    get_tiles, render_mvt_tile, BoundingBox, Layer, MapLayers, Tile, TileFilters, TileRange, View,
+};
+use crate::error::Result;
+use crate::DbPool;
+use editoast_derive::EditoastError;
+use thiserror::Error;
+
+pub use mbtiles::MbtilesWriter;
+pub use pmtiles::PmtilesWriter;
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "tiles_export", default_status = 500)]
+pub enum TilesExportError {
+    #[error("Failed to write tiles archive: {0}")]
+    WriteFailed(String),
+}
+
+/// Progress of the rendering of a zoom level
+#[derive(Debug)]
+pub struct ExportProgress {
+    pub zoom: u64,
+    pub rendered: usize,
+    /// Number of tiles to render at this zoom level, children of empty tiles are skipped
+    pub total: usize,
+}
+
+/// Destination of the exported tiles, written as soon as they are rendered
+pub trait TileWriter {
+    /// Writes a gzipped MVT tile, each layer view is a distinct layer named `{layer}_{view}`
+    fn write_tile(&mut self, tile: &Tile, data: &[u8]) -> Result<()>;
+
+    /// Writes the archive description once all the tiles are written
+    fn finish(self, archive: &TileArchive) -> Result<()>;
+}
+
+/// Description of an exported tiles archive
+#[derive(Debug)]
+pub struct TileArchive {
+    pub name: String,
+    pub zoom_range: RangeInclusive<u64>,
+    /// Extent of the exported layers, `None` if the infra is empty
+    pub bounds: Option<BoundingBox>,
+    /// TileJSON of the exported layer views indexed by their tiles layer name
+    pub tilejsons: BTreeMap<String, JsonValue>,
+}
+
+impl TileArchive {
+    /// Center of the archive bounds at the lowest zoom level
+    fn center(&self) -> Option<(f64, f64, u64)> {
+        self.bounds.as_ref().map(|bbox| {
+            (
+                (bbox.0 .0 + bbox.1 .0) / 2.,
+                (bbox.0 .1 + bbox.1 .1) / 2.,
+                *self.zoom_range.start(),
+            )
+        })
+    }
+
+    /// Metadata describing the archive content, following the MBTiles specification
+    pub fn metadata(&self) -> JsonValue {
+        let vector_layers: Vec<_> = self
+            .tilejsons
+            .keys()
+            .map(|name| {
+                json!({
+                    "id": name,
+                    "fields": {},
+                    "minzoom": self.zoom_range.start(),
+                    "maxzoom": self.zoom_range.end(),
+                })
+            })
+            .collect();
+        let mut metadata = json!({
+            "name": self.name,
+            "format": "pbf",
+            "type": "overlay",
+            "minzoom": self.zoom_range.start(),
+            "maxzoom": self.zoom_range.end(),
+            "vector_layers": vector_layers,
+            "tilejson": self.tilejsons,
+        });
+        if let Some(bbox) = &self.bounds {
+            metadata["bounds"] = json!([bbox.0 .0, bbox.0 .1, bbox.1 .0, bbox.1 .1]);
+        }
+        if let Some((lon, lat, zoom)) = self.center() {
+            metadata["center"] = json!([lon, lat, zoom]);
+        }
+        metadata
+    }
+}
+
+/// Compress data using gzip
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Layer view exported in an archive
+struct ExportedView {
+    /// Name of the view in the tiles (`{layer}_{view}`)
+    tiles_layer_name: String,
+    layer: Layer,
+    view: View,
+    bbox: BoundingBox,
+}
+
+/// Renders all the layer views of an infra for the given zoom levels and writes them in an archive
+///
+/// The tile pyramid is descended from the lowest zoom level, the children of empty tiles are skipped.
+///
+/// # Arguments
+///
+/// * `db_pool` - Pool used to render the tiles
+/// * `map_layers` - Layers description
+/// * `infra_id` - Infra to export
+/// * `zoom_range` - Zoom levels to render
+/// * `concurrency` - Number of tiles rendered concurrently
+/// * `writer` - Archive the tiles are written in
+/// * `progress` - Called each time a tile is rendered
+///
+/// Returns the number of written tiles
+pub async fn export_infra<W: TileWriter, F: FnMut(ExportProgress)>(
+    db_pool: &DbPool,
+    map_layers: &MapLayers,
+    infra_id: i64,
+    zoom_range: RangeInclusive<u64>,
+    concurrency: usize,
+    mut writer: W,
+    mut progress: F,
+) -> Result<usize> {
+    let mut layer_names: Vec<_> = map_layers.layers.keys().cloned().collect();
+    layer_names.sort();
+
+    let mut views = vec![];
+    let mut bounds: Option<BoundingBox> = None;
+    let mut tilejsons = BTreeMap::new();
+    for layer_name in layer_names {
+        let layer = &map_layers.layers[&layer_name];
+        let mut view_names: Vec<_> = layer.views.keys().cloned().collect();
+        view_names.sort();
+        for view_name in view_names {
+            let view = &layer.views[&view_name];
+            let bbox = {
+                let (db_pool, layer, view) = (db_pool.clone(), layer.clone(), view.clone());
+                block(move || {
+                    let mut conn = db_pool.get().expect("Failed to get DB connection");
+                    get_view_extent(&mut conn, &layer, &view, infra_id)
+                })
+                .await
+                .unwrap()?
+            };
+            let bbox = match bbox {
+                Some(bbox) if bbox.is_valid() => bbox,
+                _ => continue,
+            };
+            bounds.get_or_insert_with(BoundingBox::default).union(&bbox);
+
+            let tiles_layer_name = format!("{layer_name}_{view_name}");
+            tilejsons.insert(
+                tiles_layer_name.clone(),
+                layer.tilejson(&tiles_layer_name, vec![], zoom_range.clone()),
+            );
+            views.push(ExportedView {
+                tiles_layer_name,
+                layer: layer.clone(),
+                view: view.clone(),
+                bbox,
+            });
+        }
+    }
+    let views = Arc::new(views);
+
+    // Tiles to render at the current zoom level, with the indexes of the views to render in them
+    let mut level: Vec<(Tile, Vec<usize>)> = match &bounds {
+        Some(bounds) => {
+            let start = *zoom_range.start();
+            let view_ranges: Vec<_> = views
+                .iter()
+                .map(|view| TileRange::new(start, &view.bbox))
+                .collect();
+            get_tiles(start..=start, bounds)
+                .into_iter()
+                .map(|tile| {
+                    let view_indexes = (0..views.len())
+                        .filter(|&index| view_ranges[index].contains(&tile))
+                        .collect();
+                    (tile, view_indexes)
+                })
+                .collect()
+        }
+        None => vec![],
+    };
+    let mut written_tiles = 0;
+    for zoom in zoom_range.clone() {
+        let total = level.len();
+        let next_view_ranges: Vec<_> = views
+            .iter()
+            .map(|view| TileRange::new(zoom + 1, &view.bbox))
+            .collect();
+        let mut rendered_tiles = stream::iter(level)
+            .map(|(tile, view_indexes)| {
+                let (db_pool, views) = (db_pool.clone(), views.clone());
+                async move {
+                    block::<_, Result<_>>(move || {
+                        let mut conn = db_pool.get().expect("Failed to get DB connection");
+                        let mut rendered_views = vec![];
+                        for index in view_indexes {
+                            let view = &views[index];
+                            let mvt_bytes = render_mvt_tile(
+                                &mut conn,
+                                &view.tiles_layer_name,
+                                &view.layer,
+                                &view.view,
+                                infra_id,
+                                &tile,
+                                &TileFilters::default(),
+                            )?;
+                            rendered_views.push((index, mvt_bytes));
+                        }
+                        Ok((tile, rendered_views))
+                    })
+                    .await
+                    .unwrap()
+                }
+            })
+            .buffer_unordered(concurrency.max(1));
+
+        let mut next_level = vec![];
+        let mut rendered = 0;
+        while let Some(rendered_tile) = rendered_tiles.next().await {
+            let (tile, rendered_views) = rendered_tile?;
+            let mut mvt_bytes = vec![];
+            let mut descended_views = vec![];
+            for (index, view_bytes) in rendered_views {
+                if !view_bytes.is_empty() || views[index].view.may_have_records_below(zoom) {
+                    descended_views.push(index);
+                }
+                // A MVT tile is a list of layers, concatenating tiles merges their layers
+                mvt_bytes.extend(view_bytes);
+            }
+            if !mvt_bytes.is_empty() {
+                writer.write_tile(&tile, &gzip(&mvt_bytes))?;
+                written_tiles += 1;
+            }
+            if zoom < *zoom_range.end() {
+                for child in tile.children() {
+                    let view_indexes: Vec<_> = descended_views
+                        .iter()
+                        .copied()
+                        .filter(|&index| next_view_ranges[index].contains(&child))
+                        .collect();
+                    if !view_indexes.is_empty() {
+                        next_level.push((child, view_indexes));
+                    }
+                }
+            }
+            rendered += 1;
+            progress(ExportProgress {
+                zoom,
+                rendered,
+                total,
+            });
+        }
+        level = next_level;
+    }
+
+    writer.finish(&TileArchive {
+        name: format!("infra_{infra_id}"),
+        zoom_range,
+        bounds,
+        tilejsons,
+    })?;
+    Ok(written_tiles)
+}
@@ -0,0 +1,319 @@
+use std::fs::File;
+use std::io::{copy, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use tempfile::tempfile;
+
+use super::{gzip, TileArchive, TileWriter, TilesExportError};
+use crate::error::Result;
+use crate::map::Tile;
+
+/// Size of the PMTiles header
+const HEADER_SIZE: usize = 127;
+/// The header and the root directory must fit in the first 16 KiB of the archive
+const ROOT_DIRECTORY_MAX_SIZE: usize = 16_384 - HEADER_SIZE;
+const COMPRESSION_GZIP: u8 = 2;
+const TILE_TYPE_MVT: u8 = 1;
+
+/// Entry of a PMTiles directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    tile_id: u64,
+    offset: u64,
+    length: u64,
+    /// Number of consecutive tiles sharing the data, 0 for a leaf directory entry
+    run_length: u64,
+}
+
+/// Computes the PMTiles id of a tile, its position on the hilbert curve of its zoom level
+/// following the tiles of lower zoom levels
+fn tile_id(z: u64, x: u64, y: u64) -> u64 {
+    let mut id = ((1 << (2 * z)) - 1) / 3;
+    let n: u64 = 1 << z;
+    let (mut x, mut y) = (x, y);
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        id += s * s * ((3 * rx) ^ ry);
+        // Rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    id
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// Serializes and compresses a directory
+fn serialize_directory(entries: &[Entry]) -> Vec<u8> {
+    let mut buffer = vec![];
+    write_varint(&mut buffer, entries.len() as u64);
+    let mut last_id = 0;
+    for entry in entries {
+        write_varint(&mut buffer, entry.tile_id - last_id);
+        last_id = entry.tile_id;
+    }
+    for entry in entries {
+        write_varint(&mut buffer, entry.run_length);
+    }
+    for entry in entries {
+        write_varint(&mut buffer, entry.length);
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        // Offsets of contiguous entries are implicit
+        if i > 0 && entry.offset == entries[i - 1].offset + entries[i - 1].length {
+            write_varint(&mut buffer, 0);
+        } else {
+            write_varint(&mut buffer, entry.offset + 1);
+        }
+    }
+    gzip(&buffer)
+}
+
+/// Builds the root directory and the leaf directories of the archive
+/// Leaf directories are only used if the root directory would be too large
+fn build_directories(entries: &[Entry]) -> (Vec<u8>, Vec<u8>) {
+    let root = serialize_directory(entries);
+    if root.len() <= ROOT_DIRECTORY_MAX_SIZE {
+        return (root, vec![]);
+    }
+    let mut leaf_size = 4096;
+    loop {
+        let mut root_entries = vec![];
+        let mut leaves = vec![];
+        for chunk in entries.chunks(leaf_size) {
+            let leaf = serialize_directory(chunk);
+            root_entries.push(Entry {
+                tile_id: chunk[0].tile_id,
+                offset: leaves.len() as u64,
+                length: leaf.len() as u64,
+                run_length: 0,
+            });
+            leaves.extend(leaf);
+        }
+        let root = serialize_directory(&root_entries);
+        if root.len() <= ROOT_DIRECTORY_MAX_SIZE {
+            return (root, leaves);
+        }
+        leaf_size *= 2;
+    }
+}
+
+/// Coordinates in the PMTiles header format
+fn e7(coordinate: f64) -> i32 {
+    (coordinate * 10_000_000.) as i32
+}
+
+/// Writes tiles in a PMTiles (version 3) file as they are rendered
+///
+/// Tiles data are buffered in a temporary file in their rendering order, the archive isn't clustered.
+/// The header and the directories are written before the tiles data once all the tiles are known.
+///
+/// PMTiles specification: <https://github.com/protomaps/PMTiles/blob/main/spec/v3/spec.md>
+pub struct PmtilesWriter {
+    path: PathBuf,
+    tile_data: BufWriter<File>,
+    tile_data_length: u64,
+    entries: Vec<Entry>,
+}
+
+impl PmtilesWriter {
+    /// Creates the writer, the file is replaced if it exists
+    pub fn create(path: &Path) -> Result<Self> {
+        let tile_data = tempfile().map_err(|err| TilesExportError::WriteFailed(err.to_string()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            tile_data: BufWriter::new(tile_data),
+            tile_data_length: 0,
+            entries: vec![],
+        })
+    }
+
+    fn write_archive(self, archive: &TileArchive) -> std::io::Result<()> {
+        let mut tile_data = self.tile_data.into_inner()?;
+        tile_data.rewind()?;
+        let mut entries = self.entries;
+        entries.sort_by_key(|entry| entry.tile_id);
+
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        writer.write_all(&header_and_directories(
+            archive,
+            &entries,
+            self.tile_data_length,
+        ))?;
+        copy(&mut tile_data, &mut writer)?;
+        writer.flush()
+    }
+}
+
+impl TileWriter for PmtilesWriter {
+    fn write_tile(&mut self, tile: &Tile, data: &[u8]) -> Result<()> {
+        self.tile_data
+            .write_all(data)
+            .map_err(|err| TilesExportError::WriteFailed(err.to_string()))?;
+        self.entries.push(Entry {
+            tile_id: tile_id(tile.z, tile.x, tile.y),
+            offset: self.tile_data_length,
+            length: data.len() as u64,
+            run_length: 1,
+        });
+        self.tile_data_length += data.len() as u64;
+        Ok(())
+    }
+
+    fn finish(self, archive: &TileArchive) -> Result<()> {
+        self.write_archive(archive)
+            .map_err(|err| TilesExportError::WriteFailed(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Serializes the header, the directories and the metadata of an archive, the tiles data follow them
+/// `entries` must be sorted by tile id
+fn header_and_directories(
+    archive: &TileArchive,
+    entries: &[Entry],
+    tile_data_length: u64,
+) -> Vec<u8> {
+    let (root_directory, leaf_directories) = build_directories(entries);
+    let metadata = gzip(archive.metadata().to_string().as_bytes());
+
+    let root_offset = HEADER_SIZE as u64;
+    let metadata_offset = root_offset + root_directory.len() as u64;
+    let leaves_offset = metadata_offset + metadata.len() as u64;
+    let data_offset = leaves_offset + leaf_directories.len() as u64;
+
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(b"PMTiles");
+    header.push(3);
+    for value in [
+        root_offset,
+        root_directory.len() as u64,
+        metadata_offset,
+        metadata.len() as u64,
+        leaves_offset,
+        leaf_directories.len() as u64,
+        data_offset,
+        tile_data_length,
+        entries.len() as u64,
+        entries.len() as u64,
+        entries.len() as u64,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header.extend_from_slice(&[
+        0, // Not clustered
+        COMPRESSION_GZIP,
+        COMPRESSION_GZIP,
+        TILE_TYPE_MVT,
+        *archive.zoom_range.start() as u8,
+        *archive.zoom_range.end() as u8,
+    ]);
+    let ((min_lon, min_lat), (max_lon, max_lat)) = archive
+        .bounds
+        .as_ref()
+        .map_or(((-180., -85.), (180., 85.)), |bbox| (bbox.0, bbox.1));
+    for coordinate in [min_lon, min_lat, max_lon, max_lat] {
+        header.extend_from_slice(&e7(coordinate).to_le_bytes());
+    }
+    header.push(*archive.zoom_range.start() as u8);
+    for coordinate in [(min_lon + max_lon) / 2., (min_lat + max_lat) / 2.] {
+        header.extend_from_slice(&e7(coordinate).to_le_bytes());
+    }
+    assert_eq!(header.len(), HEADER_SIZE);
+
+    let mut bytes = header;
+    bytes.extend(root_directory);
+    bytes.extend(metadata);
+    bytes.extend(leaf_directories);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use super::{build_directories, tile_id, Entry, PmtilesWriter, HEADER_SIZE};
+    use crate::map::export::{TileArchive, TileWriter};
+    use crate::map::Tile;
+
+    #[test]
+    fn hilbert_tile_ids() {
+        assert_eq!(tile_id(0, 0, 0), 0);
+        assert_eq!(tile_id(1, 0, 0), 1);
+        assert_eq!(tile_id(1, 0, 1), 2);
+        assert_eq!(tile_id(1, 1, 1), 3);
+        assert_eq!(tile_id(1, 1, 0), 4);
+        assert_eq!(tile_id(2, 0, 0), 5);
+        assert_eq!(tile_id(3, 0, 0), 21);
+        assert_eq!(tile_id(20, 0, 0), 366503875925);
+    }
+
+    #[test]
+    fn large_directories_use_leaves() {
+        // Pseudo random ids and lengths so that the directory doesn't compress too well
+        let mut seed: u64 = 42;
+        let mut random = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) % 1000 + 1
+        };
+        let mut entries: Vec<Entry> = vec![];
+        let mut offset = 0;
+        for _ in 0..100_000 {
+            let tile_id = entries.last().map_or(0, |entry| entry.tile_id) + random();
+            let length = random();
+            entries.push(Entry {
+                tile_id,
+                offset,
+                length,
+                run_length: 1,
+            });
+            offset += length;
+        }
+        let (root, leaves) = build_directories(&entries);
+        assert!(root.len() + HEADER_SIZE <= 16_384);
+        assert!(!leaves.is_empty());
+    }
+
+    #[test]
+    fn archive_layout() {
+        let archive = TileArchive {
+            name: "infra_1".into(),
+            zoom_range: 0..=1,
+            bounds: None,
+            tilejsons: Default::default(),
+        };
+        let path = temp_dir().join("editoast_write_pmtiles_test.pmtiles");
+        let mut writer = PmtilesWriter::create(&path).unwrap();
+        writer
+            .write_tile(&Tile { x: 1, y: 0, z: 1 }, &[1, 2])
+            .unwrap();
+        writer.write_tile(&Tile { x: 0, y: 0, z: 0 }, &[3]).unwrap();
+        writer.finish(&archive).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(&bytes[0..8], b"PMTiles\x03");
+        let read_u64 =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let (data_offset, data_length) = (read_u64(56) as usize, read_u64(64) as usize);
+        assert_eq!(data_offset + data_length, bytes.len());
+        // Tiles data are stored in their writing order
+        assert_eq!(&bytes[data_offset..], &[1, 2, 3]);
+        // Addressed tiles count
+        assert_eq!(read_u64(72), 2);
+    }
+}
@@ -1,14 +1,27 @@
 use core::f64::consts::PI;
+use std::ops::RangeInclusive;
 
 use super::BoundingBox;
 
 /// Web mercator coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Tile {
     pub x: u64,
     pub y: u64,
     pub z: u64,
 }
 
+impl Tile {
+    /// Tiles of the next zoom level covering this tile
+    pub fn children(&self) -> impl Iterator<Item = Tile> + '_ {
+        (0..4).map(|i| Tile {
+            x: self.x * 2 + i % 2,
+            y: self.y * 2 + i / 2,
+            z: self.z + 1,
+        })
+    }
+}
+
 /// North-West and South-East web mercator coordinates
 struct NwSeCoordinates {
     nw_x: u64,
@@ -49,39 +62,68 @@ fn get_nw_se_coordinates(zoom: u64, bbox: &BoundingBox) -> NwSeCoordinates {
     }
 }
 
-/// Gets tiles for a bounding box and a zoom value
-pub fn get_tiles_to_invalidate(max_zoom: u64, bbox: &BoundingBox) -> Vec<Tile> {
-    let mut affected_tiles: Vec<Tile> = Vec::new();
-    for zoom in 0..(max_zoom + 1) {
+/// Tiles covering a bounding box at a zoom level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileRange {
+    pub z: u64,
+    pub x: RangeInclusive<u64>,
+    pub y: RangeInclusive<u64>,
+}
+
+impl TileRange {
+    /// Panics if the bbox is invalid
+    pub fn new(zoom: u64, bbox: &BoundingBox) -> Self {
         let NwSeCoordinates {
             nw_x,
             nw_y,
             se_x,
             se_y,
         } = get_nw_se_coordinates(zoom, bbox);
-        for x in nw_x..(se_x + 1) {
-            for y in se_y..(nw_y + 1) {
-                affected_tiles.push(Tile { x, y, z: zoom })
-            }
+        // Bounding boxes reaching the antimeridian or the south pole end on the next tile
+        let max_coordinate = (1 << zoom) - 1;
+        Self {
+            z: zoom,
+            x: nw_x.min(max_coordinate)..=se_x.min(max_coordinate),
+            y: se_y.min(max_coordinate)..=nw_y.min(max_coordinate),
         }
     }
-    affected_tiles
-}
 
-/// Counts tiles for a bounding box and a zoom value
-pub fn count_tiles(max_zoom: u64, bbox: &BoundingBox) -> u64 {
-    let mut count: u64 = 0;
-    for zoom in 0..(max_zoom + 1) {
-        let NwSeCoordinates {
-            nw_x,
-            nw_y,
-            se_x,
-            se_y,
-        } = get_nw_se_coordinates(zoom, bbox);
-        // Value added to count is positive or null since validity of bbox is checked in get_nw_se_coordinates()
-        count += (se_x - nw_x) * (nw_y - se_y);
+    pub fn count(&self) -> u64 {
+        (self.x.end() - self.x.start() + 1) * (self.y.end() - self.y.start() + 1)
+    }
+
+    pub fn contains(&self, tile: &Tile) -> bool {
+        tile.z == self.z && self.x.contains(&tile.x) && self.y.contains(&tile.y)
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        self.x
+            .clone()
+            .flat_map(move |x| self.y.clone().map(move |y| Tile { x, y, z: self.z }))
     }
-    count
+}
+
+/// Gets the ranges of tiles covering a bounding box for each zoom level of a range
+///
+/// Panics if the bbox is invalid
+pub fn get_tile_pyramid(zoom_range: RangeInclusive<u64>, bbox: &BoundingBox) -> Vec<TileRange> {
+    zoom_range.map(|zoom| TileRange::new(zoom, bbox)).collect()
+}
+
+/// Gets tiles covering a bounding box for each zoom level of a range
+pub fn get_tiles(zoom_range: RangeInclusive<u64>, bbox: &BoundingBox) -> Vec<Tile> {
+    get_tile_pyramid(zoom_range, bbox)
+        .iter()
+        .flat_map(TileRange::tiles)
+        .collect()
+}
+
+/// Counts tiles covering a bounding box for each zoom level of a range without enumerating them
+pub fn count_tiles(zoom_range: RangeInclusive<u64>, bbox: &BoundingBox) -> u64 {
+    get_tile_pyramid(zoom_range, bbox)
+        .iter()
+        .map(TileRange::count)
+        .sum()
 }
 
 pub fn get_layer_cache_prefix(layer_name: &str, infra_id: i64) -> String {
@@ -104,6 +146,22 @@ pub fn get_cache_tile_key(view_prefix: &str, tile: &Tile) -> String {
     format!("{view_prefix}.tile/{}/{}/{}", tile.z, tile.x, tile.y)
 }
 
+/// Gets the tile of a cache key built by [get_cache_tile_key]
+/// Returns None if the key is not a tile of the view
+pub fn parse_cache_tile_key(view_prefix: &str, key: &str) -> Option<Tile> {
+    let coordinates = key.strip_prefix(view_prefix)?.strip_prefix(".tile/")?;
+    let mut coordinates = coordinates.split('/').map(|value| value.parse().ok());
+    match (
+        coordinates.next()??,
+        coordinates.next()??,
+        coordinates.next()??,
+        coordinates.next(),
+    ) {
+        (z, x, y, None) => Some(Tile { x, y, z }),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -111,8 +169,8 @@ mod tests {
     use crate::map::BoundingBox;
 
     use super::{
-        count_tiles, get_cache_tile_key, get_layer_cache_prefix, get_tiles_to_invalidate,
-        get_view_cache_prefix, Tile,
+        count_tiles, get_cache_tile_key, get_layer_cache_prefix, get_tile_pyramid, get_tiles,
+        get_view_cache_prefix, parse_cache_tile_key, Tile, TileRange,
     };
 
     const CAMPUS_SNCF_BBOX: BoundingBox = BoundingBox((2.3535, 48.921), (2.3568, 48.922));
@@ -150,16 +208,56 @@ mod tests {
             (1, 0, 1),
             (0, 0, 0),
         ]);
-        let found_tiles: HashSet<(u64, u64, u64)> = get_tiles_to_invalidate(18, &CAMPUS_SNCF_BBOX)
+        let found_tiles: HashSet<(u64, u64, u64)> = get_tiles(0..=18, &CAMPUS_SNCF_BBOX)
             .iter()
             .map(|tile| (tile.x, tile.y, tile.z))
             .collect();
         assert_eq!(expected_tiles, found_tiles);
     }
 
+    #[test]
+    fn find_tiles_in_zoom_range() {
+        let found_tiles: HashSet<(u64, u64, u64)> = get_tiles(17..=18, &CAMPUS_SNCF_BBOX)
+            .iter()
+            .map(|tile| (tile.x, tile.y, tile.z))
+            .collect();
+        assert_eq!(found_tiles.len(), 11);
+        assert!(found_tiles.iter().all(|(_, _, z)| *z >= 17));
+    }
+
     #[test]
     fn test_count_tiles() {
-        assert_eq!(count_tiles(18, &CAMPUS_SNCF_BBOX), 3);
+        assert_eq!(count_tiles(0..=18, &CAMPUS_SNCF_BBOX), 29);
+        assert_eq!(count_tiles(17..=18, &CAMPUS_SNCF_BBOX), 11);
+    }
+
+    #[test]
+    fn tile_pyramid() {
+        let pyramid = get_tile_pyramid(16..=18, &CAMPUS_SNCF_BBOX);
+        assert_eq!(
+            pyramid[2],
+            TileRange {
+                z: 18,
+                x: 132785..=132788,
+                y: 90112..=90113
+            }
+        );
+        assert!(pyramid[0].contains(&Tile {
+            x: 33197,
+            y: 22528,
+            z: 16
+        }));
+        assert!(!pyramid[0].contains(&Tile {
+            x: 33197,
+            y: 22528,
+            z: 17
+        }));
+
+        // Large zones are counted without enumerating their tiles
+        let france = BoundingBox((-5., 42.), (8., 51.));
+        assert!(count_tiles(0..=18, &france) > 100_000_000);
+        let world = BoundingBox((-180., -85.), (180., 85.));
+        assert_eq!(count_tiles(0..=2, &world), 1 + 4 + 16);
     }
 
     #[test]
@@ -188,4 +286,33 @@ mod tests {
             "editoast.layer.track_sections.infra_1.tile/3/1/2"
         );
     }
+
+    #[test]
+    fn test_parse_cache_tile_key() {
+        let prefix = "editoast.layer.track_sections.infra_1.geo";
+        let tile = Tile { x: 1, y: 2, z: 3 };
+        assert_eq!(
+            parse_cache_tile_key(prefix, &get_cache_tile_key(prefix, &tile)),
+            Some(tile)
+        );
+        assert!(
+            parse_cache_tile_key(prefix, "editoast.layer.signals.infra_1.geo.tile/3/1/2").is_none()
+        );
+        assert!(parse_cache_tile_key(prefix, &format!("{prefix}.tile/3/1/2/4")).is_none());
+        assert!(parse_cache_tile_key(prefix, &format!("{prefix}.tile/3/a/2")).is_none());
+    }
+
+    #[test]
+    fn tile_children() {
+        let children: Vec<_> = Tile { x: 1, y: 2, z: 3 }.children().collect();
+        assert_eq!(
+            children,
+            vec![
+                Tile { x: 2, y: 4, z: 4 },
+                Tile { x: 3, y: 4, z: 4 },
+                Tile { x: 2, y: 5, z: 4 },
+                Tile { x: 3, y: 5, z: 4 },
+            ]
+        );
+    }
 }
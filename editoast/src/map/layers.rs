@@ -1,6 +1,10 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
 
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
 use serde_yaml::{self};
 
 // select C.stuff from A inner join B C on C.id = C.id;
@@ -21,6 +25,61 @@ pub struct View {
     pub cache_duration: u32,
     #[serde(rename = "where", default)]
     pub where_expr: Vec<String>,
+    /// Rendering rules depending on the tile zoom, the first matching rule is applied
+    #[serde(default)]
+    pub zoom_rules: Vec<ZoomRule>,
+    /// Filters that can be given as tile query parameters, mapping their name to the filtered SQL expression
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+}
+
+impl View {
+    /// Returns the rendering rule applied to the tiles of a zoom level
+    pub fn zoom_rule(&self, zoom: u64) -> Option<&ZoomRule> {
+        self.zoom_rules.iter().find(|rule| rule.matches(zoom))
+    }
+
+    /// Whether the tiles of the next zoom levels can contain records when a tile of this zoom is empty
+    /// Zoom rules may filter records out, other tiles are empty because their area has no record
+    pub fn may_have_records_below(&self, zoom: u64) -> bool {
+        self.zoom_rule(zoom)
+            .is_some_and(|rule| !rule.where_expr.is_empty())
+    }
+
+    /// Entity tag of the view tiles of an infra
+    /// It changes when the infra generated data or the view definition change
+    pub fn tile_etag(&self, infra_id: i64, generated_version: Option<&str>) -> String {
+        let mut hasher = DefaultHasher::new();
+        // Json objects keys are sorted, unlike the view hash maps
+        serde_json::to_value(self)
+            .unwrap()
+            .to_string()
+            .hash(&mut hasher);
+        let generated_version = generated_version.unwrap_or("none");
+        format!("{infra_id}-{generated_version}-{:x}", hasher.finish())
+    }
+}
+
+/// Rendering rule of the tiles of a zoom range
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ZoomRule {
+    #[serde(default)]
+    pub min_zoom: u64,
+    #[serde(default)]
+    pub max_zoom: Option<u64>,
+    /// Tolerance of the geometries simplification, in the unit of the view field
+    #[serde(default)]
+    pub simplify: Option<f64>,
+    /// Conditions added to the view ones
+    #[serde(rename = "where", default)]
+    pub where_expr: Vec<String>,
+}
+
+impl ZoomRule {
+    pub fn matches(&self, zoom: u64) -> bool {
+        zoom >= self.min_zoom && self.max_zoom.is_none_or(|max_zoom| zoom <= max_zoom)
+    }
 }
 
 /// Layer description
@@ -32,9 +91,39 @@ pub struct Layer {
     pub id_field: Option<String>,
     #[serde(default)]
     pub attribution: Option<String>,
+    /// Invalidate the whole layer on infra updates instead of the updated zone
+    #[serde(default)]
+    pub full_invalidation: bool,
+}
+
+impl Layer {
+    /// Builds the TileJSON metadata of a view of the layer
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the layer in the vector tiles
+    /// * `tiles` - Url patterns of the tiles
+    /// * `zoom_range` - Zoom levels at which tiles are available
+    pub fn tilejson(
+        &self,
+        name: &str,
+        tiles: Vec<String>,
+        zoom_range: RangeInclusive<u64>,
+    ) -> JsonValue {
+        json!({
+            "type": "vector",
+            "name": name,
+            "promoteId": {name: self.id_field},
+            "scheme": "xyz",
+            "tiles": tiles,
+            "attribution": self.attribution.clone().unwrap_or_default(),
+            "minzoom": zoom_range.start(),
+            "maxzoom": zoom_range.end(),
+        })
+    }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct MapLayers {
     pub layers: HashMap<String, Layer>,
 }
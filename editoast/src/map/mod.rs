@@ -1,33 +1,43 @@
 mod bounding_box;
+mod diff;
+mod export;
 mod layer_cache;
 mod layers;
+mod mvt_utils;
 mod redis_utils;
+mod seed;
+mod tile_cache;
 
-use std::collections::HashMap;
-
+use crate::client::MapLayersConfig;
 use crate::error::Result;
 pub use bounding_box::{BoundingBox, InvalidationZone};
+pub use diff::render_diff_mvt_tile;
+pub use export::{export_infra, MbtilesWriter, PmtilesWriter, TileWriter};
 pub use layers::{Layer, MapLayers, View};
-use redis::aio::ConnectionManager;
+pub use mvt_utils::{render_mvt_tile, TileFilters};
+pub use seed::{seed_infra, SeedOptions};
 
 pub use self::layer_cache::{
-    count_tiles, get_cache_tile_key, get_layer_cache_prefix, get_tiles_to_invalidate,
-    get_view_cache_prefix, Tile,
+    count_tiles, get_cache_tile_key, get_layer_cache_prefix, get_tile_pyramid, get_tiles,
+    get_view_cache_prefix, parse_cache_tile_key, Tile, TileRange,
+};
+pub use self::tile_cache::{
+    delete, get_tile, keys, memory_usage, set_tile, CacheCounters, CachedTile, TileCache,
+    TileCacheStats,
 };
-pub use self::redis_utils::{delete, get, keys, set};
 
 /// Invalidates layer cache for a specific infra and view if provided
 ///
 /// # Arguments
 ///
-/// * `redis_pool` - Pool to use to connect to the redis
+/// * `cache` - Tile cache to invalidate
 /// * `infra_id` - Infra to on which the layer must be invalidated
 /// * `layer_name` - Layer to invalidate
 /// * `view_name` - Specific view to invalidate, if not provided all layer's views are invalidated
 ///
 /// Returns the number of deleted keys
-async fn invalidate_full_layer_cache(
-    redis: &mut ConnectionManager,
+pub async fn invalidate_full_layer_cache(
+    cache: &TileCache,
     infra_id: i64,
     layer_name: &str,
     view_name: Option<&str>,
@@ -35,84 +45,82 @@ async fn invalidate_full_layer_cache(
     let prefix: String = view_name.map_or(get_layer_cache_prefix(layer_name, infra_id), |view| {
         get_view_cache_prefix(layer_name, infra_id, view)
     });
-    let matching_keys = keys(redis, &format!("{prefix}.*")).await?;
-    let number_of_deleted_keys = delete(redis, matching_keys).await?;
-    Ok(number_of_deleted_keys)
-}
-
-/// Invalidates cache for specific
-///
-/// # Arguments
-///
-/// * `redis_pool` - Pool to use to connect to the redis
-/// * `tiles_to_invalidate` - View cache prefix to tiles
-async fn invalidate_cache_tiles(
-    redis: &mut ConnectionManager,
-    tiles_to_invalidate: HashMap<String, Vec<Tile>>,
-) -> Result<u64> {
-    let mut keys_to_delete: Vec<String> = Vec::new();
-    for (view_cache_prefix, tiles) in tiles_to_invalidate {
-        for tile in tiles {
-            keys_to_delete.push(get_cache_tile_key(&view_cache_prefix, &tile));
-        }
-    }
-    let number_of_deleted_keys = delete(redis, keys_to_delete).await?;
+    let matching_keys = keys(cache, &format!("{prefix}.*")).await?;
+    let number_of_deleted_keys = delete(cache, matching_keys).await?;
     Ok(number_of_deleted_keys)
 }
 
 /// Invalidates a infra specific layer zone
 ///
+/// The cached tiles covering the zone are deleted for every zoom level up to the configured
+/// maximum zoom. If the zone covers too many tiles to enumerate their keys, the cached tiles of
+/// the view are looked up instead and the ones in the tile pyramid of the zone are deleted.
+///
 /// # Arguments
 ///
-/// * `redis_pool` - Pool to use to connect to the redis
+/// * `cache` - Tile cache to invalidate
 /// * `infra_id` - Infra to on which the layer must be invalidated
 /// * `layer_name` - Layer on which invalidation must be done
 /// * `zone` - Zone to invalidate
+/// * `config` - Maximum zoom of the served tiles and maximum number of enumerated tiles
 async fn invalidate_layer_zone(
-    redis: &mut ConnectionManager,
+    cache: &TileCache,
     infra_id: i64,
     layer_name: &str,
     zone: &InvalidationZone,
-    max_tiles: u64,
-) -> Result<()> {
-    let mut affected_tiles: HashMap<String, Vec<Tile>> = HashMap::new();
+    config: &MapLayersConfig,
+) -> Result<u64> {
+    let mut keys_to_delete: Vec<String> = Vec::new();
     for (view_name, bbox) in [("geo", &zone.geo), ("sch", &zone.sch)] {
-        if count_tiles(18, bbox) > max_tiles {
-            invalidate_full_layer_cache(redis, infra_id, layer_name, Some(view_name)).await?;
+        if !bbox.is_valid() {
+            continue;
+        }
+        let view_prefix = get_view_cache_prefix(layer_name, infra_id, view_name);
+        let pyramid = get_tile_pyramid(0..=config.max_zoom, bbox);
+        if count_tiles(0..=config.max_zoom, bbox) > config.max_tiles {
+            let cached_keys = keys(cache, &format!("{view_prefix}.tile/*")).await?;
+            keys_to_delete.extend(cached_keys.into_iter().filter(|key| {
+                parse_cache_tile_key(&view_prefix, key).is_some_and(|tile| {
+                    // The pyramid starts at zoom 0 so it is indexed by zoom level
+                    pyramid
+                        .get(tile.z as usize)
+                        .is_some_and(|range| range.contains(&tile))
+                })
+            }));
         } else {
-            affected_tiles.insert(
-                get_view_cache_prefix(layer_name, infra_id, view_name),
-                get_tiles_to_invalidate(12, bbox),
+            keys_to_delete.extend(
+                pyramid
+                    .iter()
+                    .flat_map(TileRange::tiles)
+                    .map(|tile| get_cache_tile_key(&view_prefix, &tile)),
             );
         }
     }
-    if !affected_tiles.is_empty() {
-        invalidate_cache_tiles(redis, affected_tiles).await?;
-    }
-    Ok(())
+    delete(cache, keys_to_delete).await
 }
 
 /// Invalidates a zone for all map layers
-/// If the zone is invalide nothing is done
+/// If the zone is invalide only the layers with full invalidation are invalidated
 ///
 /// # Arguments
 ///
-/// * `redis_pool` - Pool to use to connect to the redis
-/// * `layers` - Layers to invalidate
+/// * `cache` - Tile cache to invalidate
+/// * `map_layers` - Layers to invalidate
 /// * `infra_id` - Infra to on which layers must be invalidated
 /// * `zone` - Zone to invalidate
-///
-/// Panics if fail
+/// * `config` - Maximum zoom of the served tiles and maximum number of enumerated tiles
 pub async fn invalidate_zone(
-    redis: &mut ConnectionManager,
-    layers: &Vec<String>,
+    cache: &TileCache,
+    map_layers: &MapLayers,
     infra_id: i64,
     zone: &InvalidationZone,
-    max_tiles: u64,
+    config: &MapLayersConfig,
 ) -> Result<()> {
-    if zone.is_valid() {
-        for layer in layers {
-            invalidate_layer_zone(redis, infra_id, layer, zone, max_tiles).await?;
+    for (layer_name, layer) in map_layers.layers.iter() {
+        if layer.full_invalidation {
+            invalidate_full_layer_cache(cache, infra_id, layer_name, None).await?;
+        } else if zone.is_valid() {
+            invalidate_layer_zone(cache, infra_id, layer_name, zone, config).await?;
         }
     }
     Ok(())
@@ -122,16 +130,12 @@ pub async fn invalidate_zone(
 ///
 /// # Arguments
 ///
-/// * `redis_pool` - Pool to use to connect to the redis
+/// * `cache` - Tile cache to invalidate
 /// * `layers` - Layers to invalidate
 /// * `infra_id` - Infra to on which layers must be invalidated
-///
-/// Panics if fail
-pub async fn invalidate_all(redis: &mut ConnectionManager, layers: &Vec<String>, infra_id: i64) {
+pub async fn invalidate_all(cache: &TileCache, layers: &Vec<String>, infra_id: i64) -> Result<()> {
     for layer_name in layers {
-        let result = invalidate_full_layer_cache(redis, infra_id, layer_name, None).await;
-        if result.is_err() {
-            panic!("Failed to invalidate map layer: {}", result.unwrap_err());
-        }
+        invalidate_full_layer_cache(cache, infra_id, layer_name, None).await?;
     }
+    Ok(())
 }
@@ -1,10 +1,15 @@
-use diesel::sql_types::{Jsonb, Text};
+use std::collections::BTreeMap;
+
+use diesel::sql_types::{Array, Integer, Jsonb, Text};
+use diesel::{sql_query, PgConnection, RunQueryDsl};
 use mvt::{Feature, GeomData, GeomEncoder, MapGrid, Tile as MvtTile, TileId};
 use pointy::Transform64;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
-use crate::{map::View, schema::GeoJson};
+use super::{Layer, Tile, View};
+use crate::error::Result;
+use crate::schema::GeoJson;
 
 #[derive(Clone, QueryableByName, Queryable, Debug, Serialize, Deserialize)]
 pub struct GeoJsonAndData {
@@ -137,13 +142,31 @@ pub fn create_and_fill_mvt_tile<T: AsRef<str>>(
 ///
 /// * `table_name` - Table containing the data
 /// * `view` - View containing info to get the data
-pub fn get_geo_json_sql_query(table_name: &str, view: &View) -> String {
+/// * `zoom` - Zoom of the tile, used to select the view zoom rule
+/// * `filters` - Names of the view filters, their values are bound after the tile parameters
+pub fn get_geo_json_sql_query<'a, I: IntoIterator<Item = &'a String>>(
+    table_name: &str,
+    view: &View,
+    zoom: u64,
+    filters: I,
+) -> String {
+    let zoom_rule = view.zoom_rule(zoom);
+    let geometry = match zoom_rule.and_then(|rule| rule.simplify) {
+        // Collapsed geometries are preserved so that small objects stay visible
+        Some(tolerance) => format!("ST_Simplify(geographic, {tolerance}, true)"),
+        None => "geographic".to_string(),
+    };
+    let zoom_where_expr = zoom_rule.map_or(&[][..], |rule| &rule.where_expr);
+    let filters_where_expr = filters
+        .into_iter()
+        .enumerate()
+        .map(|(i, filter)| format!("({}) = ANY(${})", view.filters[filter], i + 5));
     format!(
         "
         WITH bbox AS (
             SELECT TileBBox($1, $2, $3, 3857) AS geom
         )
-        SELECT ST_AsGeoJson(geographic) AS geo_json, 
+        SELECT ST_AsGeoJson({geometry}) AS geo_json, 
             {data_expr} {exclude_fields} AS data 
         FROM {table_name} layer 
             CROSS JOIN bbox 
@@ -165,16 +188,65 @@ pub fn get_geo_json_sql_query(table_name: &str, view: &View) -> String {
         where_condition = &view
             .where_expr
             .iter()
+            .chain(zoom_where_expr)
+            .cloned()
+            .chain(filters_where_expr)
             .map(|field| format!("AND ({field})"))
             .collect::<Vec<_>>()
             .join(" "),
     )
 }
 
+/// Values of the view filters of a tile request indexed by filter name
+pub type TileFilters = BTreeMap<String, Vec<String>>;
+
+/// Gets the records of a layer view tile from the database and encodes them as a MVT tile
+///
+/// # Arguments
+///
+/// * `conn` - Database connection
+/// * `layer_name` - Name of the layer
+/// * `layer` - Layer containing the view
+/// * `view` - View to render
+/// * `infra_id` - Infra of the records
+/// * `tile` - Tile to render
+/// * `filters` - Only the records matching one of the values of each filter are rendered
+pub fn render_mvt_tile(
+    conn: &mut PgConnection,
+    layer_name: &str,
+    layer: &Layer,
+    view: &View,
+    infra_id: i64,
+    tile: &Tile,
+    filters: &TileFilters,
+) -> Result<Vec<u8>> {
+    let mut query = sql_query(get_geo_json_sql_query(
+        &layer.table_name,
+        view,
+        tile.z,
+        filters.keys(),
+    ))
+    .into_boxed()
+    .bind::<Integer, _>(tile.z as i32)
+    .bind::<Integer, _>(tile.x as i32)
+    .bind::<Integer, _>(tile.y as i32)
+    .bind::<Integer, _>(infra_id as i32);
+    for values in filters.values() {
+        query = query.bind::<Array<Text>, _>(values.clone());
+    }
+    let records = query.get_results::<GeoJsonAndData>(conn)?;
+    Ok(
+        create_and_fill_mvt_tile(tile.z, tile.x, tile.y, layer_name, records)
+            .to_bytes()
+            .unwrap(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
 
+    use crate::map::layers::ZoomRule;
     use crate::map::MapLayers;
 
     use super::{create_and_fill_mvt_tile, get_geo_json_sql_query, GeoJsonAndData};
@@ -217,11 +289,42 @@ mod tests {
             let query = get_geo_json_sql_query(
                 &track_sections.table_name,
                 track_sections.views.get("sch").unwrap(),
+                18,
+                &[],
             );
             assert_eq!(expected_queries[i], query);
         }
     }
 
+    #[test]
+    fn test_query_creation_with_zoom_rule() {
+        let mut view = MapLayers::parse().layers["signals"].views["geo"].clone();
+        view.zoom_rules = vec![ZoomRule {
+            min_zoom: 0,
+            max_zoom: Some(9),
+            simplify: Some(50.),
+            where_expr: vec!["signal.data @? '$.extensions.main'".into()],
+        }];
+        let query = get_geo_json_sql_query("osrd_infra_signallayer", &view, 9, &[]);
+        assert!(
+            query.contains("SELECT ST_AsGeoJson(ST_Simplify(geographic, 50, true)) AS geo_json")
+        );
+        assert!(query.contains("AND (signal.data @? '$.extensions.main')"));
+
+        let query = get_geo_json_sql_query("osrd_infra_signallayer", &view, 10, &[]);
+        assert!(query.contains("SELECT ST_AsGeoJson(geographic) AS geo_json"));
+        assert!(!query.contains("extensions.main"));
+    }
+
+    #[test]
+    fn test_query_creation_with_filters() {
+        let view = &MapLayers::parse().layers["errors"].views["geo"];
+        let filters = vec!["error_type".to_string()];
+        let query = get_geo_json_sql_query("osrd_infra_errorlayer", view, 12, &filters);
+        assert!(query.contains("AND ((layer.information->>'error_type') = ANY($5))"));
+        assert!(!query.contains("settings"));
+    }
+
     #[test]
     fn test_create_and_fill_tile() {
         let records = vec![GeoJsonAndData {
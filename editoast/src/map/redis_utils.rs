@@ -1,25 +1,61 @@
-pub use super::bounding_box::{BoundingBox, InvalidationZone};
-pub use super::layers::{Layer, MapLayers};
 use crate::error::Result;
 use redis::aio::ConnectionManager;
 use redis::{cmd, FromRedisValue, ToRedisArgs};
 
+/// Number of keys requested per `SCAN` iteration and sent per command
+const BATCH_SIZE: usize = 1000;
+
+/// Lists the keys matching a glob pattern
+/// Keys are iterated using `SCAN` since `KEYS` blocks Redis until the whole keyspace is read
 pub async fn keys(redis: &mut ConnectionManager, key_pattern: &str) -> Result<Vec<String>> {
-    Ok(cmd("KEYS")
-        .arg(key_pattern)
-        .query_async::<_, Vec<String>>(redis)
-        .await?)
+    let mut keys = vec![];
+    let mut cursor: u64 = 0;
+    loop {
+        let (next_cursor, batch): (u64, Vec<String>) = cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(key_pattern)
+            .arg("COUNT")
+            .arg(BATCH_SIZE)
+            .query_async(redis)
+            .await?;
+        keys.extend(batch);
+        if next_cursor == 0 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+    // A key can be returned several times if the keyspace is rehashed during the iteration
+    keys.sort();
+    keys.dedup();
+    Ok(keys)
 }
 
+/// Deletes keys by batches, returns the number of deleted keys
+/// Values are reclaimed in the background by `UNLINK` so that deleting large tiles doesn't block Redis
 pub async fn delete(redis: &mut ConnectionManager, keys_to_delete: Vec<String>) -> Result<u64> {
-    if keys_to_delete.is_empty() {
-        return Ok(0);
+    let mut deleted = 0;
+    for batch in keys_to_delete.chunks(BATCH_SIZE) {
+        deleted += cmd("UNLINK")
+            .arg(batch)
+            .query_async::<_, u64>(redis)
+            .await?;
     }
-    let mut del = cmd("DEL");
-    for key in keys_to_delete {
-        del.arg(key);
+    Ok(deleted)
+}
+
+/// Gets the number of bytes used by keys in Redis memory, 0 if a key does not exist
+pub async fn memory_usage(redis: &mut ConnectionManager, keys: &[String]) -> Result<Vec<u64>> {
+    let mut usages = Vec::with_capacity(keys.len());
+    for batch in keys.chunks(BATCH_SIZE) {
+        let mut pipeline = redis::pipe();
+        for key in batch {
+            pipeline.cmd("MEMORY").arg("USAGE").arg(key);
+        }
+        let batch_usages: Vec<Option<u64>> = pipeline.query_async(redis).await?;
+        usages.extend(batch_usages.into_iter().map(Option::unwrap_or_default));
     }
-    Ok(del.query_async::<_, u64>(redis).await?)
+    Ok(usages)
 }
 
 pub async fn set<T: ToRedisArgs>(redis: &mut ConnectionManager, key: &str, value: T) -> Result<()> {
@@ -32,22 +68,21 @@ pub async fn set<T: ToRedisArgs>(redis: &mut ConnectionManager, key: &str, value
 
 /// Gets Redis value associated to a  specific key
 /// Returns None if key does not exists.
-pub async fn get<T: Default + FromRedisValue>(
+pub async fn get<T: FromRedisValue>(
     redis: &mut ConnectionManager,
     cache_key: &str,
-) -> Option<T> {
-    cmd("GET")
+) -> Result<Option<T>> {
+    Ok(cmd("GET")
         .arg(cache_key)
         .query_async::<_, Option<T>>(redis)
-        .await
-        .unwrap()
+        .await?)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
         client::RedisConfig,
-        map::redis_utils::{delete, get, keys, set},
+        map::redis_utils::{delete, get, keys, set, BATCH_SIZE},
     };
     use actix_web::test as actix_test;
     use redis::aio::ConnectionManager;
@@ -62,41 +97,71 @@ mod tests {
     async fn test_redis_set_get_list_delete() {
         let mut redis_pool = create_redis_pool().await;
         // Check redis empty
-        let test_keys = keys(&mut redis_pool, "test_*").await.unwrap();
+        let test_keys = keys(&mut redis_pool, "test_single_*").await.unwrap();
         assert!(test_keys.is_empty());
         // Add two keys and check presence
-        set(&mut redis_pool, "test_1", "value_1").await.unwrap();
-        set(&mut redis_pool, "test_2", "value_2").await.unwrap();
-        let mut test_keys = keys(&mut redis_pool, "test_*").await.unwrap();
+        set(&mut redis_pool, "test_single_1", "value_1")
+            .await
+            .unwrap();
+        set(&mut redis_pool, "test_single_2", "value_2")
+            .await
+            .unwrap();
+        let mut test_keys = keys(&mut redis_pool, "test_single_*").await.unwrap();
         test_keys.sort();
-        assert_eq!(test_keys, vec!["test_1", "test_2"]);
+        assert_eq!(test_keys, vec!["test_single_1", "test_single_2"]);
         // Get value 1
-        let value_1 = get::<String>(&mut redis_pool, "test_1").await.unwrap();
+        let value_1 = get::<String>(&mut redis_pool, "test_single_1")
+            .await
+            .unwrap()
+            .unwrap();
         assert_eq!("value_1", value_1);
         // Get nonexisting key
-        let does_not_exist = get::<Vec<u8>>(&mut redis_pool, "does_not_exist").await;
+        let does_not_exist = get::<Vec<u8>>(&mut redis_pool, "does_not_exist")
+            .await
+            .unwrap();
         assert!(does_not_exist.is_none());
         // Set and get empty vec
         let empty_vec: Vec<u8> = vec![];
-        set(&mut redis_pool, "test_empty", empty_vec.clone())
+        set(&mut redis_pool, "test_single_empty", empty_vec.clone())
+            .await
+            .unwrap();
+        let test_empty = get::<Vec<u8>>(&mut redis_pool, "test_single_empty")
             .await
             .unwrap();
-        let test_empty = get::<Vec<u8>>(&mut redis_pool, "test_empty").await;
         assert!(test_empty.is_some());
         assert_eq!(test_empty.unwrap(), empty_vec);
         // Delete two keys and check absence
         let result = delete(
             &mut redis_pool,
             vec![
-                String::from("test_1"),
-                String::from("test_2"),
-                String::from("test_empty"),
+                String::from("test_single_1"),
+                String::from("test_single_2"),
+                String::from("test_single_empty"),
             ],
         )
         .await
         .unwrap();
         assert_eq!(result, 3);
-        let test_keys = keys(&mut redis_pool, "test_*").await.unwrap();
+        let test_keys = keys(&mut redis_pool, "test_single_*").await.unwrap();
         assert!(test_keys.is_empty());
     }
+
+    #[actix_test]
+    async fn test_redis_batched_list_delete() {
+        let mut redis_pool = create_redis_pool().await;
+        let key_count = 2 * BATCH_SIZE + 1;
+        for i in 0..key_count {
+            set(&mut redis_pool, &format!("test_batch_{i}"), i as u64)
+                .await
+                .unwrap();
+        }
+        let test_keys = keys(&mut redis_pool, "test_batch_*").await.unwrap();
+        assert_eq!(test_keys.len(), key_count);
+        let deleted = delete(&mut redis_pool, test_keys).await.unwrap();
+        assert_eq!(deleted, key_count as u64);
+        assert!(keys(&mut redis_pool, "test_batch_*")
+            .await
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -0,0 +1,230 @@
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use actix_web::web::block;
+use diesel::sql_types::{BigInt, Double, Nullable};
+use diesel::{sql_query, PgConnection, RunQueryDsl};
+use futures::{stream, StreamExt};
+use log::info;
+
+use super::{
+    get_cache_tile_key, get_tiles, get_view_cache_prefix, render_mvt_tile, set_tile, BoundingBox,
+    CachedTile, Layer, MapLayers, TileCache, TileFilters, TileRange, View,
+};
+use crate::error::Result;
+use crate::infra::Infra;
+use crate::DbPool;
+
+/// Number of tiles rendered before checking that the infra is unchanged
+const SEED_BATCH_SIZE: usize = 1000;
+
+/// Options of a tiles seeding
+#[derive(Debug, Clone)]
+pub struct SeedOptions {
+    /// Layers to seed, all the layers if empty
+    pub layers: Vec<String>,
+    pub zoom_range: RangeInclusive<u64>,
+    /// Number of tiles rendered concurrently
+    pub concurrency: usize,
+}
+
+/// Progress of the seeding of a layer view
+#[derive(Debug)]
+pub struct SeedProgress<'a> {
+    pub layer_name: &'a str,
+    pub view_name: &'a str,
+    /// Zoom level being seeded, `seeded` and `total` count the tiles of this level
+    pub zoom: u64,
+    pub seeded: usize,
+    pub total: usize,
+}
+
+#[derive(QueryableByName, Debug)]
+struct ExtentQueryable {
+    #[diesel(sql_type = Nullable<Double>)]
+    x_min: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    y_min: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    x_max: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    y_max: Option<f64>,
+}
+
+/// Gets the bounding box of the geometries of a layer view for an infra
+/// Returns `None` if the infra has no object in the layer
+pub(super) fn get_view_extent(
+    conn: &mut PgConnection,
+    layer: &Layer,
+    view: &View,
+    infra_id: i64,
+) -> Result<Option<BoundingBox>> {
+    let extent: ExtentQueryable = sql_query(format!(
+        "SELECT ST_XMin(extent) AS x_min, ST_YMin(extent) AS y_min,
+            ST_XMax(extent) AS x_max, ST_YMax(extent) AS y_max
+        FROM (
+            SELECT ST_Extent(ST_Transform({on_field}, 4326)) AS extent
+            FROM {table_name}
+            WHERE infra_id = $1
+        ) AS layer_extent",
+        on_field = view.on_field,
+        table_name = layer.table_name,
+    ))
+    .bind::<BigInt, _>(infra_id)
+    .get_result(conn)?;
+    match (extent.x_min, extent.y_min, extent.x_max, extent.y_max) {
+        (Some(x_min), Some(y_min), Some(x_max), Some(y_max)) => {
+            Ok(Some(BoundingBox((x_min, y_min), (x_max, y_max))))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Gets the infra generated version and modification date, identifying the data of the seeded tiles
+async fn get_infra_state(db_pool: &DbPool, infra_id: i64) -> Result<(Option<String>, SystemTime)> {
+    let db_pool = db_pool.clone();
+    block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve(&mut conn, infra_id)?;
+        Ok((infra.generated_version.clone(), infra.modified_time()))
+    })
+    .await
+    .unwrap()
+}
+
+/// Renders the tiles covering the extent of an infra and stores them in the cache
+///
+/// # Arguments
+///
+/// * `db_pool` - Pool used to render the tiles
+/// * `cache` - Cache where tiles are stored
+/// * `map_layers` - Layers description
+/// * `infra_id` - Infra to seed
+/// * `options` - Layers, zoom levels and concurrency of the seeding
+/// * `progress` - Called each time a tile is stored
+///
+/// Like the infra export, the pyramid is only descended below non-empty tiles.
+/// Tiles are rendered by batches, the seeding stops after a batch if the infra was regenerated or modified
+/// meanwhile, to avoid storing outdated tiles and to let the seeding of the new data take over.
+/// Returns the number of seeded tiles
+pub async fn seed_infra<F: FnMut(SeedProgress)>(
+    db_pool: &DbPool,
+    cache: &TileCache,
+    map_layers: &MapLayers,
+    infra_id: i64,
+    options: &SeedOptions,
+    mut progress: F,
+) -> Result<usize> {
+    let mut layer_names: Vec<_> = map_layers
+        .layers
+        .keys()
+        .filter(|name| options.layers.is_empty() || options.layers.contains(name))
+        .cloned()
+        .collect();
+    layer_names.sort();
+
+    let infra_state = get_infra_state(db_pool, infra_id).await?;
+    let (generated_version, last_modified) = infra_state.clone();
+
+    let mut seeded_tiles = 0;
+    for layer_name in layer_names {
+        let layer = Arc::new(map_layers.layers[&layer_name].clone());
+        let mut view_names: Vec<_> = layer.views.keys().cloned().collect();
+        view_names.sort();
+        for view_name in view_names {
+            let view = Arc::new(layer.views[&view_name].clone());
+            let bbox = {
+                let (db_pool, layer, view) = (db_pool.clone(), layer.clone(), view.clone());
+                block(move || {
+                    let mut conn = db_pool.get().expect("Failed to get DB connection");
+                    get_view_extent(&mut conn, &layer, &view, infra_id)
+                })
+                .await
+                .unwrap()?
+            };
+            let bbox = match bbox {
+                Some(bbox) if bbox.is_valid() => bbox,
+                _ => continue,
+            };
+
+            let view_prefix = get_view_cache_prefix(&layer_name, infra_id, &view_name);
+            let etag = view.tile_etag(infra_id, generated_version.as_deref());
+            let start = *options.zoom_range.start();
+            let mut level = get_tiles(start..=start, &bbox);
+            for zoom in options.zoom_range.clone() {
+                let total = level.len();
+                let next_range = TileRange::new(zoom + 1, &bbox);
+                let mut next_level = vec![];
+                let mut seeded = 0;
+                for batch in level.chunks(SEED_BATCH_SIZE) {
+                    let rendered_tiles: Vec<_> = stream::iter(batch.iter().copied())
+                        .map(|tile| {
+                            let (db_pool, layer, view) =
+                                (db_pool.clone(), layer.clone(), view.clone());
+                            let layer_name = layer_name.clone();
+                            async move {
+                                block::<_, Result<_>>(move || {
+                                    let mut conn =
+                                        db_pool.get().expect("Failed to get DB connection");
+                                    let mvt_bytes = render_mvt_tile(
+                                        &mut conn,
+                                        &layer_name,
+                                        &layer,
+                                        &view,
+                                        infra_id,
+                                        &tile,
+                                        &TileFilters::default(),
+                                    )?;
+                                    Ok((tile, mvt_bytes))
+                                })
+                                .await
+                                .unwrap()
+                            }
+                        })
+                        .buffer_unordered(options.concurrency.max(1))
+                        .collect()
+                        .await;
+
+                    // The batch is outdated if the infra was regenerated while rendering it
+                    if get_infra_state(db_pool, infra_id).await? != infra_state {
+                        info!("Infra {infra_id} was modified, stopping the seeding of its tiles");
+                        return Ok(seeded_tiles + seeded);
+                    }
+
+                    for rendered_tile in rendered_tiles {
+                        let (tile, mvt_bytes) = rendered_tile?;
+                        if zoom < *options.zoom_range.end()
+                            && (!mvt_bytes.is_empty() || view.may_have_records_below(zoom))
+                        {
+                            next_level
+                                .extend(tile.children().filter(|child| next_range.contains(child)));
+                        }
+                        let cached_tile = CachedTile {
+                            etag: etag.clone(),
+                            last_modified,
+                            mvt_bytes,
+                        };
+                        set_tile(
+                            cache,
+                            &get_cache_tile_key(&view_prefix, &tile),
+                            &cached_tile,
+                        )
+                        .await?;
+                        seeded += 1;
+                        progress(SeedProgress {
+                            layer_name: &layer_name,
+                            view_name: &view_name,
+                            zoom,
+                            seeded,
+                            total,
+                        });
+                    }
+                }
+                seeded_tiles += seeded;
+                level = next_level;
+            }
+        }
+    }
+    Ok(seeded_tiles)
+}
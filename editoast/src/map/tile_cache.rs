@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use actix_web::rt::time::timeout;
+use lru::LruCache;
+use redis::aio::ConnectionManager;
+use redis::{cmd, Client, ErrorKind, RedisError};
+use serde::Serialize;
+
+use super::redis_utils;
+use crate::client::{RedisConfig, TileCacheBackend, TileCacheConfig};
+use crate::error::Result;
+
+/// Maximum duration of a connection attempt to Redis
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+/// Delay before a new connection attempt after a failure, doubled after each failure
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct RedisConnectionState {
+    connection: Option<ConnectionManager>,
+    /// Instant before which no connection is attempted and the delay that led to it
+    retry: Option<(Instant, Duration)>,
+}
+
+/// Redis tile cache, the connection is opened on first use so that editoast starts without Redis
+#[derive(Clone)]
+pub struct RedisTileCache {
+    client: Client,
+    state: Arc<Mutex<RedisConnectionState>>,
+}
+
+impl RedisTileCache {
+    /// Returns the Redis connection, opening it if needed
+    /// Fails fast while Redis is unreachable so that tiles are rendered from the database instead
+    async fn connection(&self) -> Result<ConnectionManager> {
+        {
+            let state = self.state.lock().unwrap();
+            if let Some(connection) = state.connection.as_ref() {
+                return Ok(connection.clone());
+            }
+            if let Some((retry_at, _)) = state.retry {
+                if Instant::now() < retry_at {
+                    let err = (
+                        ErrorKind::IoError,
+                        "Redis is unreachable, connection postponed",
+                    );
+                    return Err(RedisError::from(err).into());
+                }
+            }
+        }
+
+        // The lock isn't held while connecting, requests don't wait for each other
+        let connecting = timeout(
+            CONNECTION_TIMEOUT,
+            self.client.get_tokio_connection_manager(),
+        );
+        let new_connection = match connecting.await {
+            Ok(new_connection) => new_connection,
+            Err(_) => Err(RedisError::from((
+                ErrorKind::IoError,
+                "Redis connection timed out",
+            ))),
+        };
+        let mut state = self.state.lock().unwrap();
+        match new_connection {
+            Ok(new_connection) => {
+                state.retry = None;
+                // Keep the connection opened by a concurrent request if any
+                Ok(state.connection.get_or_insert(new_connection).clone())
+            }
+            Err(err) => {
+                let delay = state.retry.map_or(MIN_RETRY_DELAY, |(_, delay)| {
+                    (delay * 2).min(MAX_RETRY_DELAY)
+                });
+                state.retry = Some((Instant::now() + delay, delay));
+                Err(err.into())
+            }
+        }
+    }
+}
+
+/// Storage of the rendered map tiles
+#[derive(Clone)]
+pub enum TileCache {
+    Redis(RedisTileCache),
+    Memory(Arc<Mutex<LruCache<String, Vec<u8>>>>),
+}
+
+impl TileCache {
+    /// Builds the tile cache selected by the configuration
+    pub fn new(config: &TileCacheConfig, redis_config: &RedisConfig) -> Result<Self> {
+        match config.tile_cache {
+            TileCacheBackend::Redis => Self::new_redis(&redis_config.redis_url),
+            TileCacheBackend::Memory => Ok(Self::new_memory(config.tile_cache_size)),
+        }
+    }
+
+    pub fn new_redis(redis_url: &str) -> Result<Self> {
+        Ok(Self::Redis(RedisTileCache {
+            client: Client::open(redis_url)?,
+            state: Default::default(),
+        }))
+    }
+
+    /// Builds an in-process LRU cache keeping at most `size` tiles
+    pub fn new_memory(size: usize) -> Self {
+        let size = NonZeroUsize::new(size).unwrap_or(NonZeroUsize::MIN);
+        Self::Memory(Arc::new(Mutex::new(LruCache::new(size))))
+    }
+
+    /// Checks that the cache backend is reachable
+    pub async fn ping(&self) -> Result<()> {
+        match self {
+            Self::Redis(redis) => Ok(cmd("PING")
+                .query_async::<_, ()>(&mut redis.connection().await?)
+                .await?),
+            Self::Memory(_) => Ok(()),
+        }
+    }
+}
+
+/// Checks whether a key matches a glob pattern where `*` matches any sequence of characters
+fn matches_pattern(pattern: &str, key: &str) -> bool {
+    let parts: Vec<_> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == key;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if key.len() < first.len() + last.len() || !key.starts_with(first) || !key.ends_with(last) {
+        return false;
+    }
+    // Middle parts are matched greedily between the prefix and the suffix
+    let mut rest = &key[first.len()..key.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Lists the keys of the cached tiles matching a glob pattern
+pub async fn keys(cache: &TileCache, key_pattern: &str) -> Result<Vec<String>> {
+    match cache {
+        TileCache::Redis(redis) => {
+            redis_utils::keys(&mut redis.connection().await?, key_pattern).await
+        }
+        TileCache::Memory(lru) => Ok(lru
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| matches_pattern(key_pattern, key))
+            .cloned()
+            .collect()),
+    }
+}
+
+/// Returns the memory usage in bytes of cached tiles, 0 for missing keys
+pub async fn memory_usage(cache: &TileCache, keys: &[String]) -> Result<Vec<u64>> {
+    match cache {
+        TileCache::Redis(redis) => {
+            redis_utils::memory_usage(&mut redis.connection().await?, keys).await
+        }
+        TileCache::Memory(lru) => {
+            let lru = lru.lock().unwrap();
+            Ok(keys
+                .iter()
+                .map(|key| {
+                    lru.peek(key)
+                        .map_or(0, |value| (key.len() + value.len()) as u64)
+                })
+                .collect())
+        }
+    }
+}
+
+/// Deletes cached tiles, returns the number of deleted keys
+pub async fn delete(cache: &TileCache, keys_to_delete: Vec<String>) -> Result<u64> {
+    match cache {
+        TileCache::Redis(redis) => {
+            redis_utils::delete(&mut redis.connection().await?, keys_to_delete).await
+        }
+        TileCache::Memory(lru) => {
+            let mut lru = lru.lock().unwrap();
+            Ok(keys_to_delete
+                .iter()
+                .filter(|key| lru.pop(*key).is_some())
+                .count() as u64)
+        }
+    }
+}
+
+pub async fn set(cache: &TileCache, key: &str, value: Vec<u8>) -> Result<()> {
+    match cache {
+        TileCache::Redis(redis) => {
+            redis_utils::set(&mut redis.connection().await?, key, value).await
+        }
+        TileCache::Memory(lru) => {
+            lru.lock().unwrap().put(key.to_string(), value);
+            Ok(())
+        }
+    }
+}
+
+/// Gets a cached tile
+/// Returns None if the tile is not in the cache.
+pub async fn get(cache: &TileCache, key: &str) -> Result<Option<Vec<u8>>> {
+    match cache {
+        TileCache::Redis(redis) => redis_utils::get(&mut redis.connection().await?, key).await,
+        TileCache::Memory(lru) => Ok(lru.lock().unwrap().get(key).cloned()),
+    }
+}
+
+/// Tile stored in the cache with the entity tag of its content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedTile {
+    pub etag: String,
+    /// Modification date of the infra when the tile was rendered, stored to the second
+    pub last_modified: SystemTime,
+    pub mvt_bytes: Vec<u8>,
+}
+
+/// First byte of the cached tiles, values stored with another layout are ignored
+const CACHED_TILE_FORMAT: u8 = 1;
+
+impl CachedTile {
+    /// The entity tag prefixed by its length and the modification date in seconds are stored before the tile
+    fn to_bytes(&self) -> Vec<u8> {
+        let last_modified = self
+            .last_modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let mut bytes = Vec::with_capacity(11 + self.etag.len() + self.mvt_bytes.len());
+        bytes.push(CACHED_TILE_FORMAT);
+        bytes.extend_from_slice(&(self.etag.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(self.etag.as_bytes());
+        bytes.extend_from_slice(&last_modified.to_le_bytes());
+        bytes.extend_from_slice(&self.mvt_bytes);
+        bytes
+    }
+
+    /// Returns None if the bytes aren't a cached tile
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.strip_prefix(&[CACHED_TILE_FORMAT])?;
+        let etag_length = u16::from_le_bytes(bytes.get(..2)?.try_into().unwrap()) as usize;
+        let etag = bytes.get(2..2 + etag_length)?;
+        let bytes = &bytes[2 + etag_length..];
+        let last_modified = u64::from_le_bytes(bytes.get(..8)?.try_into().unwrap());
+        Some(Self {
+            etag: String::from_utf8(etag.to_vec()).ok()?,
+            last_modified: SystemTime::UNIX_EPOCH + Duration::from_secs(last_modified),
+            mvt_bytes: bytes[8..].to_vec(),
+        })
+    }
+}
+
+/// Gets a cached tile with its entity tag
+/// Returns None if the tile is not in the cache.
+pub async fn get_tile(cache: &TileCache, key: &str) -> Result<Option<CachedTile>> {
+    Ok(get(cache, key)
+        .await?
+        .and_then(|bytes| CachedTile::from_bytes(&bytes)))
+}
+
+/// Caches a tile with its entity tag
+pub async fn set_tile(cache: &TileCache, key: &str, tile: &CachedTile) -> Result<()> {
+    set(cache, key, tile.to_bytes()).await
+}
+
+/// Number of tiles served from the cache or rendered from the database
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheCounters {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Tile cache hits and misses since editoast started, indexed by view cache prefix
+#[derive(Debug, Default)]
+pub struct TileCacheStats(Mutex<HashMap<String, CacheCounters>>);
+
+impl TileCacheStats {
+    /// Records a tile request of a layer view
+    pub fn record(&self, view_prefix: &str, hit: bool) {
+        let mut counters = self.0.lock().unwrap();
+        let counters = counters.entry(view_prefix.to_string()).or_default();
+        if hit {
+            counters.hits += 1;
+        } else {
+            counters.misses += 1;
+        }
+    }
+
+    pub fn counters(&self, view_prefix: &str) -> CacheCounters {
+        self.0
+            .lock()
+            .unwrap()
+            .get(view_prefix)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        delete, get, get_tile, keys, matches_pattern, memory_usage, set, set_tile, CacheCounters,
+        CachedTile, TileCache, TileCacheStats, MIN_RETRY_DELAY,
+    };
+    use actix_web::test as actix_test;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn glob_patterns() {
+        assert!(matches_pattern("editoast.layer.*", "editoast.layer.1.tile"));
+        assert!(matches_pattern("*.tile", "editoast.layer.1.tile"));
+        assert!(matches_pattern("a*b*c", "a_b_c"));
+        assert!(matches_pattern("abc", "abc"));
+        assert!(!matches_pattern("abc", "abcd"));
+        assert!(!matches_pattern("a*bc", "abc_"));
+        assert!(!matches_pattern("ab*ba", "aba"));
+    }
+
+    #[actix_test]
+    async fn memory_cache_set_get_list_delete() {
+        let cache = TileCache::new_memory(2);
+        set(&cache, "test_1", vec![1]).await.unwrap();
+        set(&cache, "test_2", vec![2]).await.unwrap();
+        assert_eq!(get(&cache, "test_1").await.unwrap(), Some(vec![1]));
+        // The least recently used tile is evicted
+        set(&cache, "test_3", vec![3]).await.unwrap();
+        assert!(get(&cache, "test_2").await.unwrap().is_none());
+
+        let mut test_keys = keys(&cache, "test_*").await.unwrap();
+        test_keys.sort();
+        assert_eq!(test_keys, vec!["test_1", "test_3"]);
+        let deleted = delete(&cache, vec!["test_1".into(), "test_2".into()])
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(keys(&cache, "*").await.unwrap(), vec!["test_3"]);
+        assert_eq!(
+            memory_usage(&cache, &["test_3".into(), "test_1".into()])
+                .await
+                .unwrap(),
+            vec![7, 0]
+        );
+    }
+
+    #[actix_test]
+    async fn unreachable_redis_fails_fast() {
+        let cache = TileCache::new_redis("redis://127.0.0.1:1").unwrap();
+        let redis = match &cache {
+            TileCache::Redis(redis) => redis.clone(),
+            TileCache::Memory(_) => unreachable!(),
+        };
+        assert!(cache.ping().await.is_err());
+        let (retry_at, delay) = redis.state.lock().unwrap().retry.unwrap();
+        assert_eq!(delay, MIN_RETRY_DELAY);
+        // No connection is attempted before the retry delay is elapsed
+        assert!(get(&cache, "test").await.is_err());
+        assert_eq!(redis.state.lock().unwrap().retry, Some((retry_at, delay)));
+    }
+
+    #[actix_test]
+    async fn cached_tile_with_etag() {
+        let cache = TileCache::new_memory(2);
+        let tile = CachedTile {
+            etag: "1-1-abc".into(),
+            last_modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1_000),
+            mvt_bytes: vec![1, 2, 3],
+        };
+        set_tile(&cache, "tile", &tile).await.unwrap();
+        assert_eq!(get_tile(&cache, "tile").await.unwrap(), Some(tile));
+        set(&cache, "invalid", vec![42]).await.unwrap();
+        assert!(get_tile(&cache, "invalid").await.unwrap().is_none());
+        // Raw tiles of the previous layout are not read as cached tiles
+        set(&cache, "raw", vec![0x1a, 0, 1, 2]).await.unwrap();
+        assert!(get_tile(&cache, "raw").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn cache_stats() {
+        let stats = TileCacheStats::default();
+        stats.record("view", true);
+        stats.record("view", false);
+        stats.record("view", true);
+        assert_eq!(stats.counters("view"), CacheCounters { hits: 2, misses: 1 });
+        assert_eq!(stats.counters("other_view"), CacheCounters::default());
+    }
+}
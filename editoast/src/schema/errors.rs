@@ -1,7 +1,7 @@
 use super::{OSRDIdentified, OSRDObject, ObjectType};
 use crate::schema::ObjectRef;
 use serde::{Deserialize, Serialize};
-use strum_macros::EnumVariantNames;
+use strum_macros::{EnumVariantNames, IntoStaticStr};
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
@@ -14,14 +14,26 @@ pub struct InfraError {
     sub_type: InfraErrorType,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, EnumVariantNames)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, EnumVariantNames, IntoStaticStr)]
 #[strum(serialize_all = "snake_case")]
 #[serde(tag = "error_type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum InfraErrorType {
+    CloseDetectors {
+        reference: ObjectRef,
+        distance: f64,
+    },
+    DanglingSwitchPort {
+        port_name: String,
+    },
+    DegenerateGeometry,
+    DetectorAtTrackEnd,
     DuplicatedGroup {
         original_group_path: String,
     },
     EmptyObject,
+    InconsistentLength {
+        geo_length: f64,
+    },
     InvalidGroup {
         group: String,
         switch_type: String,
@@ -30,9 +42,30 @@ pub enum InfraErrorType {
         reference: ObjectRef,
     },
     InvalidRoute,
+    InvalidSpeedLimit {
+        speed_limit: f64,
+        expected_range: [f64; 2],
+    },
     InvalidSwitchPorts,
+    LinkedDetectorTooFar {
+        reference: ObjectRef,
+        max_distance: f64,
+    },
+    LongDetectionSection {
+        length: f64,
+        max_length: f64,
+    },
+    MisplacedSwitchPort {
+        port_name: String,
+        distance: f64,
+        max_distance: f64,
+    },
     MissingRoute,
+    MissingSpeedLimit,
     NoBufferStop,
+    NoRouteEntry {
+        reference: ObjectRef,
+    },
     ObjectOutOfPath {
         reference: ObjectRef,
     },
@@ -40,15 +73,31 @@ pub enum InfraErrorType {
         position: f64,
         expected_range: [f64; 2],
     },
+    OutOfSpeedSection,
+    OverlappingRanges {
+        overlapping_field: String,
+    },
+    OverlappingSignals {
+        reference: ObjectRef,
+    },
+    OverlappingSpeedSections {
+        reference: ObjectRef,
+    },
     OverlappingSwitches {
         reference: ObjectRef,
     },
     OverlappingTrackLinks {
         reference: ObjectRef,
     },
+    SelfIntersectingGeometry {
+        point: [f64; 2],
+    },
     UnknownPortName {
         port_name: String,
     },
+    UnknownSignalingSystem {
+        signaling_system: String,
+    },
     UnusedPort {
         port_name: String,
     },
@@ -236,6 +285,251 @@ impl InfraError {
             sub_type: InfraErrorType::OverlappingTrackLinks { reference },
         }
     }
+
+    pub fn new_degenerate_geometry<T: AsRef<str>, O: OSRDObject>(obj: &O, field: T) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: false,
+            sub_type: InfraErrorType::DegenerateGeometry,
+        }
+    }
+
+    pub fn new_inconsistent_length<O: OSRDObject>(obj: &O, geo_length: f64) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: "length".into(),
+            is_warning: true,
+            sub_type: InfraErrorType::InconsistentLength { geo_length },
+        }
+    }
+
+    pub fn new_overlapping_ranges<T: AsRef<str>, U: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        field: T,
+        overlapping_field: U,
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: false,
+            sub_type: InfraErrorType::OverlappingRanges {
+                overlapping_field: overlapping_field.as_ref().into(),
+            },
+        }
+    }
+
+    pub fn new_self_intersecting_geometry<T: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        field: T,
+        point: [f64; 2],
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: true,
+            sub_type: InfraErrorType::SelfIntersectingGeometry { point },
+        }
+    }
+
+    pub fn new_linked_detector_too_far<O: OSRDObject, T: AsRef<str>>(
+        obj: &O,
+        detector: T,
+        max_distance: f64,
+    ) -> Self {
+        let reference = ObjectRef::new(ObjectType::Detector, detector);
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: "linked_detector".into(),
+            is_warning: true,
+            sub_type: InfraErrorType::LinkedDetectorTooFar {
+                reference,
+                max_distance,
+            },
+        }
+    }
+
+    pub fn new_no_route_entry<T: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        field: T,
+        reference: ObjectRef,
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: true,
+            sub_type: InfraErrorType::NoRouteEntry { reference },
+        }
+    }
+
+    pub fn new_overlapping_signals<O: OSRDObject, T: AsRef<str>>(obj: &O, other: T) -> Self {
+        let reference = ObjectRef::new(ObjectType::Signal, other);
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: Default::default(),
+            is_warning: false,
+            sub_type: InfraErrorType::OverlappingSignals { reference },
+        }
+    }
+
+    pub fn new_unknown_signaling_system<T: AsRef<str>, U: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        field: T,
+        signaling_system: U,
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: false,
+            sub_type: InfraErrorType::UnknownSignalingSystem {
+                signaling_system: signaling_system.as_ref().into(),
+            },
+        }
+    }
+
+    pub fn new_invalid_speed_limit<T: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        field: T,
+        speed_limit: f64,
+        expected_range: [f64; 2],
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: false,
+            sub_type: InfraErrorType::InvalidSpeedLimit {
+                speed_limit,
+                expected_range,
+            },
+        }
+    }
+
+    pub fn new_missing_speed_limit<O: OSRDObject>(obj: &O) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: "speed_limit".into(),
+            is_warning: true,
+            sub_type: InfraErrorType::MissingSpeedLimit,
+        }
+    }
+
+    pub fn new_out_of_speed_section<T: AsRef<str>, O: OSRDObject>(obj: &O, field: T) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: true,
+            sub_type: InfraErrorType::OutOfSpeedSection,
+        }
+    }
+
+    pub fn new_overlapping_speed_sections<T: AsRef<str>, U: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        field: T,
+        other: U,
+    ) -> Self {
+        let reference = ObjectRef::new(ObjectType::SpeedSection, other);
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: field.as_ref().into(),
+            is_warning: false,
+            sub_type: InfraErrorType::OverlappingSpeedSections { reference },
+        }
+    }
+
+    pub fn new_dangling_switch_port<T: AsRef<str>, O: OSRDObject>(obj: &O, port_name: T) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: format!("ports.{}", port_name.as_ref()),
+            is_warning: false,
+            sub_type: InfraErrorType::DanglingSwitchPort {
+                port_name: port_name.as_ref().into(),
+            },
+        }
+    }
+
+    pub fn new_misplaced_switch_port<T: AsRef<str>, O: OSRDObject>(
+        obj: &O,
+        port_name: T,
+        distance: f64,
+        max_distance: f64,
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: format!("ports.{}", port_name.as_ref()),
+            is_warning: true,
+            sub_type: InfraErrorType::MisplacedSwitchPort {
+                port_name: port_name.as_ref().into(),
+                distance,
+                max_distance,
+            },
+        }
+    }
+
+    pub fn new_close_detectors<O: OSRDObject, T: AsRef<str>>(
+        obj: &O,
+        other: T,
+        distance: f64,
+    ) -> Self {
+        let reference = ObjectRef::new(ObjectType::Detector, other);
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: "position".into(),
+            is_warning: false,
+            sub_type: InfraErrorType::CloseDetectors {
+                reference,
+                distance,
+            },
+        }
+    }
+
+    pub fn new_detector_at_track_end<O: OSRDObject>(obj: &O) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: "position".into(),
+            is_warning: true,
+            sub_type: InfraErrorType::DetectorAtTrackEnd,
+        }
+    }
+
+    pub fn new_long_detection_section<O: OSRDObject>(
+        obj: &O,
+        length: f64,
+        max_length: f64,
+    ) -> Self {
+        Self {
+            obj_id: obj.get_id().clone(),
+            obj_type: obj.get_type(),
+            field: Default::default(),
+            is_warning: true,
+            sub_type: InfraErrorType::LongDetectionSection { length, max_length },
+        }
+    }
+
+    /// Return the snake case name of the error type (ex: `invalid_reference`)
+    pub fn get_error_type(&self) -> &'static str {
+        (&self.sub_type).into()
+    }
+
+    /// Override the severity of the error
+    pub fn set_warning(&mut self, is_warning: bool) {
+        self.is_warning = is_warning;
+    }
 }
 
 impl OSRDIdentified for InfraError {
@@ -7,6 +7,10 @@ mod geo_json;
 pub mod operation;
 mod operational_point;
 mod railjson;
+mod railjson_csv;
+mod railjson_merge;
+mod railjson_migration;
+mod railjson_stream;
 mod route;
 mod signal;
 mod speed_section;
@@ -24,6 +28,12 @@ pub use errors::{InfraError, InfraErrorType};
 pub use geo_json::GeoJson;
 pub use operational_point::{OperationalPoint, OperationalPointCache, OperationalPointPart};
 pub use railjson::{find_objects, RailJson, RailjsonError};
+pub use railjson_csv::{csv_update_operations, objects_to_csv};
+pub use railjson_merge::{
+    merge_operations, railjson_fragment_from_value, MergeConflictPolicy, MergeReport,
+};
+pub use railjson_migration::migrate_railjson;
+pub use railjson_stream::persist_railjson_file;
 pub use route::Route;
 use serde::{Deserialize, Serialize};
 pub use signal::{Signal, SignalCache};
@@ -32,7 +42,7 @@ use strum_macros::Display;
 use strum_macros::EnumIter;
 pub use switch::{Switch, SwitchCache};
 pub use switch_type::{SwitchPortConnection, SwitchType};
-pub use track_section::{LineString, TrackSection, TrackSectionCache};
+pub use track_section::{haversine_distance, LineString, TrackSection, TrackSectionCache};
 pub use track_section_link::TrackSectionLink;
 
 use self::utils::{Identifier, NonBlankString};
@@ -97,6 +107,23 @@ impl ObjectType {
         }
     }
 
+    /// Returns the name of the railjson field listing the objects of this type.
+    pub fn get_railjson_field(&self) -> &'static str {
+        match *self {
+            ObjectType::TrackSection => "track_sections",
+            ObjectType::Signal => "signals",
+            ObjectType::SpeedSection => "speed_sections",
+            ObjectType::Detector => "detectors",
+            ObjectType::TrackSectionLink => "track_section_links",
+            ObjectType::Switch => "switches",
+            ObjectType::SwitchType => "switch_types",
+            ObjectType::BufferStop => "buffer_stops",
+            ObjectType::Route => "routes",
+            ObjectType::OperationalPoint => "operational_points",
+            ObjectType::Catenary => "catenaries",
+        }
+    }
+
     /// Returns the layer table name.
     /// Returns `None` for objects that doesn't have a layer such as routes or switch types.
     pub fn get_geometry_layer_table(&self) -> Option<&'static str> {
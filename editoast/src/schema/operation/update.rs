@@ -18,6 +18,14 @@ pub struct UpdateOperation {
 }
 
 impl UpdateOperation {
+    pub fn new(obj_type: ObjectType, obj_id: String, railjson_patch: Patch) -> Self {
+        Self {
+            obj_id,
+            obj_type,
+            railjson_patch,
+        }
+    }
+
     pub fn apply(&self, infra_id: i64, conn: &mut PgConnection) -> Result<RailjsonObject> {
         // Load object
 
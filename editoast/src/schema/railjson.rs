@@ -1,3 +1,4 @@
+use super::railjson_migration::migrate_railjson;
 use super::{
     BufferStop, Catenary, Detector, OSRDTyped, OperationalPoint, Route, Signal, SpeedSection,
     Switch, SwitchType, TrackSection, TrackSectionLink,
@@ -12,6 +13,7 @@ use diesel::{
 };
 use editoast_derive::EditoastError;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
 
 #[derive(Deserialize, Derivative, Serialize, Clone, Debug)]
@@ -38,9 +40,18 @@ pub struct RailJson {
 pub enum RailjsonError {
     #[error("Wrong railjson version '{0}'. Should be {}", RAILJSON_VERSION)]
     WrongVersion(String),
+    #[error("Invalid railjson: {0}")]
+    InvalidRailjson(String),
 }
 
 impl RailJson {
+    /// Deserialize a railjson, upgrading it first if it uses an older version of the format
+    pub fn from_value(mut railjson: Value) -> Result<Self> {
+        migrate_railjson(&mut railjson)?;
+        serde_json::from_value(railjson)
+            .map_err(|err| RailjsonError::InvalidRailjson(err.to_string()).into())
+    }
+
     pub fn persist<T: AsRef<str>>(&self, infra_name: T, conn: &mut PgConnection) -> Result<Infra> {
         if self.version != RAILJSON_VERSION {
             return Err(RailjsonError::WrongVersion(self.version.clone()).into());
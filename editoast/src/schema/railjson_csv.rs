@@ -0,0 +1,507 @@
+use super::operation::{Operation, UpdateOperation};
+use super::ObjectType;
+use crate::error::Result;
+use editoast_derive::EditoastError;
+use json_patch::{AddOperation, Patch, PatchOperation, RemoveOperation, ReplaceOperation};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use thiserror::Error;
+
+/// Separator used to build column names from nested fields (ex: `extensions.sncf.line_code`)
+const COLUMN_SEPARATOR: char = '.';
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "railjson:csv")]
+pub enum CsvError {
+    #[error("Invalid csv: {0}")]
+    InvalidCsv(String),
+    #[error("Missing 'id' column")]
+    MissingIdColumn,
+    #[error("Object '{0}' is listed more than once")]
+    DuplicateObject(String),
+    #[error("Object '{0}' not found")]
+    ObjectNotFound(String),
+    #[error("Rows of object '{0}' have different values for field '{1}'")]
+    InconsistentRows(String, String),
+}
+
+/// Serialize railjson objects to csv.
+/// Nested objects are flattened into columns, arrays are written as json unless listed in `expand`,
+/// in which case the object is written once per element of the array.
+pub fn objects_to_csv(objects: &[Value], expand: Option<&str>) -> Result<String> {
+    let rows: Vec<_> = objects
+        .iter()
+        .flat_map(|object| flatten_object(object, expand))
+        .collect();
+
+    // `id` is always the first column, others are sorted to get a stable output
+    let columns: BTreeSet<_> = rows
+        .iter()
+        .flat_map(|row| row.keys())
+        .filter(|column| column.as_str() != "id")
+        .collect();
+    let columns: Vec<_> = std::iter::once("id")
+        .chain(columns.into_iter().map(String::as_str))
+        .collect();
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&columns).map_err(csv_error)?;
+    for row in rows.iter() {
+        let record = columns
+            .iter()
+            .map(|column| row.get(*column).map(String::as_str).unwrap_or_default());
+        writer.write_record(record).map_err(csv_error)?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|err| CsvError::InvalidCsv(err.to_string()))?;
+    Ok(String::from_utf8(data).expect("csv writer output is valid utf-8"))
+}
+
+/// Flatten a railjson object into one or several csv rows
+fn flatten_object(object: &Value, expand: Option<&str>) -> Vec<BTreeMap<String, String>> {
+    let mut row = BTreeMap::new();
+    flatten_value(object, "", &mut row);
+
+    let (field, elements) =
+        match expand.and_then(|field| Some((field, object.get(field)?.as_array()?))) {
+            Some((field, elements)) if !elements.is_empty() => (field, elements),
+            _ => return vec![row],
+        };
+    row.remove(field);
+    elements
+        .iter()
+        .map(|element| {
+            let mut element_row = row.clone();
+            flatten_value(element, field, &mut element_row);
+            element_row
+        })
+        .collect()
+}
+
+fn flatten_value(value: &Value, prefix: &str, row: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(fields) if !fields.is_empty() && !is_geometry(fields) => {
+            for (key, field) in fields {
+                let column = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}{COLUMN_SEPARATOR}{key}")
+                };
+                flatten_value(field, &column, row);
+            }
+        }
+        Value::Null => (),
+        Value::String(value) => {
+            row.insert(prefix.to_string(), value.clone());
+        }
+        value => {
+            row.insert(prefix.to_string(), value.to_string());
+        }
+    }
+}
+
+/// Geometries are kept as geojson in a single column
+fn is_geometry(fields: &Map<String, Value>) -> bool {
+    fields.contains_key("type") && fields.contains_key("coordinates")
+}
+
+/// Build the operations updating objects from a csv using the `objects_to_csv` layout.
+/// `objects` must contain the current railjson of every object listed in the csv.
+/// Empty cells remove the field, cells matching the current value are ignored.
+/// Rows sharing the same id (see `expand`) are gathered, each row giving an element of the expanded array.
+pub fn csv_update_operations(
+    obj_type: ObjectType,
+    data: &[u8],
+    objects: &HashMap<String, Value>,
+) -> Result<Vec<Operation>> {
+    let mut reader = csv::Reader::from_reader(data);
+    let columns: Vec<String> = reader
+        .headers()
+        .map_err(csv_error)?
+        .iter()
+        .map(String::from)
+        .collect();
+    let id_index = columns
+        .iter()
+        .position(|column| column == "id")
+        .ok_or(CsvError::MissingIdColumn)?;
+    let paths: Vec<Vec<_>> = columns
+        .iter()
+        .map(|column| column.split(COLUMN_SEPARATOR).collect())
+        .collect();
+
+    // Group the rows by object, keeping the order of the csv
+    let mut objects_rows: Vec<(String, Vec<csv::StringRecord>)> = vec![];
+    let mut objects_index = HashMap::new();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let obj_id = record.get(id_index).unwrap_or_default().to_string();
+        let index = *objects_index.entry(obj_id.clone()).or_insert_with(|| {
+            objects_rows.push((obj_id, vec![]));
+            objects_rows.len() - 1
+        });
+        objects_rows[index].1.push(record);
+    }
+
+    let mut operations = vec![];
+    for (obj_id, rows) in objects_rows {
+        let object = objects
+            .get(&obj_id)
+            .ok_or_else(|| CsvError::ObjectNotFound(obj_id.clone()))?;
+
+        // Columns of arrays and of their elements are gathered by array field
+        let mut expanded_columns: BTreeMap<Vec<&str>, Vec<usize>> = BTreeMap::new();
+        for (index, path) in paths.iter().enumerate() {
+            if let Some(depth) = (1..=path.len()).find(|depth| {
+                object
+                    .pointer(&to_pointer(&path[..*depth]))
+                    .is_some_and(Value::is_array)
+            }) {
+                expanded_columns
+                    .entry(path[..depth].to_vec())
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        let mut patch = vec![];
+        let mut handled_columns: HashSet<_> = [id_index].into();
+        let mut expanded = false;
+        for (field, field_columns) in expanded_columns {
+            // The column of the array itself holds the whole array as json, or its elements once expanded
+            // if they are scalars. Element columns hold the fields of expanded object elements.
+            let (array_column, element_columns): (Vec<_>, Vec<_>) = field_columns
+                .into_iter()
+                .partition(|index| paths[*index].len() == field.len());
+            let array_column = array_column.first().copied();
+            handled_columns.extend(element_columns.iter().copied());
+            let pointer = to_pointer(&field);
+            let current = object.pointer(&pointer).unwrap();
+            let current_element =
+                |position: usize| current.get(position).or_else(|| current.get(0));
+            let elements = if rows
+                .iter()
+                .any(|row| element_columns.iter().any(|index| !row[*index].is_empty()))
+            {
+                rows.iter()
+                    .enumerate()
+                    .map(|(position, row)| {
+                        let mut element = Value::Object(Map::new());
+                        for index in element_columns.iter() {
+                            let sub_path = &paths[*index][field.len()..];
+                            let current_value = current_element(position)
+                                .and_then(|e| e.pointer(&to_pointer(sub_path)));
+                            if !row[*index].is_empty() {
+                                insert_value(
+                                    &mut element,
+                                    sub_path,
+                                    parse_cell(current_value, &row[*index]),
+                                );
+                            }
+                        }
+                        element
+                    })
+                    .collect()
+            } else if let Some(index) =
+                array_column.filter(|index| has_scalar_elements(&rows, *index))
+            {
+                rows.iter()
+                    .enumerate()
+                    .map(|(position, row)| match &row[index] {
+                        "" => Value::Null,
+                        cell => parse_cell(current_element(position), cell),
+                    })
+                    .collect()
+            } else {
+                // The array was not expanded for this object (ex: empty array)
+                continue;
+            };
+            expanded = true;
+            handled_columns.extend(array_column);
+            let value = Value::Array(elements);
+            if !same_value(current, &value) {
+                patch.push(PatchOperation::Replace(ReplaceOperation {
+                    path: pointer,
+                    value,
+                }));
+            }
+        }
+        if rows.len() > 1 && !expanded {
+            return Err(CsvError::DuplicateObject(obj_id).into());
+        }
+
+        for (index, path) in paths.iter().enumerate() {
+            if handled_columns.contains(&index) {
+                continue;
+            }
+            let cell = &rows[0][index];
+            if rows.iter().any(|row| &row[index] != cell) {
+                return Err(CsvError::InconsistentRows(obj_id, columns[index].clone()).into());
+            }
+            if let Some(operation) = cell_operation(object, path, cell) {
+                patch.push(operation);
+            }
+        }
+        if !patch.is_empty() {
+            operations.push(Operation::Update(UpdateOperation::new(
+                obj_type,
+                obj_id,
+                Patch(patch),
+            )));
+        }
+    }
+    Ok(operations)
+}
+
+/// Whether the rows of an object hold the elements of an expanded scalar array in the given column,
+/// instead of the whole array as json
+fn has_scalar_elements(rows: &[csv::StringRecord], index: usize) -> bool {
+    if rows.len() > 1 {
+        return true;
+    }
+    let cell = &rows[0][index];
+    !cell.is_empty() && !serde_json::from_str::<Value>(cell).is_ok_and(|value| value.is_array())
+}
+
+/// Compute the patch operation setting a field to the value of a cell
+fn cell_operation(object: &Value, path: &[&str], cell: &str) -> Option<PatchOperation> {
+    let pointer = to_pointer(path);
+    let current = object.pointer(&pointer);
+
+    if cell.is_empty() {
+        return current.map(|_| PatchOperation::Remove(RemoveOperation { path: pointer }));
+    }
+
+    let value = parse_cell(current, cell);
+    if let Some(current) = current {
+        if same_value(current, &value) {
+            return None;
+        }
+        return Some(PatchOperation::Replace(ReplaceOperation {
+            path: pointer,
+            value,
+        }));
+    }
+
+    // Missing parents are created along with the field
+    let depth = (1..path.len())
+        .rev()
+        .find(|depth| object.pointer(&to_pointer(&path[..*depth])).is_some())
+        .unwrap_or(0);
+    let value = path[depth + 1..].iter().rev().fold(value, |value, key| {
+        Value::Object(Map::from_iter([(key.to_string(), value)]))
+    });
+    Some(PatchOperation::Add(AddOperation {
+        path: to_pointer(&path[..=depth]),
+        value,
+    }))
+}
+
+/// Parse a cell, strings are kept as is if the current value of the field is a string
+fn parse_cell(current: Option<&Value>, cell: &str) -> Value {
+    match current {
+        Some(Value::String(_)) => Value::String(cell.to_string()),
+        _ => serde_json::from_str(cell).unwrap_or_else(|_| Value::String(cell.to_string())),
+    }
+}
+
+/// Set a nested field of an object, creating the missing parents
+fn insert_value(object: &mut Value, path: &[&str], value: Value) {
+    let (key, parents) = path.split_last().unwrap();
+    let mut target = object;
+    for parent in parents {
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        target = target
+            .as_object_mut()
+            .unwrap()
+            .entry(parent.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    target
+        .as_object_mut()
+        .unwrap()
+        .insert(key.to_string(), value);
+}
+
+/// Compare two values, numbers are compared whatever their representation (ex: `1` and `1.0`)
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same_value(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn to_pointer(path: &[&str]) -> String {
+    path.iter()
+        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn csv_error(err: csv::Error) -> CsvError {
+    CsvError::InvalidCsv(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_update_operations, objects_to_csv};
+    use crate::schema::operation::Operation;
+    use crate::schema::ObjectType;
+    use serde_json::{json, to_value, Value};
+    use std::collections::HashMap;
+
+    fn operational_point() -> Value {
+        json!({
+            "id": "op",
+            "parts": [
+                { "track": "A", "position": 10. },
+                { "track": "B", "position": 20. },
+            ],
+            "extensions": {
+                "sncf": { "ci": 1, "ch": "BV", "trigram": "ABC" },
+            },
+        })
+    }
+
+    #[test]
+    fn export_flattened() {
+        let csv = objects_to_csv(&[operational_point()], None).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "id,extensions.sncf.ch,extensions.sncf.ci,extensions.sncf.trigram,parts"
+        );
+        assert!(lines.next().unwrap().starts_with("op,BV,1,ABC,"));
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn export_expanded() {
+        let csv = objects_to_csv(&[operational_point()], Some("parts")).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(",parts.position,parts.track"));
+        assert!(lines[1].ends_with(",10.0,A"));
+        assert!(lines[2].ends_with(",20.0,B"));
+    }
+
+    #[test]
+    fn import_updates() {
+        let objects = HashMap::from([("op".to_string(), operational_point())]);
+        let csv = "id,extensions.sncf.trigram,extensions.sncf.ci,extensions.sncf.ch,extensions.sncf.ch_label\nop,XYZ,1,,Label\n";
+        let operations =
+            csv_update_operations(ObjectType::OperationalPoint, csv.as_bytes(), &objects).unwrap();
+        assert_eq!(operations.len(), 1);
+        let patch = match &operations[0] {
+            Operation::Update(update) => to_value(update).unwrap()["railjson_patch"].clone(),
+            _ => panic!("An update operation is expected"),
+        };
+        assert_eq!(
+            patch,
+            json!([
+                { "op": "replace", "path": "/extensions/sncf/trigram", "value": "XYZ" },
+                { "op": "remove", "path": "/extensions/sncf/ch" },
+                { "op": "add", "path": "/extensions/sncf/ch_label", "value": "Label" },
+            ])
+        );
+    }
+
+    #[test]
+    fn import_expanded() {
+        let objects = HashMap::from([("op".to_string(), operational_point())]);
+        let csv = objects_to_csv(&[operational_point()], Some("parts")).unwrap();
+        let operations =
+            csv_update_operations(ObjectType::OperationalPoint, csv.as_bytes(), &objects).unwrap();
+        assert!(operations.is_empty());
+
+        let csv = csv.replace(",20.0,B", ",25.0,B");
+        let operations =
+            csv_update_operations(ObjectType::OperationalPoint, csv.as_bytes(), &objects).unwrap();
+        assert_eq!(operations.len(), 1);
+        let patch = match &operations[0] {
+            Operation::Update(update) => to_value(update).unwrap()["railjson_patch"].clone(),
+            _ => panic!("An update operation is expected"),
+        };
+        assert_eq!(
+            patch,
+            json!([{
+                "op": "replace",
+                "path": "/parts",
+                "value": [
+                    { "track": "A", "position": 10. },
+                    { "track": "B", "position": 25. },
+                ],
+            }])
+        );
+    }
+
+    #[test]
+    fn import_expanded_scalars() {
+        let switch_type = json!({ "id": "point", "ports": ["BASE", "LEFT"], "groups": {} });
+        let objects = HashMap::from([("point".to_string(), switch_type.clone())]);
+        let csv = objects_to_csv(&[switch_type], Some("ports")).unwrap();
+        let operations =
+            csv_update_operations(ObjectType::SwitchType, csv.as_bytes(), &objects).unwrap();
+        assert!(operations.is_empty());
+
+        let csv = csv.replace(",LEFT", ",RIGHT");
+        let operations =
+            csv_update_operations(ObjectType::SwitchType, csv.as_bytes(), &objects).unwrap();
+        assert_eq!(operations.len(), 1);
+        let patch = match &operations[0] {
+            Operation::Update(update) => to_value(update).unwrap()["railjson_patch"].clone(),
+            _ => panic!("An update operation is expected"),
+        };
+        assert_eq!(
+            patch,
+            json!([{ "op": "replace", "path": "/ports", "value": ["BASE", "RIGHT"] }])
+        );
+
+        // Arrays written as json are still updated as a whole
+        let csv = "id,groups,ports\npoint,{},\"[\"\"BASE\"\"]\"\n";
+        let operations =
+            csv_update_operations(ObjectType::SwitchType, csv.as_bytes(), &objects).unwrap();
+        let patch = match &operations[0] {
+            Operation::Update(update) => to_value(update).unwrap()["railjson_patch"].clone(),
+            _ => panic!("An update operation is expected"),
+        };
+        assert_eq!(
+            patch,
+            json!([{ "op": "replace", "path": "/ports", "value": ["BASE"] }])
+        );
+    }
+
+    #[test]
+    fn import_inconsistent_rows() {
+        let objects = HashMap::from([("op".to_string(), operational_point())]);
+        let csv = objects_to_csv(&[operational_point()], Some("parts")).unwrap();
+        let csv = csv.replacen("ABC", "XYZ", 1);
+        assert!(
+            csv_update_operations(ObjectType::OperationalPoint, csv.as_bytes(), &objects).is_err()
+        );
+    }
+
+    #[test]
+    fn import_unknown_object() {
+        let csv = "id,name\nunknown,test\n";
+        assert!(csv_update_operations(
+            ObjectType::OperationalPoint,
+            csv.as_bytes(),
+            &HashMap::new()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use super::operation::{DeleteOperation, Operation, RailjsonObject};
+use super::{OSRDIdentified, OSRDObject, ObjectRef, ObjectType, RailJson, RailjsonError};
+use crate::error::Result;
+use crate::infra::RAILJSON_VERSION;
+use crate::infra_cache::InfraCache;
+use derivative::Derivative;
+use editoast_derive::EditoastError;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use strum::IntoEnumIterator;
+use thiserror::Error;
+
+/// How to handle railjson objects whose id is already used in the infra
+#[derive(Debug, Derivative, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[derivative(Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeConflictPolicy {
+    /// Keep the existing object
+    #[derivative(Default)]
+    Skip,
+    /// Replace the existing object
+    Overwrite,
+    /// Import the object with a prefixed id
+    Rename,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MergeReport {
+    pub created: usize,
+    pub skipped: Vec<ObjectRef>,
+    pub overwritten: Vec<ObjectRef>,
+    pub renamed: Vec<RenamedObject>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenamedObject {
+    #[serde(flatten)]
+    pub obj_ref: ObjectRef,
+    pub new_id: String,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "railjson:merge")]
+pub enum MergeError {
+    #[error("A non empty prefix is required to rename conflicting objects")]
+    EmptyPrefix,
+    #[error("Renamed object '{0}' conflicts with an existing object")]
+    RenamedIdConflict(String),
+}
+
+/// Deserialize a railjson fragment, missing object lists are considered empty
+pub fn railjson_fragment_from_value(mut railjson: Value) -> Result<RailJson> {
+    if let Some(fragment) = railjson.as_object_mut() {
+        fragment
+            .entry("version")
+            .or_insert_with(|| RAILJSON_VERSION.into());
+        for obj_type in ObjectType::iter() {
+            fragment
+                .entry(obj_type.get_railjson_field())
+                .or_insert_with(|| Value::Array(vec![]));
+        }
+    }
+    RailJson::from_value(railjson)
+}
+
+/// Compute the operations merging a railjson fragment into an infra.
+/// Objects whose id is already used in the infra are handled according to the given policy.
+pub fn merge_operations(
+    railjson: RailJson,
+    infra_cache: &InfraCache,
+    policy: MergeConflictPolicy,
+    prefix: &str,
+) -> Result<(Vec<Operation>, MergeReport)> {
+    let objects = into_objects(railjson);
+    let is_conflict = |obj: &RailjsonObject| {
+        infra_cache
+            .get_objects_by_type(obj.get_type())
+            .contains_key(obj.get_id())
+    };
+
+    let mut report = MergeReport::default();
+    let mut operations = vec![];
+    match policy {
+        MergeConflictPolicy::Skip => {
+            for obj in objects {
+                if is_conflict(&obj) {
+                    report.skipped.push(obj.get_ref());
+                } else {
+                    operations.push(Operation::Create(Box::new(obj)));
+                }
+            }
+        }
+        MergeConflictPolicy::Overwrite => {
+            for obj in objects {
+                if is_conflict(&obj) {
+                    operations.push(Operation::Delete(DeleteOperation::from(obj.get_ref())));
+                    report.overwritten.push(obj.get_ref());
+                }
+                operations.push(Operation::Create(Box::new(obj)));
+            }
+        }
+        MergeConflictPolicy::Rename => {
+            if prefix.is_empty() {
+                return Err(MergeError::EmptyPrefix.into());
+            }
+            let fragment_refs: HashSet<_> = objects.iter().map(|obj| obj.get_ref()).collect();
+            let mut renamed = HashMap::new();
+            for obj in objects.iter().filter(|obj| is_conflict(obj)) {
+                let new_ref = ObjectRef::new(obj.get_type(), format!("{prefix}{}", obj.get_id()));
+                if fragment_refs.contains(&new_ref)
+                    || infra_cache
+                        .get_objects_by_type(new_ref.obj_type)
+                        .contains_key(&new_ref.obj_id)
+                {
+                    return Err(MergeError::RenamedIdConflict(new_ref.obj_id).into());
+                }
+                renamed.insert(obj.get_ref(), new_ref.obj_id);
+            }
+
+            for obj in objects {
+                let obj_ref = obj.get_ref();
+                let mut data = obj.get_data();
+                rename_references(&mut data, &renamed);
+                if let Some(new_id) = renamed.get(&obj_ref) {
+                    data["id"] = new_id.clone().into();
+                    report.renamed.push(RenamedObject {
+                        obj_ref: obj_ref.clone(),
+                        new_id: new_id.clone(),
+                    });
+                }
+                let obj: RailjsonObject = serde_json::from_value(json!({
+                    "obj_type": obj_ref.obj_type.to_string(),
+                    "railjson": data,
+                }))
+                .map_err(|err| RailjsonError::InvalidRailjson(err.to_string()))?;
+                operations.push(Operation::Create(Box::new(obj)));
+            }
+        }
+    }
+    report.created = operations
+        .iter()
+        .filter(|op| matches!(op, Operation::Create(_)))
+        .count();
+    Ok((operations, report))
+}
+
+fn into_objects(railjson: RailJson) -> Vec<RailjsonObject> {
+    let mut objects: Vec<RailjsonObject> = vec![];
+    objects.extend(railjson.track_sections.into_iter().map(Into::into));
+    objects.extend(railjson.track_section_links.into_iter().map(Into::into));
+    objects.extend(railjson.switch_types.into_iter().map(Into::into));
+    objects.extend(railjson.switches.into_iter().map(Into::into));
+    objects.extend(railjson.detectors.into_iter().map(Into::into));
+    objects.extend(railjson.buffer_stops.into_iter().map(Into::into));
+    objects.extend(railjson.signals.into_iter().map(Into::into));
+    objects.extend(railjson.routes.into_iter().map(Into::into));
+    objects.extend(railjson.speed_sections.into_iter().map(Into::into));
+    objects.extend(railjson.catenaries.into_iter().map(Into::into));
+    objects.extend(railjson.operational_points.into_iter().map(Into::into));
+    objects
+}
+
+fn rename_id(value: &mut Value, obj_type: ObjectType, renamed: &HashMap<ObjectRef, String>) {
+    if let Some(new_id) = value
+        .as_str()
+        .and_then(|id| renamed.get(&ObjectRef::new(obj_type, id)))
+    {
+        *value = new_id.clone().into();
+    }
+}
+
+/// Update the references of a railjson object to renamed objects
+fn rename_references(value: &mut Value, renamed: &HashMap<ObjectRef, String>) {
+    match value {
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| rename_references(value, renamed)),
+        Value::Object(object) => {
+            // Waypoints reference either a detector or a buffer stop
+            let waypoint_type = match object.get("type").and_then(Value::as_str) {
+                Some("Detector") => Some(ObjectType::Detector),
+                Some("BufferStop") => Some(ObjectType::BufferStop),
+                _ => None,
+            };
+            for (key, field) in object.iter_mut() {
+                match (key.as_str(), waypoint_type) {
+                    ("id", Some(obj_type)) => rename_id(field, obj_type, renamed),
+                    ("track", _) => rename_id(field, ObjectType::TrackSection, renamed),
+                    ("switch_type", _) => rename_id(field, ObjectType::SwitchType, renamed),
+                    ("linked_detector", _) => rename_id(field, ObjectType::Detector, renamed),
+                    ("release_detectors", _) => field
+                        .as_array_mut()
+                        .into_iter()
+                        .flatten()
+                        .for_each(|detector| rename_id(detector, ObjectType::Detector, renamed)),
+                    ("switches_directions", _) => {
+                        if let Value::Object(directions) = field {
+                            *directions = std::mem::take(directions)
+                                .into_iter()
+                                .map(|(switch, group)| {
+                                    let switch_ref = ObjectRef::new(ObjectType::Switch, &switch);
+                                    (renamed.get(&switch_ref).cloned().unwrap_or(switch), group)
+                                })
+                                .collect();
+                        }
+                    }
+                    _ => rename_references(field, renamed),
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_operations, railjson_fragment_from_value, MergeConflictPolicy, MergeError};
+    use crate::error::EditoastError;
+    use crate::infra_cache::tests::create_small_infra_cache;
+    use crate::schema::operation::Operation;
+    use crate::schema::OSRDIdentified;
+    use serde_json::json;
+
+    fn fragment() -> crate::schema::RailJson {
+        railjson_fragment_from_value(json!({
+            "track_sections": [{
+                "id": "A",
+                "length": 500.,
+                "slopes": [],
+                "curves": [],
+                "geo": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+                "sch": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+            }],
+            "detectors": [{
+                "id": "new_detector",
+                "track": "A",
+                "position": 10.,
+                "applicable_directions": "BOTH",
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn merge_skip() {
+        let infra_cache = create_small_infra_cache();
+        let (operations, report) =
+            merge_operations(fragment(), &infra_cache, MergeConflictPolicy::Skip, "").unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(report.created, 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].obj_id, "A");
+    }
+
+    #[test]
+    fn merge_overwrite() {
+        let infra_cache = create_small_infra_cache();
+        let (operations, report) =
+            merge_operations(fragment(), &infra_cache, MergeConflictPolicy::Overwrite, "").unwrap();
+        assert_eq!(operations.len(), 3);
+        assert!(matches!(operations[0], Operation::Delete(_)));
+        assert_eq!(report.created, 2);
+        assert_eq!(report.overwritten.len(), 1);
+    }
+
+    #[test]
+    fn merge_rename() {
+        let infra_cache = create_small_infra_cache();
+        let (operations, report) = merge_operations(
+            fragment(),
+            &infra_cache,
+            MergeConflictPolicy::Rename,
+            "new_",
+        )
+        .unwrap();
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].new_id, "new_A");
+        let ids: Vec<_> = operations
+            .iter()
+            .map(|op| match op {
+                Operation::Create(obj) => obj.get_id().clone(),
+                _ => panic!("Only creations are expected"),
+            })
+            .collect();
+        assert_eq!(ids, vec!["new_A", "new_detector"]);
+        match &operations[1] {
+            Operation::Create(obj) => assert_eq!(obj.get_data()["track"], "new_A"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn merge_rename_empty_prefix() {
+        let infra_cache = create_small_infra_cache();
+        let err = merge_operations(fragment(), &infra_cache, MergeConflictPolicy::Rename, "")
+            .err()
+            .unwrap();
+        assert_eq!(err.get_type(), MergeError::EmptyPrefix.get_type());
+    }
+}
@@ -0,0 +1,285 @@
+use super::RailjsonError;
+use crate::error::Result;
+use crate::infra::RAILJSON_VERSION;
+use serde_json::{Map, Value};
+
+/// A step of the railjson upgrade chain
+struct Migration {
+    /// Whether the migration applies to a railjson of the given version
+    accepts: fn(&str) -> bool,
+    /// Version of the railjson once migrated
+    target: &'static str,
+    /// Upgrade the railjson, describing every change in the given list
+    apply: fn(&mut Map<String, Value>, &mut Vec<String>),
+}
+
+/// Migrations ordered from the oldest supported version to the current one
+const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        accepts: |version| version.starts_with("2."),
+        target: "3.0.0",
+        apply: migrate_2_to_3_0_0,
+    },
+    Migration {
+        accepts: |version| version == "3.0" || version == "3.0.0",
+        target: "3.1.0",
+        apply: migrate_3_0_0_to_3_1_0,
+    },
+];
+
+/// Summary of an applied migration step
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+    pub from: String,
+    pub to: String,
+    pub changes: Vec<String>,
+}
+
+/// Upgrade a railjson to the current version (see `RAILJSON_VERSION`).
+/// Returns the list of applied migrations, empty if the railjson was already up to date.
+pub fn migrate_railjson(railjson: &mut Value) -> Result<Vec<MigrationReport>> {
+    let railjson = match railjson.as_object_mut() {
+        Some(railjson) => railjson,
+        None => return Err(RailjsonError::InvalidRailjson("expected a json object".into()).into()),
+    };
+
+    let mut version = match railjson.get("version").and_then(Value::as_str) {
+        Some(version) => version.to_string(),
+        None => return Err(RailjsonError::WrongVersion("".into()).into()),
+    };
+
+    let mut reports = vec![];
+    while version != RAILJSON_VERSION {
+        let migration = match MIGRATIONS.iter().find(|m| (m.accepts)(&version)) {
+            Some(migration) => migration,
+            None => return Err(RailjsonError::WrongVersion(version).into()),
+        };
+        let mut changes = vec![];
+        (migration.apply)(railjson, &mut changes);
+        railjson.insert("version".into(), migration.target.into());
+        reports.push(MigrationReport {
+            from: version,
+            to: migration.target.into(),
+            changes,
+        });
+        version = migration.target.into();
+    }
+    Ok(reports)
+}
+
+/// Iterate over the objects of a railjson collection
+fn objects_mut<'a>(
+    railjson: &'a mut Map<String, Value>,
+    collection: &str,
+) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    railjson
+        .get_mut(collection)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// Move the given fields of an object into its `extensions.<extension>` object.
+/// Returns whether a field has been moved.
+fn move_to_extension(object: &mut Map<String, Value>, extension: &str, fields: &[&str]) -> bool {
+    let moved: Map<String, Value> = fields
+        .iter()
+        .filter_map(|field| {
+            object
+                .remove(*field)
+                .map(|value| (field.to_string(), value))
+        })
+        .collect();
+    if moved.is_empty() {
+        return false;
+    }
+    let extensions = object
+        .entry("extensions")
+        .or_insert_with(|| Value::Object(Default::default()));
+    if let Some(extensions) = extensions.as_object_mut() {
+        extensions.insert(extension.into(), Value::Object(moved));
+    }
+    true
+}
+
+/// Railjson 3.0.0 removed the interlocking description (aspects, scripts, tvd sections),
+/// replaced route paths by an entry point direction and introduced catenaries.
+fn migrate_2_to_3_0_0(railjson: &mut Map<String, Value>, changes: &mut Vec<String>) {
+    for collection in ["aspects", "script_functions", "tvd_sections"] {
+        if railjson.remove(collection).is_some() {
+            changes.push(format!("removed '{collection}'"));
+        }
+    }
+
+    let signals = objects_mut(railjson, "signals")
+        .filter_map(|signal| signal.remove("expr"))
+        .count();
+    if signals > 0 {
+        changes.push(format!("removed 'expr' from {signals} signals"));
+    }
+
+    let links = objects_mut(railjson, "track_section_links")
+        .filter_map(|link| link.remove("navigability"))
+        .count();
+    if links > 0 {
+        changes.push(format!(
+            "removed 'navigability' from {links} track section links"
+        ));
+    }
+
+    let mut routes = 0;
+    for route in objects_mut(railjson, "routes") {
+        let path = match route.remove("path") {
+            Some(path) => path,
+            None => continue,
+        };
+        routes += 1;
+        if route.contains_key("entry_point_direction") {
+            continue;
+        }
+        let direction = path
+            .get(0)
+            .and_then(|range| range.get("direction"))
+            .cloned()
+            .unwrap_or_else(|| "START_TO_STOP".into());
+        route.insert("entry_point_direction".into(), direction);
+    }
+    if routes > 0 {
+        changes.push(format!(
+            "replaced 'path' by 'entry_point_direction' in {routes} routes"
+        ));
+    }
+
+    if !railjson.contains_key("catenaries") {
+        railjson.insert("catenaries".into(), Value::Array(vec![]));
+        changes.push("added empty 'catenaries'".into());
+    }
+}
+
+/// Railjson 3.1.0 moved SNCF specific fields into object extensions.
+fn migrate_3_0_0_to_3_1_0(railjson: &mut Map<String, Value>, changes: &mut Vec<String>) {
+    let tracks = objects_mut(railjson, "track_sections")
+        .map(|track| {
+            move_to_extension(
+                track,
+                "sncf",
+                &["line_code", "line_name", "track_number", "track_name"],
+            )
+        })
+        .filter(|moved| *moved)
+        .count();
+    if tracks > 0 {
+        changes.push(format!(
+            "moved line and track fields to 'extensions.sncf' in {tracks} track sections"
+        ));
+    }
+
+    let operational_points = objects_mut(railjson, "operational_points")
+        .map(|op| {
+            move_to_extension(
+                op,
+                "sncf",
+                &["ci", "ch", "ch_short_label", "ch_long_label", "trigram"],
+            )
+        })
+        .filter(|moved| *moved)
+        .count();
+    if operational_points > 0 {
+        changes.push(format!(
+            "moved ci, ch and trigram fields to 'extensions.sncf' in {operational_points} operational points"
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::migrate_railjson;
+    use crate::error::EditoastError;
+    use crate::infra::RAILJSON_VERSION;
+    use crate::schema::{RailJson, RailjsonError};
+    use serde_json::{from_value, json};
+
+    #[test]
+    fn up_to_date_railjson() {
+        let mut railjson = serde_json::to_value(RailJson::default()).unwrap();
+        let expected = railjson.clone();
+        let reports = migrate_railjson(&mut railjson).unwrap();
+        assert!(reports.is_empty());
+        assert_eq!(railjson, expected);
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut railjson = json!({ "version": "1.0.0" });
+        let err = migrate_railjson(&mut railjson).unwrap_err();
+        let expected = RailjsonError::WrongVersion("1.0.0".into());
+        assert_eq!(err.get_type(), expected.get_type());
+    }
+
+    #[test]
+    fn migrate_from_2_x() {
+        let mut railjson = json!({
+            "version": "2.2.3",
+            "aspects": [],
+            "script_functions": [],
+            "tvd_sections": [],
+            "operational_points": [{
+                "id": "op",
+                "parts": [],
+                "ci": 1,
+                "ch": "BV",
+                "ch_short_label": "short",
+                "ch_long_label": "long",
+                "trigram": "ABC",
+            }],
+            "routes": [{
+                "id": "route",
+                "entry_point": { "type": "Detector", "id": "d1" },
+                "exit_point": { "type": "Detector", "id": "d2" },
+                "release_detectors": [],
+                "switches_directions": {},
+                "path": [{ "track": "t", "begin": 0., "end": 1., "direction": "STOP_TO_START" }],
+            }],
+            "switch_types": [],
+            "switches": [],
+            "track_section_links": [],
+            "track_sections": [{
+                "id": "track",
+                "length": 1.,
+                "slopes": [],
+                "curves": [],
+                "geo": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+                "sch": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+                "line_code": 1,
+                "line_name": "line",
+                "track_number": 1,
+                "track_name": "V1",
+            }],
+            "speed_sections": [],
+            "signals": [],
+            "buffer_stops": [],
+            "detectors": [],
+        });
+
+        let reports = migrate_railjson(&mut railjson).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].to, RAILJSON_VERSION);
+
+        let railjson: RailJson = from_value(railjson).unwrap();
+        assert_eq!(railjson.version, RAILJSON_VERSION);
+        assert!(railjson.catenaries.is_empty());
+        let sncf = railjson.track_sections[0].extensions.sncf.as_ref().unwrap();
+        assert_eq!(sncf.track_name.0, "V1");
+        let sncf = railjson.operational_points[0]
+            .extensions
+            .sncf
+            .as_ref()
+            .unwrap();
+        assert_eq!(sncf.trigram, "ABC");
+        assert_eq!(
+            railjson.routes[0].entry_point_direction,
+            crate::schema::Direction::StopToStart
+        );
+    }
+}
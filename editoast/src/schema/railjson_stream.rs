@@ -0,0 +1,287 @@
+use super::{
+    BufferStop, Catenary, Detector, ObjectType, OperationalPoint, RailJson, RailjsonError, Route,
+    Signal, SpeedSection, Switch, SwitchType, TrackSection, TrackSectionLink,
+};
+use crate::error::{InternalError, Result};
+use crate::infra::{Infra, RAILJSON_VERSION};
+use diesel::{Connection, PgConnection};
+use serde::de::{DeserializeOwned, DeserializeSeed, Error as DeError, MapAccess, SeqAccess};
+use serde::de::{IgnoredAny, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::fmt::{Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use strum::IntoEnumIterator;
+
+/// Number of objects kept in memory before being persisted
+const PERSIST_CHUNK_SIZE: usize = 1000;
+
+type PersistBatch<T> = fn(&[T], i64, &mut PgConnection) -> Result<()>;
+
+/// Import a railjson file into a new infra.
+/// Files using the current version are streamed, older ones are loaded in memory to be migrated.
+/// The version is read first since it can be any key of the railjson.
+pub fn persist_railjson_file<T: AsRef<str>>(
+    mut file: File,
+    infra_name: T,
+    conn: &mut PgConnection,
+) -> Result<Infra> {
+    let version = read_railjson_version(BufReader::new(&file))?;
+    file.rewind()
+        .map_err(|err| RailjsonError::InvalidRailjson(err.to_string()))?;
+    if version == RAILJSON_VERSION {
+        if let Some(infra) = persist_railjson_stream(BufReader::new(&file), &infra_name, conn)? {
+            return Ok(infra);
+        }
+        file.rewind()
+            .map_err(|err| RailjsonError::InvalidRailjson(err.to_string()))?;
+    }
+
+    let railjson = serde_json::from_reader(BufReader::new(file))
+        .map_err(|err| RailjsonError::InvalidRailjson(err.to_string()))?;
+    RailJson::from_value(railjson)?.persist(infra_name, conn)
+}
+
+/// Read the version of a railjson, skipping its objects without keeping them in memory
+fn read_railjson_version<R: Read>(reader: R) -> Result<String> {
+    #[derive(Deserialize)]
+    struct RailjsonVersion {
+        version: String,
+    }
+
+    let railjson: RailjsonVersion = serde_json::from_reader(reader)
+        .map_err(|err| RailjsonError::InvalidRailjson(err.to_string()))?;
+    Ok(railjson.version)
+}
+
+/// Persist a railjson into a new infra without loading it entirely in memory.
+/// Object arrays are deserialized incrementally and persisted by chunks.
+/// Returns `None` if the railjson uses an older version of the format, in which case nothing is persisted.
+pub fn persist_railjson_stream<R: Read, T: AsRef<str>>(
+    reader: R,
+    infra_name: T,
+    conn: &mut PgConnection,
+) -> Result<Option<Infra>> {
+    let res = conn.transaction::<_, StreamError, _>(|conn| {
+        let infra = Infra::create(infra_name, conn)?;
+        let mut context = StreamContext {
+            infra_id: infra.id,
+            conn,
+            error: None,
+        };
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let res = deserializer
+            .deserialize_map(RailJsonVisitor {
+                context: &mut context,
+            })
+            .and_then(|_| deserializer.end());
+        match (res, context.error) {
+            (Ok(()), _) => Ok(infra),
+            (Err(_), Some(err)) => Err(err),
+            (Err(err), None) => Err(RailjsonError::InvalidRailjson(err.to_string()).into()),
+        }
+    });
+
+    match res {
+        Ok(infra) => Ok(Some(infra)),
+        Err(StreamError::Outdated) => Ok(None),
+        Err(StreamError::Internal(err)) => Err(err),
+    }
+}
+
+/// Reason of an interrupted import, other than a malformed railjson
+#[derive(Debug)]
+enum StreamError {
+    Outdated,
+    Internal(InternalError),
+}
+
+impl<T: Into<InternalError>> From<T> for StreamError {
+    fn from(err: T) -> Self {
+        StreamError::Internal(err.into())
+    }
+}
+
+struct StreamContext<'a> {
+    infra_id: i64,
+    conn: &'a mut PgConnection,
+    error: Option<StreamError>,
+}
+
+struct RailJsonVisitor<'c, 'a> {
+    context: &'c mut StreamContext<'a>,
+}
+
+impl<'c, 'a> RailJsonVisitor<'c, 'a> {
+    fn next_objects<'de, A: MapAccess<'de>, T: DeserializeOwned>(
+        &mut self,
+        map: &mut A,
+        persist: PersistBatch<T>,
+    ) -> std::result::Result<(), A::Error> {
+        map.next_value_seed(PersistSeq {
+            context: self.context,
+            persist,
+        })
+    }
+}
+
+impl<'de, 'c, 'a> Visitor<'de> for RailJsonVisitor<'c, 'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+        formatter.write_str("a railjson object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> std::result::Result<(), A::Error> {
+        let mut seen = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "version" {
+                let version: String = map.next_value()?;
+                if version != RAILJSON_VERSION {
+                    self.context.error = Some(StreamError::Outdated);
+                    return Err(A::Error::custom("outdated railjson version"));
+                }
+                seen.insert("version");
+                continue;
+            }
+
+            let obj_type = match ObjectType::iter().find(|t| t.get_railjson_field() == key) {
+                Some(obj_type) => obj_type,
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                    return Err(A::Error::custom(format!("unknown field `{key}`")));
+                }
+            };
+            match obj_type {
+                ObjectType::TrackSection => {
+                    self.next_objects(&mut map, TrackSection::persist_batch)
+                }
+                ObjectType::Signal => self.next_objects(&mut map, Signal::persist_batch),
+                ObjectType::SpeedSection => {
+                    self.next_objects(&mut map, SpeedSection::persist_batch)
+                }
+                ObjectType::Detector => self.next_objects(&mut map, Detector::persist_batch),
+                ObjectType::TrackSectionLink => {
+                    self.next_objects(&mut map, TrackSectionLink::persist_batch)
+                }
+                ObjectType::Switch => self.next_objects(&mut map, Switch::persist_batch),
+                ObjectType::SwitchType => self.next_objects(&mut map, SwitchType::persist_batch),
+                ObjectType::BufferStop => self.next_objects(&mut map, BufferStop::persist_batch),
+                ObjectType::Route => self.next_objects(&mut map, Route::persist_batch),
+                ObjectType::OperationalPoint => {
+                    self.next_objects(&mut map, OperationalPoint::persist_batch)
+                }
+                ObjectType::Catenary => self.next_objects(&mut map, Catenary::persist_batch),
+            }?;
+            seen.insert(obj_type.get_railjson_field());
+        }
+
+        if !seen.contains("version") {
+            return Err(A::Error::missing_field("version"));
+        }
+        if let Some(obj_type) = ObjectType::iter().find(|t| !seen.contains(t.get_railjson_field()))
+        {
+            return Err(A::Error::missing_field(obj_type.get_railjson_field()));
+        }
+        Ok(())
+    }
+}
+
+/// Deserialize an array of objects, persisting them by chunks
+struct PersistSeq<'c, 'a, T> {
+    context: &'c mut StreamContext<'a>,
+    persist: PersistBatch<T>,
+}
+
+impl<'c, 'a, T> PersistSeq<'c, 'a, T> {
+    fn persist_chunk<E: DeError>(&mut self, chunk: &[T]) -> std::result::Result<(), E> {
+        (self.persist)(chunk, self.context.infra_id, self.context.conn).map_err(|err| {
+            self.context.error = Some(StreamError::Internal(err));
+            E::custom("failed to persist objects")
+        })
+    }
+}
+
+impl<'de, 'c, 'a, T: DeserializeOwned> DeserializeSeed<'de> for PersistSeq<'c, 'a, T> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'c, 'a, T: DeserializeOwned> Visitor<'de> for PersistSeq<'c, 'a, T> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+        formatter.write_str("a list of railjson objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> std::result::Result<(), A::Error> {
+        let mut chunk = Vec::with_capacity(PERSIST_CHUNK_SIZE);
+        while let Some(object) = seq.next_element::<T>()? {
+            chunk.push(object);
+            if chunk.len() == PERSIST_CHUNK_SIZE {
+                self.persist_chunk(&chunk)?;
+                chunk.clear();
+            }
+        }
+        self.persist_chunk(&chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{persist_railjson_stream, read_railjson_version};
+    use crate::infra::RAILJSON_VERSION;
+    use crate::schema::{find_objects, RailJson, TrackSection};
+    use crate::tests::test_transaction;
+    use serde_json::json;
+
+    #[test]
+    fn persist_stream_ok() {
+        test_transaction(|conn| {
+            let railjson = RailJson {
+                track_sections: (0..2500).map(|_| Default::default()).collect(),
+                ..Default::default()
+            };
+            let railjson = serde_json::to_vec(&railjson).unwrap();
+
+            let infra = persist_railjson_stream(railjson.as_slice(), "test", conn)
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(infra.railjson_version, RAILJSON_VERSION);
+            assert_eq!(find_objects::<TrackSection>(conn, infra.id).len(), 2500);
+        });
+    }
+
+    #[test]
+    fn persist_stream_outdated() {
+        test_transaction(|conn| {
+            let railjson = json!({ "version": "3.0.0" }).to_string();
+            let res = persist_railjson_stream(railjson.as_bytes(), "test", conn).unwrap();
+            assert!(res.is_none());
+        });
+    }
+
+    #[test]
+    fn read_version_last_key() {
+        let railjson = json!({ "track_sections": [{ "id": "T" }], "version": "2.3.1" }).to_string();
+        let version = read_railjson_version(railjson.as_bytes()).unwrap();
+        assert_eq!(version, "2.3.1");
+        assert!(read_railjson_version(b"{}".as_slice()).is_err());
+    }
+
+    #[test]
+    fn persist_stream_missing_field() {
+        test_transaction(|conn| {
+            let railjson = json!({ "version": RAILJSON_VERSION }).to_string();
+            assert!(persist_railjson_stream(railjson.as_bytes(), "test", conn).is_err());
+        });
+    }
+}
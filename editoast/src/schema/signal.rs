@@ -12,7 +12,6 @@ use super::Side;
 use crate::infra_cache::Cache;
 use crate::infra_cache::ObjectCache;
 use derivative::Derivative;
-use diesel::sql_types::{Double, Text};
 
 use editoast_derive::Model;
 use serde::{Deserialize, Serialize};
@@ -80,17 +79,20 @@ impl OSRDIdentified for Signal {
     }
 }
 
-#[derive(QueryableByName, Debug, Clone, Derivative)]
+#[derive(Debug, Clone, Derivative)]
 #[derivative(Hash, PartialEq)]
 pub struct SignalCache {
-    #[diesel(sql_type = Text)]
     pub obj_id: String,
     #[derivative(Hash = "ignore", PartialEq = "ignore")]
-    #[diesel(sql_type = Text)]
     pub track: String,
     #[derivative(Hash = "ignore", PartialEq = "ignore")]
-    #[diesel(sql_type = Double)]
     pub position: f64,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub direction: Direction,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub linked_detector: Option<String>,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub logical_signals: Vec<LogicalSignal>,
 }
 
 impl OSRDTyped for SignalCache {
@@ -115,19 +117,16 @@ impl Cache for SignalCache {
     }
 }
 
-impl SignalCache {
-    pub fn new(obj_id: String, track: String, position: f64) -> Self {
-        Self {
-            obj_id,
-            track,
-            position,
-        }
-    }
-}
-
 impl From<Signal> for SignalCache {
     fn from(sig: Signal) -> Self {
-        Self::new(sig.id.0, sig.track.0, sig.position)
+        Self {
+            obj_id: sig.id.0,
+            track: sig.track.0,
+            position: sig.position,
+            direction: sig.direction,
+            linked_detector: sig.linked_detector,
+            logical_signals: sig.logical_signals.unwrap_or_default(),
+        }
     }
 }
 
@@ -39,9 +39,9 @@ pub struct SpeedSectionExtensions {
 #[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct SpeedSectionLpvSncfExtension {
-    announcement: Vec<Panel>,
-    z: Panel,
-    r: Vec<Panel>,
+    pub announcement: Vec<Panel>,
+    pub z: Panel,
+    pub r: Vec<Panel>,
 }
 
 impl OSRDTyped for SpeedSection {
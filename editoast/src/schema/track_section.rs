@@ -1,4 +1,5 @@
 use super::utils::Identifier;
+use super::utils::KilometrePoint;
 use super::utils::NonBlankString;
 use super::Endpoint;
 use super::OSRDIdentified;
@@ -32,13 +33,13 @@ pub struct TrackSection {
     pub extensions: TrackSectionExtensions,
 }
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct TrackSectionExtensions {
     pub sncf: Option<TrackSectionSncfExtension>,
 }
 
-#[derive(Debug, Derivative, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Derivative, Clone, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 #[derivative(Default)]
 pub struct TrackSectionSncfExtension {
@@ -48,6 +49,86 @@ pub struct TrackSectionSncfExtension {
     pub track_number: i32,
     #[derivative(Default(value = r#""track_test".into()"#))]
     pub track_name: NonBlankString,
+    /// Kilometre points of the line located along the track
+    #[serde(default)]
+    pub kilometre_points: Vec<KilometrePointAnchor>,
+}
+
+/// Track offset at which a kilometre point of the line is located
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct KilometrePointAnchor {
+    pub offset: f64,
+    pub kilometre_point: KilometrePoint,
+}
+
+impl TrackSection {
+    /// Returns the (offset, kilometre point) pairs delimiting the linear segments of the track kilometre points.
+    /// Kilometre points are extrapolated up to the track ends, a single anchor means that they follow the track offsets.
+    fn kilometre_profile(&self) -> Vec<(f64, f64)> {
+        let mut anchors: Vec<_> = match &self.extensions.sncf {
+            Some(sncf) => sncf
+                .kilometre_points
+                .iter()
+                .map(|anchor| (anchor.offset, anchor.kilometre_point.0))
+                .collect(),
+            None => return vec![],
+        };
+        anchors.sort_by(|a, b| a.0.total_cmp(&b.0));
+        anchors.dedup_by(|a, b| a.0 == b.0);
+        let (first, last) = match (anchors.first(), anchors.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return vec![],
+        };
+
+        let slope = |a: (f64, f64), b: (f64, f64)| {
+            if a.0 == b.0 {
+                1.
+            } else {
+                (b.1 - a.1) / (b.0 - a.0)
+            }
+        };
+        let begin_slope = slope(first, anchors.get(1).copied().unwrap_or(first));
+        let end_slope = slope(anchors[anchors.len().saturating_sub(2)], last);
+
+        let mut profile = vec![];
+        if first.0 > 0. {
+            profile.push((0., first.1 - first.0 * begin_slope));
+        }
+        profile.extend(anchors.iter().copied());
+        if last.0 < self.length {
+            profile.push((self.length, last.1 + (self.length - last.0) * end_slope));
+        }
+        profile
+    }
+
+    /// Returns the kilometre point at the given offset, `None` if the track has no kilometre point
+    pub fn kilometre_point(&self, offset: f64) -> Option<KilometrePoint> {
+        let profile = self.kilometre_profile();
+        let segment = profile
+            .windows(2)
+            .find(|segment| offset <= segment[1].0)
+            .or_else(|| profile.windows(2).last())?;
+        let (a, b) = (segment[0], segment[1]);
+        Some(KilometrePoint(
+            a.1 + (offset - a.0) * (b.1 - a.1) / (b.0 - a.0),
+        ))
+    }
+
+    /// Returns the offset of the given kilometre point, `None` if it isn't located on the track
+    pub fn kilometre_point_offset(&self, kilometre_point: KilometrePoint) -> Option<f64> {
+        let kp = kilometre_point.0;
+        self.kilometre_profile().windows(2).find_map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            if kp < a.1.min(b.1) || kp > a.1.max(b.1) {
+                return None;
+            }
+            if a.1 == b.1 {
+                return Some(a.0);
+            }
+            Some(a.0 + (kp - a.1) * (b.0 - a.0) / (b.1 - a.1))
+        })
+    }
 }
 
 impl OSRDTyped for TrackSection {
@@ -129,6 +210,135 @@ impl LineString {
         }
         BoundingBox(min, max)
     }
+
+    /// Returns the point located at the given fraction of the line geodesic length.
+    /// The fraction is clamped between 0 and 1, returns None if the line has no coordinates.
+    pub fn interpolate(&self, fraction: f64) -> Option<[f64; 2]> {
+        let coords = match self {
+            Self::LineString { coordinates } => coordinates,
+        };
+        if fraction >= 1. {
+            return coords.last().copied();
+        }
+        let mut remaining = fraction.max(0.) * self.geodesic_length();
+        for (a, b) in coords.iter().zip(coords.iter().skip(1)) {
+            let segment = haversine_distance(a, b);
+            if remaining < segment {
+                let ratio = remaining / segment;
+                return Some([a[0] + (b[0] - a[0]) * ratio, a[1] + (b[1] - a[1]) * ratio]);
+            }
+            remaining -= segment;
+        }
+        coords.last().copied()
+    }
+
+    /// Returns the length of the line in meters, coordinates being WGS84 longitudes and latitudes.
+    pub fn geodesic_length(&self) -> f64 {
+        let coords = match self {
+            Self::LineString { coordinates } => coordinates,
+        };
+        coords
+            .iter()
+            .zip(coords.iter().skip(1))
+            .map(|(a, b)| haversine_distance(a, b))
+            .sum()
+    }
+
+    /// A line string is degenerate if it doesn't have at least two distinct points
+    pub fn is_degenerate(&self) -> bool {
+        let coords = match self {
+            Self::LineString { coordinates } => coordinates,
+        };
+        coords.iter().skip(1).all(|point| point == &coords[0])
+    }
+
+    /// Returns a point where two non consecutive segments of the line intersect, if any.
+    /// The shared end point of a closed line isn't an intersection.
+    pub fn find_self_intersection(&self) -> Option<[f64; 2]> {
+        let coords = match self {
+            Self::LineString { coordinates } => coordinates,
+        };
+        let segments: Vec<_> = coords
+            .iter()
+            .zip(coords.iter().skip(1))
+            .filter(|(a, b)| a != b)
+            .collect();
+        let closed = segments.len() > 2 && coords.first() == coords.last();
+        let bounds: Vec<_> = segments
+            .iter()
+            .map(|(a, b)| {
+                (
+                    [a[0].min(b[0]), a[1].min(b[1])],
+                    [a[0].max(b[0]), a[1].max(b[1])],
+                )
+            })
+            .collect();
+
+        // Sweep the segments along the x axis, only segments with overlapping bounds are compared
+        let mut order: Vec<_> = (0..segments.len()).collect();
+        order.sort_by(|a, b| bounds[*a].0[0].total_cmp(&bounds[*b].0[0]));
+        for (rank, i) in order.iter().enumerate() {
+            let (min, max) = bounds[*i];
+            for j in order[rank + 1..].iter() {
+                let (other_min, other_max) = bounds[*j];
+                if other_min[0] > max[0] {
+                    break;
+                }
+                if other_min[1] > max[1] || other_max[1] < min[1] {
+                    continue;
+                }
+                let (first, second) = (*i.min(j), *i.max(j));
+                // Consecutive segments share an end point
+                if second - first < 2 || (closed && first == 0 && second == segments.len() - 1) {
+                    continue;
+                }
+                if let Some(point) = segments_intersection(segments[first], segments[second]) {
+                    return Some(point);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Mean radius of the earth in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Great circle distance in meters between two WGS84 points
+pub fn haversine_distance(a: &[f64; 2], b: &[f64; 2]) -> f64 {
+    let (lat_a, lat_b) = (a[1].to_radians(), b[1].to_radians());
+    let delta_lat = lat_b - lat_a;
+    let delta_lon = (b[0] - a[0]).to_radians();
+    let h =
+        (delta_lat / 2.).sin().powi(2) + lat_a.cos() * lat_b.cos() * (delta_lon / 2.).sin().powi(2);
+    2. * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// Returns a common point of two segments, if any
+fn segments_intersection(
+    (a, b): (&[f64; 2], &[f64; 2]),
+    (c, d): (&[f64; 2], &[f64; 2]),
+) -> Option<[f64; 2]> {
+    let cross = |o: &[f64; 2], p: &[f64; 2], q: &[f64; 2]| {
+        (p[0] - o[0]) * (q[1] - o[1]) - (p[1] - o[1]) * (q[0] - o[0])
+    };
+    let within = |o: &[f64; 2], p: &[f64; 2], q: &[f64; 2]| {
+        q[0] >= o[0].min(p[0])
+            && q[0] <= o[0].max(p[0])
+            && q[1] >= o[1].min(p[1])
+            && q[1] <= o[1].max(p[1])
+    };
+    let (d1, d2) = (cross(a, b, c), cross(a, b, d));
+    let (d3, d4) = (cross(c, d, a), cross(c, d, b));
+    if d1 * d2 < 0. && d3 * d4 < 0. {
+        let ratio = d3 / (d3 - d4);
+        return Some([a[0] + (b[0] - a[0]) * ratio, a[1] + (b[1] - a[1]) * ratio]);
+    }
+    // Collinear or touching segments
+    [(d1, a, b, c), (d2, a, b, d), (d3, c, d, a), (d4, c, d, b)]
+        .into_iter()
+        .find(|(cross, o, p, q)| *cross == 0. && within(o, p, q))
+        .map(|(.., q)| *q)
 }
 
 #[derive(Debug, Clone, Derivative)]
@@ -141,6 +351,14 @@ pub struct TrackSectionCache {
     pub bbox_geo: BoundingBox,
     #[derivative(Hash = "ignore", PartialEq = "ignore")]
     pub bbox_sch: BoundingBox,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub geo: LineString,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub slopes: Vec<Slope>,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub curves: Vec<Curve>,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    pub loading_gauge_limits: Vec<LoadingGaugeLimit>,
 }
 
 impl OSRDTyped for TrackSectionCache {
@@ -169,6 +387,17 @@ impl TrackSectionCache {
             track: self.obj_id.clone().into(),
         }
     }
+
+    /// Return the coordinates of an endpoint of the track geometry
+    /// Returns None if the geometry has no coordinates
+    pub fn get_endpoint_location(&self, endpoint: Endpoint) -> Option<[f64; 2]> {
+        let LineString::LineString { coordinates } = &self.geo;
+        let location = match endpoint {
+            Endpoint::Begin => coordinates.first(),
+            Endpoint::End => coordinates.last(),
+        };
+        location.copied()
+    }
 }
 
 impl From<TrackSection> for TrackSectionCache {
@@ -178,6 +407,10 @@ impl From<TrackSection> for TrackSectionCache {
             length: track.length,
             bbox_geo: track.geo.get_bbox(),
             bbox_sch: track.sch.get_bbox(),
+            geo: track.geo,
+            slopes: track.slopes,
+            curves: track.curves,
+            loading_gauge_limits: track.loading_gauge_limits,
         }
     }
 }
@@ -198,7 +431,8 @@ mod test {
     use super::{LineString::LineString, TrackSectionExtensions};
     use crate::infra::tests::test_infra_transaction;
     use crate::map::BoundingBox;
-    use serde_json::from_str;
+    use crate::schema::utils::KilometrePoint;
+    use serde_json::{from_str, from_value, json};
 
     #[test]
     fn test_persist() {
@@ -230,6 +464,87 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_line_string_interpolate() {
+        let line_string = LineString {
+            coordinates: vec![[0., 0.], [3., 0.], [3., 1.]],
+        };
+
+        assert_eq!(line_string.interpolate(0.), Some([0., 0.]));
+        assert_eq!(line_string.interpolate(2.), Some([3., 1.]));
+        let [x, y] = line_string.interpolate(0.5).unwrap();
+        assert!((x - 2.).abs() < 1e-9 && y.abs() < 1e-9);
+        let [x, y] = line_string.interpolate(0.875).unwrap();
+        assert!((x - 3.).abs() < 1e-9 && (y - 0.5).abs() < 1e-9);
+
+        // Longitude degrees are shorter than latitude ones far from the equator
+        let line_string = LineString {
+            coordinates: vec![[0., 60.], [2., 60.], [2., 61.]],
+        };
+        let [x, y] = line_string.interpolate(0.5).unwrap();
+        assert!((x - 2.).abs() < 0.05 && (y - 60.).abs() < 0.05);
+
+        let empty = LineString {
+            coordinates: vec![],
+        };
+        assert!(empty.interpolate(0.5).is_none());
+    }
+
+    #[test]
+    fn test_line_string_self_intersection() {
+        let crossing = LineString {
+            coordinates: vec![[0., 0.], [2., 2.], [2., 0.], [0., 2.]],
+        };
+        assert_eq!(crossing.find_self_intersection(), Some([1., 1.]));
+
+        let closed = LineString {
+            coordinates: vec![[0., 0.], [1., 0.], [1., 1.], [0., 1.], [0., 0.]],
+        };
+        assert_eq!(closed.find_self_intersection(), None);
+
+        let closed_crossing = LineString {
+            coordinates: vec![[0., 0.], [2., 2.], [2., 0.], [0., 2.], [0., 0.]],
+        };
+        assert_eq!(closed_crossing.find_self_intersection(), Some([1., 1.]));
+    }
+
+    #[test]
+    fn test_kilometre_points() {
+        let track: TrackSection = from_value(json!({
+            "id": "track",
+            "length": 1000.,
+            "slopes": [],
+            "curves": [],
+            "geo": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+            "sch": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+            "extensions": { "sncf": {
+                "line_code": 420000,
+                "line_name": "line",
+                "track_number": 1,
+                "track_name": "V1",
+                "kilometre_points": [
+                    { "offset": 600., "kilometre_point": "12+000" },
+                    { "offset": 100., "kilometre_point": "12+500" },
+                ],
+            }},
+        }))
+        .unwrap();
+
+        assert_eq!(track.kilometre_point(0.), Some(KilometrePoint(12600.)));
+        assert_eq!(track.kilometre_point(350.), Some(KilometrePoint(12250.)));
+        assert_eq!(track.kilometre_point(1000.), Some(KilometrePoint(11600.)));
+        assert_eq!(
+            track.kilometre_point_offset(KilometrePoint(12250.)),
+            Some(350.)
+        );
+        assert_eq!(
+            track.kilometre_point_offset(KilometrePoint(11700.)),
+            Some(900.)
+        );
+        assert_eq!(track.kilometre_point_offset(KilometrePoint(13000.)), None);
+        assert_eq!(TrackSection::default().kilometre_point(0.), None);
+    }
+
     #[test]
     fn test_track_extensions_deserialization() {
         from_str::<TrackSectionExtensions>(r#"{}"#).unwrap();
@@ -0,0 +1,89 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A kilometre point (PK) along a line, written `<km>+<m>` (ex: `12+345`).
+/// Stored as a distance in meters from the origin of the line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct KilometrePoint(pub f64);
+
+impl FromStr for KilometrePoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid kilometre point '{s}', expected '<km>+<m>'");
+        let (sign, value) = match s.strip_prefix('-') {
+            Some(value) => (-1., value),
+            None => (1., s),
+        };
+        let (km, m) = value.split_once('+').ok_or_else(invalid)?;
+        let km: u32 = km.parse().map_err(|_| invalid())?;
+        let m: f64 = m.parse().map_err(|_| invalid())?;
+        if !(0. ..1000.).contains(&m) {
+            return Err(invalid());
+        }
+        Ok(KilometrePoint(sign * (km as f64 * 1000. + m)))
+    }
+}
+
+impl Display for KilometrePoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0. { "-" } else { "" };
+        // Round to the millimeter to avoid writing `1000` meters
+        let value = (self.0.abs() * 1000.).round() / 1000.;
+        let km = (value / 1000.).floor();
+        let m = value - km * 1000.;
+        write!(f, "{sign}{km}+{:03}", m.trunc() as u32)?;
+        if m.fract() != 0. {
+            let decimals = format!("{:.3}", m.fract());
+            write!(
+                f,
+                "{}",
+                decimals.trim_start_matches('0').trim_end_matches('0')
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for KilometrePoint {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for KilometrePoint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KilometrePoint;
+
+    #[test]
+    fn test_parse_kilometre_point() {
+        assert_eq!("12+345".parse(), Ok(KilometrePoint(12345.)));
+        assert_eq!("0+005.5".parse(), Ok(KilometrePoint(5.5)));
+        assert_eq!("-1+200".parse(), Ok(KilometrePoint(-1200.)));
+        assert!("12345".parse::<KilometrePoint>().is_err());
+        assert!("12+1000".parse::<KilometrePoint>().is_err());
+    }
+
+    #[test]
+    fn test_display_kilometre_point() {
+        assert_eq!(KilometrePoint(12345.).to_string(), "12+345");
+        assert_eq!(KilometrePoint(5.5).to_string(), "0+005.5");
+        assert_eq!(KilometrePoint(-1200.).to_string(), "-1+200");
+        assert_eq!(KilometrePoint(1999.9999).to_string(), "2+000");
+    }
+}
@@ -1,5 +1,7 @@
 mod identifier;
+mod kilometre_point;
 mod non_blank_string;
 
 pub use identifier::Identifier;
+pub use kilometre_point::KilometrePoint;
 pub use non_blank_string::NonBlankString;
@@ -0,0 +1,363 @@
+//! Generate a simplified schematic layout of an infra from its topology.
+//! Tracks of the same line are laid out side by side along the x axis, parallel tracks
+//! (see `TrackSectionSncfExtension::track_number`) being offset by a fixed spacing.
+//! Tracks diverging from a parallel track join it at 45°.
+
+use crate::schema::operation::{Operation, UpdateOperation};
+use crate::schema::{
+    find_objects, Endpoint, LineString, ObjectType, Switch, SwitchType, TrackEndpoint,
+    TrackSection, TrackSectionLink,
+};
+use diesel::PgConnection;
+use json_patch::{Patch, PatchOperation, ReplaceOperation};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+/// Approximative length of a degree, used to convert the layout to coordinates
+const METERS_PER_DEGREE: f64 = 111_320.;
+/// Distance between two parallel tracks of a line
+const TRACK_SPACING: f64 = 20.;
+/// Distance between two lines
+const LINE_SPACING: f64 = 500.;
+/// Distance between two disconnected parts of a line
+const COMPONENT_GAP: f64 = 100.;
+
+/// Position of a track in the layout, in meters
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    begin: f64,
+    end: f64,
+    y: f64,
+}
+
+impl Placement {
+    fn x(&self, endpoint: Endpoint) -> f64 {
+        match endpoint {
+            Endpoint::Begin => self.begin,
+            Endpoint::End => self.end,
+        }
+    }
+
+    /// Direction going from the given endpoint out of the track
+    fn outward(&self, endpoint: Endpoint) -> f64 {
+        match endpoint {
+            Endpoint::Begin => (self.begin - self.end).signum(),
+            Endpoint::End => (self.end - self.begin).signum(),
+        }
+    }
+}
+
+/// List the connections between track endpoints made by links and switches
+pub fn track_connections(
+    links: &[TrackSectionLink],
+    switches: &[Switch],
+    switch_types: &[SwitchType],
+) -> Vec<(TrackEndpoint, TrackEndpoint)> {
+    let switch_types: HashMap<_, _> = switch_types
+        .iter()
+        .map(|switch_type| (&switch_type.id, switch_type))
+        .collect();
+    let mut connections: Vec<_> = links
+        .iter()
+        .map(|link| (link.src.clone(), link.dst.clone()))
+        .collect();
+    for switch in switches {
+        let switch_type = match switch_types.get(&switch.switch_type) {
+            Some(switch_type) => switch_type,
+            None => continue,
+        };
+        for connection in switch_type.groups.values().flatten() {
+            if let (Some(src), Some(dst)) = (
+                switch.ports.get(&connection.src),
+                switch.ports.get(&connection.dst),
+            ) {
+                connections.push((src.clone(), dst.clone()));
+            }
+        }
+    }
+    connections
+}
+
+/// Compute the schematic geometry of every given track
+pub fn compute_schematic(
+    tracks: &[TrackSection],
+    connections: &[(TrackEndpoint, TrackEndpoint)],
+) -> HashMap<String, LineString> {
+    let index: HashMap<&str, usize> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| (track.id.as_str(), i))
+        .collect();
+    let mut neighbours: HashMap<(usize, Endpoint), Vec<(usize, Endpoint)>> = HashMap::new();
+    for (src, dst) in connections {
+        if let (Some(&src_track), Some(&dst_track)) =
+            (index.get(src.track.as_str()), index.get(dst.track.as_str()))
+        {
+            neighbours
+                .entry((src_track, src.endpoint))
+                .or_default()
+                .push((dst_track, dst.endpoint));
+            neighbours
+                .entry((dst_track, dst.endpoint))
+                .or_default()
+                .push((src_track, src.endpoint));
+        }
+    }
+
+    let sncf = |i: usize| tracks[i].extensions.sncf.as_ref();
+    let mut lines: BTreeMap<Option<i32>, Vec<usize>> = BTreeMap::new();
+    for i in 0..tracks.len() {
+        lines
+            .entry(sncf(i).map(|sncf| sncf.line_code))
+            .or_default()
+            .push(i);
+    }
+
+    let mut placements: Vec<Option<Placement>> = vec![None; tracks.len()];
+    for (line_index, (line_code, line_tracks)) in lines.iter().enumerate() {
+        let track_number = |i: usize| sncf(i).map(|sncf| sncf.track_number).unwrap_or_default();
+        let track_numbers: BTreeSet<_> = line_tracks.iter().map(|i| track_number(*i)).collect();
+        let y = |i: usize| {
+            let rank = track_numbers.range(..track_number(i)).count();
+            line_index as f64 * LINE_SPACING + rank as f64 * TRACK_SPACING
+        };
+        let same_line = |i: usize| &sncf(i).map(|sncf| sncf.line_code) == line_code;
+
+        // Lay out each connected part of the line after the previous one
+        let mut offset = 0.;
+        for &start in line_tracks {
+            if placements[start].is_some() {
+                continue;
+            }
+            placements[start] = Some(Placement {
+                begin: 0.,
+                end: tracks[start].length,
+                y: y(start),
+            });
+            let mut component = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(track) = queue.pop_front() {
+                let placement = placements[track].unwrap();
+                for endpoint in [Endpoint::Begin, Endpoint::End] {
+                    let x = placement.x(endpoint);
+                    let outward = placement.outward(endpoint);
+                    for &(next, next_endpoint) in
+                        neighbours.get(&(track, endpoint)).unwrap_or(&vec![])
+                    {
+                        if placements[next].is_some() || !same_line(next) {
+                            continue;
+                        }
+                        let other_x = x + outward * tracks[next].length;
+                        let (begin, end) = match next_endpoint {
+                            Endpoint::Begin => (x, other_x),
+                            Endpoint::End => (other_x, x),
+                        };
+                        placements[next] = Some(Placement {
+                            begin,
+                            end,
+                            y: y(next),
+                        });
+                        component.push(next);
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            let min_x = component
+                .iter()
+                .map(|i| placements[*i].unwrap())
+                .map(|p| p.begin.min(p.end))
+                .fold(f64::MAX, f64::min);
+            let mut max_x = offset;
+            for i in component {
+                let placement = placements[i].as_mut().unwrap();
+                placement.begin += offset - min_x;
+                placement.end += offset - min_x;
+                max_x = max_x.max(placement.begin.max(placement.end));
+            }
+            offset = max_x + COMPONENT_GAP;
+        }
+    }
+
+    // Anchor the layout next to the geographic infra
+    let origin = tracks
+        .iter()
+        .map(|track| track.geo.get_bbox().0)
+        .fold((f64::MAX, f64::MAX), |(x, y), min| {
+            (x.min(min.0), y.min(min.1))
+        });
+    let to_coordinates = |(x, y): (f64, f64)| {
+        [
+            origin.0 + x / METERS_PER_DEGREE,
+            origin.1 + y / METERS_PER_DEGREE,
+        ]
+    };
+
+    tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let placement = placements[i].unwrap();
+            // Endpoints connected only to parallel tracks join the closest one at 45°
+            let bend = |endpoint: Endpoint| {
+                let neighbour_ys: Vec<_> = neighbours
+                    .get(&(i, endpoint))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(next, _)| placements[*next])
+                    .filter(|next| {
+                        (next.x(Endpoint::Begin) - placement.x(endpoint)).abs() < 1e-6
+                            || (next.x(Endpoint::End) - placement.x(endpoint)).abs() < 1e-6
+                    })
+                    .map(|next| next.y)
+                    .collect();
+                if neighbour_ys.is_empty() || neighbour_ys.contains(&placement.y) {
+                    return None;
+                }
+                neighbour_ys
+                    .into_iter()
+                    .min_by(|a, b| (a - placement.y).abs().total_cmp(&(b - placement.y).abs()))
+            };
+
+            let length = (placement.end - placement.begin).abs();
+            let inward = (placement.end - placement.begin).signum();
+            let mut points = vec![];
+            if let Some(y) = bend(Endpoint::Begin) {
+                let shift = (y - placement.y).abs().min(length / 2.);
+                points.push((placement.begin, y));
+                points.push((placement.begin + inward * shift, placement.y));
+            } else {
+                points.push((placement.begin, placement.y));
+            }
+            if let Some(y) = bend(Endpoint::End) {
+                let shift = (y - placement.y).abs().min(length / 2.);
+                points.push((placement.end - inward * shift, placement.y));
+                points.push((placement.end, y));
+            } else {
+                points.push((placement.end, placement.y));
+            }
+
+            let coordinates = points.into_iter().map(to_coordinates).collect();
+            (track.id.to_string(), LineString::LineString { coordinates })
+        })
+        .collect()
+}
+
+/// Build the operations replacing the schematic geometry of the tracks of an infra.
+/// Tracks whose geometry is already up to date are left untouched.
+pub fn schematic_operations(conn: &mut PgConnection, infra_id: i64) -> Vec<Operation> {
+    let tracks: Vec<TrackSection> = find_objects(conn, infra_id);
+    let connections = track_connections(
+        &find_objects::<TrackSectionLink>(conn, infra_id),
+        &find_objects::<Switch>(conn, infra_id),
+        &find_objects::<SwitchType>(conn, infra_id),
+    );
+    let mut schematic = compute_schematic(&tracks, &connections);
+
+    tracks
+        .iter()
+        .filter_map(|track| {
+            let sch = schematic.remove(track.id.as_str())?;
+            if sch == track.sch {
+                return None;
+            }
+            let patch = Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                path: "/sch".into(),
+                value: serde_json::to_value(sch).unwrap(),
+            })]);
+            Some(Operation::Update(UpdateOperation::new(
+                ObjectType::TrackSection,
+                track.id.to_string(),
+                patch,
+            )))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_schematic, METERS_PER_DEGREE};
+    use crate::schema::{Endpoint, LineString, TrackEndpoint, TrackSection};
+
+    fn track(id: &str, track_number: i32) -> TrackSection {
+        let mut track = TrackSection {
+            id: id.into(),
+            length: 100.,
+            geo: LineString::LineString {
+                coordinates: vec![[0., 0.], [1., 1.]],
+            },
+            ..Default::default()
+        };
+        let sncf = track.extensions.sncf.get_or_insert_with(Default::default);
+        sncf.line_code = 1;
+        sncf.track_number = track_number;
+        track
+    }
+
+    fn connection(
+        src: &str,
+        src_endpoint: Endpoint,
+        dst: &str,
+        dst_endpoint: Endpoint,
+    ) -> (TrackEndpoint, TrackEndpoint) {
+        (
+            TrackEndpoint {
+                track: src.into(),
+                endpoint: src_endpoint,
+            },
+            TrackEndpoint {
+                track: dst.into(),
+                endpoint: dst_endpoint,
+            },
+        )
+    }
+
+    /// Convert a schematic geometry back to meters
+    fn meters(sch: &LineString) -> Vec<(f64, f64)> {
+        let LineString::LineString { coordinates } = sch;
+        coordinates
+            .iter()
+            .map(|[x, y]| {
+                (
+                    (x * METERS_PER_DEGREE).round(),
+                    (y * METERS_PER_DEGREE).round(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parallel_tracks() {
+        let tracks = [track("A", 1), track("B", 1), track("C", 2)];
+        let connections = [
+            connection("A", Endpoint::End, "B", Endpoint::Begin),
+            connection("A", Endpoint::End, "C", Endpoint::Begin),
+        ];
+        let schematic = compute_schematic(&tracks, &connections);
+
+        assert_eq!(meters(&schematic["A"]), vec![(0., 0.), (100., 0.)]);
+        assert_eq!(meters(&schematic["B"]), vec![(100., 0.), (200., 0.)]);
+        assert_eq!(
+            meters(&schematic["C"]),
+            vec![(100., 0.), (120., 20.), (200., 20.)]
+        );
+    }
+
+    #[test]
+    fn reversed_track() {
+        let tracks = [track("A", 1), track("B", 1)];
+        let connections = [connection("A", Endpoint::End, "B", Endpoint::End)];
+        let schematic = compute_schematic(&tracks, &connections);
+
+        assert_eq!(meters(&schematic["A"]), vec![(0., 0.), (100., 0.)]);
+        assert_eq!(meters(&schematic["B"]), vec![(200., 0.), (100., 0.)]);
+    }
+
+    #[test]
+    fn disconnected_tracks() {
+        let tracks = [track("A", 1), track("B", 1)];
+        let schematic = compute_schematic(&tracks, &[]);
+
+        assert_eq!(meters(&schematic["A"]), vec![(0., 0.), (100., 0.)]);
+        assert_eq!(meters(&schematic["B"]), vec![(200., 0.), (300., 0.)]);
+    }
+}
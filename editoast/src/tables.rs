@@ -200,3 +200,13 @@ table! {
         data -> Jsonb,
     }
 }
+
+table! {
+    osrd_infra_errorsetting(id) {
+        id -> BigInt,
+        infra_id -> BigInt,
+        error_type -> Text,
+        enabled -> Bool,
+        is_warning -> Nullable<Bool>,
+    }
+}
@@ -0,0 +1,93 @@
+use actix_web::dev::HttpServiceFactory;
+use actix_web::get;
+use actix_web::web::{block, Data, Json, Path};
+use chashmap::CHashMap;
+
+use crate::error::Result;
+use crate::infra::Infra;
+use crate::infra_cache::{compute_detection_sections, DetectionSection, Graph, InfraCache};
+use crate::DbPool;
+
+/// Return `/infra/<infra_id>/detection_sections` routes
+pub fn routes() -> impl HttpServiceFactory {
+    list_detection_sections
+}
+
+/// List the train detection sections of the infra: track ranges bounded by detectors and buffer stops
+#[get("/detection_sections")]
+async fn list_detection_sections(
+    infra: Path<i64>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    db_pool: Data<DbPool>,
+) -> Result<Json<Vec<DetectionSection>>> {
+    let infra = infra.into_inner();
+    let sections = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve(&mut conn, infra)?;
+        let infra_cache = InfraCache::get_or_load(&mut conn, &infra_caches, &infra)?;
+        let graph = Graph::load(&infra_cache);
+        Ok(compute_detection_sections(&infra_cache, &graph))
+    })
+    .await
+    .unwrap()?;
+    Ok(Json(sections))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test as actix_test;
+    use actix_web::test::{call_and_read_body_json, call_service, TestRequest};
+    use serde_json::{json, Value};
+
+    use crate::infra::Infra;
+    use crate::schema::{Detector, TrackSection};
+    use crate::views::infra::tests::{
+        create_infra_request, create_object_request, delete_infra_request,
+    };
+    use crate::views::tests::create_test_service;
+
+    #[actix_test]
+    async fn list_detection_sections() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("detection_sections_test")).await;
+
+        let track = TrackSection {
+            id: "track".into(),
+            length: 100.,
+            ..Default::default()
+        };
+        let req = create_object_request(infra.id, track.into());
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+        let detector = Detector {
+            id: "detector".into(),
+            track: "track".into(),
+            position: 40.,
+            ..Default::default()
+        };
+        let req = create_object_request(infra.id, detector.into());
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/detection_sections", infra.id).as_str())
+            .to_request();
+        let sections: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            sections,
+            json!([
+                {
+                    "track_ranges": [{ "track": "track", "begin": 0., "end": 40. }],
+                    "waypoints": [{ "type": "Detector", "id": "detector" }],
+                },
+                {
+                    "track_ranges": [{ "track": "track", "begin": 40., "end": 100. }],
+                    "waypoints": [{ "type": "Detector", "id": "detector" }],
+                },
+            ])
+        );
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+}
@@ -3,27 +3,29 @@ use actix_web::post;
 use actix_web::web::{block, Data, Json, Path};
 use chashmap::CHashMap;
 use diesel::PgConnection;
-use redis::Client;
 use thiserror::Error;
 
-use crate::client::MapLayersConfig;
+use crate::client::{MapLayersConfig, ValidationConfig};
 use crate::infra::Infra;
 use crate::infra_cache::InfraCache;
-use crate::map::{self, InvalidationZone, MapLayers};
+use crate::map::{self, InvalidationZone, MapLayers, TileCache};
 use crate::schema::operation::{Operation, OperationResult};
 use crate::{generated_data, DbPool};
 use editoast_derive::EditoastError;
+use log::error;
 
 /// CRUD for edit an infrastructure. Takes a batch of operations.
 #[post("")]
+#[allow(clippy::too_many_arguments)]
 pub async fn edit<'a>(
     infra: Path<i64>,
     operations: Json<Vec<Operation>>,
     db_pool: Data<DbPool>,
     infra_caches: Data<CHashMap<i64, InfraCache>>,
-    redis_client: Data<Client>,
+    tile_cache: Data<TileCache>,
     map_layers: Data<MapLayers>,
     map_layers_config: Data<MapLayersConfig>,
+    validation_config: Data<ValidationConfig>,
 ) -> Result<Json<Vec<OperationResult>>> {
     let infra = infra.into_inner();
     let (operation_results, invalid_zone) = block::<_, Result<_>>(move || {
@@ -31,29 +33,40 @@ pub async fn edit<'a>(
         let infra = Infra::retrieve_for_update(&mut conn, infra)?;
         let mut infra_cache =
             InfraCache::get_or_load_mut(&mut conn, &infra_caches, &infra).unwrap();
-        apply_edit(&mut conn, &infra, &operations, &mut infra_cache)
+        apply_edit(
+            &mut conn,
+            &infra,
+            &operations,
+            &mut infra_cache,
+            &validation_config,
+        )
     })
     .await
     .unwrap()?;
 
-    let mut conn = redis_client.get_tokio_connection_manager().await.unwrap();
-    map::invalidate_zone(
-        &mut conn,
-        &map_layers.layers.keys().cloned().collect(),
+    if let Err(err) = map::invalidate_zone(
+        &tile_cache,
+        &map_layers,
         infra,
         &invalid_zone,
-        map_layers_config.max_tiles,
+        &map_layers_config,
     )
-    .await?;
+    .await
+    {
+        error!(
+            "Failed to invalidate map tiles of infra {infra}, cached tiles may be outdated: {err}"
+        );
+    }
 
     Ok(Json(operation_results))
 }
 
-fn apply_edit(
+pub(super) fn apply_edit(
     conn: &mut PgConnection,
     infra: &Infra,
     operations: &[Operation],
     infra_cache: &mut InfraCache,
+    validation_config: &ValidationConfig,
 ) -> Result<(Vec<OperationResult>, InvalidationZone)> {
     // Check if the infra is locked
     if infra.locked {
@@ -75,8 +88,14 @@ fn apply_edit(
     // Apply operations to infra cache
     infra_cache.apply_operations(&operation_results);
     // Refresh layers if needed
-    generated_data::update_all(conn, infra.id, &operation_results, infra_cache)
-        .expect("Update generated data failed");
+    generated_data::update_all(
+        conn,
+        infra.id,
+        &operation_results,
+        infra_cache,
+        validation_config,
+    )
+    .expect("Update generated data failed");
 
     // Bump infra generated version to the infra version
     infra.bump_generated_version(conn)?;
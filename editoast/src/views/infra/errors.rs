@@ -1,23 +1,37 @@
+use crate::client::ValidationConfig;
 use crate::error::Result;
+use crate::generated_data::{self, ErrorSetting, ErrorSettings};
+use crate::infra::Infra;
+use crate::infra_cache::InfraCache;
+use crate::map::{self, BoundingBox, TileCache};
 use crate::schema::InfraErrorType;
 use crate::views::pagination::{
     paginate, PaginatedResponse, PaginationError, PaginationQueryParam,
 };
 use crate::DbPool;
 use actix_web::dev::HttpServiceFactory;
-use actix_web::get;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
 use actix_web::web::{block, Data, Json as WebJson, Path, Query};
-use diesel::sql_types::{BigInt, Json, Nullable, Text};
-use diesel::{PgConnection, RunQueryDsl};
+use actix_web::{get, put, HttpResponse};
+use chashmap::CHashMap;
+use diesel::sql_types::{BigInt, Bool, Double, Json, Nullable, Text};
+use diesel::{sql_query, Connection, PgConnection, RunQueryDsl};
 use editoast_derive::EditoastError;
+use log::error;
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Value as JsonValue};
 use strum::VariantNames;
 use thiserror::Error;
 
 /// Return `/infra/<infra_id>/errors` routes
 pub fn routes() -> impl HttpServiceFactory {
-    list_errors
+    (
+        list_errors,
+        errors_summary,
+        export_errors,
+        get_settings,
+        update_settings,
+    )
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -67,6 +81,379 @@ async fn list_errors(
     }))
 }
 
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    #[default]
+    Geojson,
+    Csv,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ExportQueryParams {
+    #[serde(default)]
+    format: ExportFormat,
+    #[serde(flatten)]
+    filters: QueryParams,
+}
+
+/// Export all the errors of an infra with their geometry as GeoJSON or csv
+#[get("/errors/export")]
+async fn export_errors(
+    db_pool: Data<DbPool>,
+    infra: Path<i64>,
+    params: Query<ExportQueryParams>,
+) -> Result<HttpResponse> {
+    let infra = infra.into_inner();
+    let params = params.into_inner();
+    if let Some(error_type) = &params.filters.error_type {
+        if !check_error_type_query(error_type) {
+            return Err(ListErrorsErrors::WrongErrorTypeProvided.into());
+        }
+    }
+
+    let format = params.format;
+    let infra_errors = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve(&mut conn, infra)?;
+        let query = infra_errors_query(
+            &params.filters,
+            "information::text, ST_AsGeoJSON(geographic)::json as geographic,
+            ST_AsText(geographic) as geographic_wkt",
+        ) + " ORDER BY information->>'obj_type', information->>'obj_id', information->>'error_type'";
+        Ok(sql_query(query)
+            .bind::<BigInt, _>(infra.id)
+            .bind::<Text, _>(params.filters.error_type.unwrap_or_default())
+            .bind::<Text, _>(params.filters.object_id.unwrap_or_default())
+            .load::<ExportedErrorQueryable>(&mut conn)?)
+    })
+    .await
+    .unwrap()?;
+
+    let (content_type, extension, body) = match format {
+        ExportFormat::Geojson => (
+            "application/geo+json",
+            "geojson",
+            errors_to_geojson(&infra_errors).to_string(),
+        ),
+        ExportFormat::Csv => ("text/csv", "csv", errors_to_csv(&infra_errors)?),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "infra_{infra}_errors.{extension}"
+            ))],
+        })
+        .body(body))
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+struct ExportedErrorQueryable {
+    #[diesel(sql_type = Json)]
+    information: JsonValue,
+    #[diesel(sql_type = Nullable<Json>)]
+    geographic: Option<JsonValue>,
+    #[diesel(sql_type = Nullable<Text>)]
+    geographic_wkt: Option<String>,
+}
+
+/// Build a GeoJSON feature collection of errors, the error information being the feature properties
+fn errors_to_geojson(infra_errors: &[ExportedErrorQueryable]) -> JsonValue {
+    let features: Vec<_> = infra_errors
+        .iter()
+        .map(|error| {
+            json!({
+                "type": "Feature",
+                "geometry": error.geographic,
+                "properties": error.information,
+            })
+        })
+        .collect();
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// Columns of the csv export, the remaining error information is written as json in `context`
+const CSV_COLUMNS: [&str; 5] = ["obj_type", "obj_id", "field", "error_type", "is_warning"];
+
+/// Write errors as csv, one row per error with its geometry as WKT
+fn errors_to_csv(infra_errors: &[ExportedErrorQueryable]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let header = CSV_COLUMNS.iter().chain(&["context", "geometry"]);
+    writer
+        .write_record(header)
+        .map_err(|err| ListErrorsErrors::ExportFailed(err.to_string()))?;
+    for error in infra_errors {
+        let mut context = error.information.as_object().cloned().unwrap_or_default();
+        let mut record: Vec<_> = CSV_COLUMNS
+            .iter()
+            .map(|column| match context.remove(*column) {
+                Some(JsonValue::String(value)) => value,
+                Some(value) => value.to_string(),
+                None => String::new(),
+            })
+            .collect();
+        record.push(if context.is_empty() {
+            String::new()
+        } else {
+            JsonValue::Object(context).to_string()
+        });
+        record.push(error.geographic_wkt.clone().unwrap_or_default());
+        writer
+            .write_record(record)
+            .map_err(|err| ListErrorsErrors::ExportFailed(err.to_string()))?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|err| ListErrorsErrors::ExportFailed(err.to_string()))?;
+    Ok(String::from_utf8(data).expect("csv writer output is valid utf-8"))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SummaryQueryParams {
+    /// Infra to compare the errors with (ex: the infra a clone was made from)
+    compare_to: Option<i64>,
+}
+
+/// Return the errors of an infra counted by error type, object type and level
+#[get("/errors/summary")]
+async fn errors_summary(
+    db_pool: Data<DbPool>,
+    infra: Path<i64>,
+    params: Query<SummaryQueryParams>,
+) -> Result<WebJson<ErrorsSummary>> {
+    let infra = infra.into_inner();
+    let compare_to = params.compare_to;
+    if compare_to == Some(infra) {
+        return Err(ListErrorsErrors::CompareToSameInfra.into());
+    }
+    let groups = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve(&mut conn, infra)?;
+        if let Some(compare_to) = compare_to {
+            Infra::retrieve(&mut conn, compare_to)?;
+        }
+        Ok(sql_query(include_str!("sql/errors_summary.sql"))
+            .bind::<BigInt, _>(infra.id)
+            .bind::<Nullable<BigInt>, _>(compare_to)
+            .load::<ErrorGroupQueryable>(&mut conn)?)
+    })
+    .await
+    .unwrap()?;
+    Ok(WebJson(ErrorsSummary::new(groups, compare_to)))
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+struct ErrorGroupQueryable {
+    #[diesel(sql_type = Text)]
+    error_type: String,
+    #[diesel(sql_type = Text)]
+    obj_type: String,
+    #[diesel(sql_type = Bool)]
+    is_warning: bool,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+    #[diesel(sql_type = BigInt)]
+    reference_count: i64,
+    #[diesel(sql_type = BigInt)]
+    new_count: i64,
+    #[diesel(sql_type = BigInt)]
+    fixed_count: i64,
+    #[diesel(sql_type = Nullable<Double>)]
+    geo_x_min: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    geo_y_min: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    geo_x_max: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    geo_y_max: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    sch_x_min: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    sch_y_min: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    sch_x_max: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    sch_y_max: Option<f64>,
+}
+
+/// Build a bounding box from its bounds, `None` if the area is unknown
+fn bounding_box(
+    x_min: Option<f64>,
+    y_min: Option<f64>,
+    x_max: Option<f64>,
+    y_max: Option<f64>,
+) -> Option<BoundingBox> {
+    Some(BoundingBox((x_min?, y_min?), (x_max?, y_max?)))
+}
+
+/// Smallest bounding box containing all the given ones
+fn union_bboxes<'a, I: Iterator<Item = &'a BoundingBox>>(bboxes: I) -> Option<BoundingBox> {
+    bboxes.fold(None, |union, bbox| {
+        let mut union = union.unwrap_or_default();
+        union.union(bbox);
+        Some(union)
+    })
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+struct ErrorsSummary {
+    errors: i64,
+    warnings: i64,
+    /// Area affected by the errors of the infra
+    bbox_geo: Option<BoundingBox>,
+    bbox_sch: Option<BoundingBox>,
+    groups: Vec<ErrorGroup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comparison: Option<ErrorsComparison>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+struct ErrorGroup {
+    error_type: String,
+    obj_type: String,
+    is_warning: bool,
+    count: i64,
+    bbox_geo: Option<BoundingBox>,
+    bbox_sch: Option<BoundingBox>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    comparison: Option<ErrorGroupComparison>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+struct ErrorGroupComparison {
+    /// Number of errors of the group in the compared infra
+    reference_count: i64,
+    /// Errors missing from the compared infra (regressions)
+    new: i64,
+    /// Errors of the compared infra that are gone
+    fixed: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+struct ErrorsComparison {
+    infra_id: i64,
+    new: i64,
+    fixed: i64,
+}
+
+impl ErrorsSummary {
+    fn new(groups: Vec<ErrorGroupQueryable>, compare_to: Option<i64>) -> Self {
+        let groups: Vec<_> = groups
+            .into_iter()
+            .map(|group| ErrorGroup {
+                bbox_geo: bounding_box(
+                    group.geo_x_min,
+                    group.geo_y_min,
+                    group.geo_x_max,
+                    group.geo_y_max,
+                ),
+                bbox_sch: bounding_box(
+                    group.sch_x_min,
+                    group.sch_y_min,
+                    group.sch_x_max,
+                    group.sch_y_max,
+                ),
+                comparison: compare_to.map(|_| ErrorGroupComparison {
+                    reference_count: group.reference_count,
+                    new: group.new_count,
+                    fixed: group.fixed_count,
+                }),
+                error_type: group.error_type,
+                obj_type: group.obj_type,
+                is_warning: group.is_warning,
+                count: group.count,
+            })
+            .collect();
+
+        let count = |is_warning: bool| {
+            groups
+                .iter()
+                .filter(|group| group.is_warning == is_warning)
+                .map(|group| group.count)
+                .sum()
+        };
+        let comparison = compare_to.map(|infra_id| {
+            let group_comparisons = groups.iter().filter_map(|group| group.comparison.as_ref());
+            ErrorsComparison {
+                infra_id,
+                new: group_comparisons
+                    .clone()
+                    .map(|comparison| comparison.new)
+                    .sum(),
+                fixed: group_comparisons.map(|comparison| comparison.fixed).sum(),
+            }
+        });
+        Self {
+            errors: count(false),
+            warnings: count(true),
+            bbox_geo: union_bboxes(groups.iter().filter_map(|group| group.bbox_geo.as_ref())),
+            bbox_sch: union_bboxes(groups.iter().filter_map(|group| group.bbox_sch.as_ref())),
+            groups,
+            comparison,
+        }
+    }
+}
+
+/// Return the validation settings of an infra
+#[get("/errors/settings")]
+async fn get_settings(
+    db_pool: Data<DbPool>,
+    infra: Path<i64>,
+) -> Result<WebJson<Vec<ErrorSetting>>> {
+    let infra = infra.into_inner();
+    block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve(&mut conn, infra)?;
+        Ok(WebJson(ErrorSettings::load(&mut conn, infra.id)?.to_vec()))
+    })
+    .await
+    .unwrap()
+}
+
+/// Replace the validation settings of an infra and regenerate its errors
+/// The infra version is bumped so that the errors tiles entity tag changes
+#[put("/errors/settings")]
+async fn update_settings(
+    db_pool: Data<DbPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    tile_cache: Data<TileCache>,
+    infra: Path<i64>,
+    settings: WebJson<Vec<ErrorSetting>>,
+    validation_config: Data<ValidationConfig>,
+) -> Result<WebJson<Vec<ErrorSetting>>> {
+    let infra = infra.into_inner();
+    let settings = settings.into_inner();
+    if let Some(setting) = settings
+        .iter()
+        .find(|setting| !check_error_type_query(&setting.error_type))
+    {
+        return Err(ListErrorsErrors::UnknownErrorType(setting.error_type.clone()).into());
+    }
+
+    let settings = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        conn.transaction(|conn| {
+            let infra = Infra::retrieve_for_update(conn, infra)?;
+            let settings: ErrorSettings = settings.into_iter().collect();
+            settings.save(conn, infra.id)?;
+            let infra = infra.bump_version(conn)?;
+            let infra_cache = InfraCache::get_or_load(conn, &infra_caches, &infra)?;
+            generated_data::refresh_errors(conn, infra.id, &infra_cache, &validation_config)?;
+            infra.bump_generated_version(conn)?;
+            Ok(settings.to_vec())
+        })
+    })
+    .await
+    .unwrap()?;
+
+    if let Err(err) = map::invalidate_full_layer_cache(&tile_cache, infra, "errors", None).await {
+        error!("Failed to invalidate the errors tiles of infra {infra}, cached tiles may be outdated: {err}");
+    }
+    Ok(WebJson(settings))
+}
+
 /// Check if the query parameter error_type exist
 fn check_error_type_query(param: &String) -> bool {
     InfraErrorType::VARIANTS
@@ -79,6 +466,13 @@ fn check_error_type_query(param: &String) -> bool {
 enum ListErrorsErrors {
     #[error("Wrong Error type provided")]
     WrongErrorTypeProvided,
+    #[error("Unknown error type '{0}'")]
+    UnknownErrorType(String),
+    #[error("An infra can't be compared to itself")]
+    CompareToSameInfra,
+    #[error("Couldn't export errors: {0}")]
+    #[editoast_error(status = 500)]
+    ExportFailed(String),
 }
 
 #[derive(QueryableByName, Debug, Clone)]
@@ -120,16 +514,18 @@ impl From<InfraErrorQueryable> for InfraErrorModel {
     }
 }
 
-fn get_paginated_infra_errors(
-    conn: &mut PgConnection,
-    infra: i64,
-    page: i64,
-    per_page: i64,
-    params: &QueryParams,
-) -> Result<(Vec<InfraErrorModel>, u64)> {
-    let mut query =
-        String::from("SELECT information::text, ST_AsGeoJSON(ST_Transform(geographic, 4326))::json as geographic,
-        ST_AsGeoJSON(ST_Transform(schematic, 4326))::json as schematic FROM osrd_infra_errorlayer WHERE infra_id = $1");
+/// Build the query selecting the given columns of the errors of an infra (`$1`)
+/// The `error_type` (`$2`) and `object_id` (`$3`) filters are applied if given
+fn infra_errors_query(params: &QueryParams, columns: &str) -> String {
+    let mut query = format!(
+        "SELECT {columns} FROM (
+            SELECT information,
+            ST_Transform(geographic, 4326) as geographic,
+            ST_Transform(schematic, 4326) as schematic
+            FROM osrd_infra_errorlayer
+            WHERE infra_id = $1
+        ) AS infra_errors WHERE TRUE"
+    );
     if params.level == Level::Warnings {
         query += " AND information->>'is_warning' = 'true'"
     } else if params.level == Level::Errors {
@@ -141,7 +537,21 @@ fn get_paginated_infra_errors(
     if params.object_id.is_some() {
         query += " AND information->>'obj_id' = $3"
     }
+    query
+}
 
+fn get_paginated_infra_errors(
+    conn: &mut PgConnection,
+    infra: i64,
+    page: i64,
+    per_page: i64,
+    params: &QueryParams,
+) -> Result<(Vec<InfraErrorModel>, u64)> {
+    let query = infra_errors_query(
+        params,
+        "information::text, ST_AsGeoJSON(geographic)::json as geographic,
+        ST_AsGeoJSON(schematic)::json as schematic",
+    );
     let infra_errors = paginate(query, page, per_page)
         .bind::<BigInt, _>(infra)
         .bind::<Text, _>(params.error_type.clone().unwrap_or_default())
@@ -157,13 +567,98 @@ fn get_paginated_infra_errors(
 
 #[cfg(test)]
 mod tests {
+    use super::{
+        errors_to_csv, errors_to_geojson, ErrorGroupQueryable, ErrorsSummary,
+        ExportedErrorQueryable,
+    };
     use crate::infra::Infra;
+    use crate::map::BoundingBox;
     use crate::views::infra::errors::check_error_type_query;
+    use crate::views::infra::tests::{create_infra_request, delete_infra_request};
     use crate::views::tests::create_test_service;
     use actix_web::http::StatusCode;
     use actix_web::test as actix_test;
     use actix_web::test::{call_and_read_body_json, call_service, TestRequest};
-    use serde_json::json;
+    use serde_json::{json, Value};
+
+    fn error_group(error_type: &str, is_warning: bool, count: i64) -> ErrorGroupQueryable {
+        ErrorGroupQueryable {
+            error_type: error_type.into(),
+            obj_type: "TrackSection".into(),
+            is_warning,
+            count,
+            reference_count: 1,
+            new_count: count,
+            fixed_count: 1,
+            geo_x_min: Some(0.),
+            geo_y_min: Some(count as f64),
+            geo_x_max: Some(1.),
+            geo_y_max: Some(2. * count as f64),
+            sch_x_min: None,
+            sch_y_min: None,
+            sch_x_max: None,
+            sch_y_max: None,
+        }
+    }
+
+    #[test]
+    fn summary() {
+        let groups = vec![
+            error_group("invalid_reference", false, 2),
+            error_group("out_of_range", false, 3),
+            error_group("unused_port", true, 1),
+        ];
+        let summary = ErrorsSummary::new(groups.clone(), None);
+        assert_eq!(summary.errors, 5);
+        assert_eq!(summary.warnings, 1);
+        assert_eq!(summary.bbox_geo, Some(BoundingBox((0., 1.), (1., 6.))));
+        assert_eq!(summary.bbox_sch, None);
+        assert!(summary.comparison.is_none());
+        assert!(summary
+            .groups
+            .iter()
+            .all(|group| group.comparison.is_none()));
+
+        let summary = ErrorsSummary::new(groups, Some(42));
+        let comparison = summary.comparison.unwrap();
+        assert_eq!(comparison.infra_id, 42);
+        assert_eq!(comparison.new, 6);
+        assert_eq!(comparison.fixed, 3);
+    }
+
+    fn exported_errors() -> Vec<ExportedErrorQueryable> {
+        vec![ExportedErrorQueryable {
+            information: json!({
+                "obj_id": "track",
+                "obj_type": "TrackSection",
+                "field": "slopes.0.end",
+                "is_warning": false,
+                "error_type": "out_of_range",
+                "position": 530.,
+                "expected_range": [100., 500.],
+            }),
+            geographic: Some(json!({ "type": "Point", "coordinates": [1., 2.] })),
+            geographic_wkt: Some("POINT(1 2)".into()),
+        }]
+    }
+
+    #[test]
+    fn export_geojson() {
+        let geojson = errors_to_geojson(&exported_errors());
+        assert_eq!(geojson["type"], "FeatureCollection");
+        assert_eq!(geojson["features"][0]["geometry"]["type"], "Point");
+        assert_eq!(geojson["features"][0]["properties"]["obj_id"], "track");
+    }
+
+    #[test]
+    fn export_csv() {
+        let csv = errors_to_csv(&exported_errors()).unwrap();
+        assert_eq!(
+            csv,
+            "obj_type,obj_id,field,error_type,is_warning,context,geometry\n\
+            TrackSection,track,slopes.0.end,out_of_range,false,\"{\"\"expected_range\"\":[100.0,500.0],\"\"position\"\":530.0}\",POINT(1 2)\n"
+        );
+    }
 
     #[test]
     fn check_error_type() {
@@ -201,4 +696,110 @@ mod tests {
         let response = call_service(&app, req).await;
         assert_eq!(response.status(), StatusCode::NO_CONTENT);
     }
+
+    #[actix_test]
+    async fn errors_summary_get() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("errors_summary_test")).await;
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/errors/summary", infra.id).as_str())
+            .to_request();
+        let summary: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(summary["errors"], json!(0));
+        assert!(summary.get("comparison").is_none());
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{0}/errors/summary?compare_to={0}", infra.id).as_str())
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let reference: Infra =
+            call_and_read_body_json(&app, create_infra_request("errors_summary_reference")).await;
+        let req = TestRequest::get()
+            .uri(
+                format!(
+                    "/infra/{}/errors/summary?compare_to={}",
+                    infra.id, reference.id
+                )
+                .as_str(),
+            )
+            .to_request();
+        let summary: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(summary["comparison"]["new"], json!(0));
+
+        let response = call_service(&app, delete_infra_request(reference.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_test]
+    async fn errors_export() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("errors_export_test")).await;
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/errors/export?level=warnings", infra.id).as_str())
+            .to_request();
+        let geojson: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            geojson,
+            json!({ "type": "FeatureCollection", "features": [] })
+        );
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/errors/export?format=csv", infra.id).as_str())
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("content-type").unwrap(), "text/csv");
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_test]
+    async fn error_settings_update() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("error_settings_test")).await;
+
+        let settings = json!([
+            { "error_type": "missing_route", "enabled": false },
+            { "error_type": "invalid_reference", "is_warning": true },
+        ]);
+        let req = TestRequest::put()
+            .uri(format!("/infra/{}/errors/settings", infra.id).as_str())
+            .set_json(settings)
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/errors/settings", infra.id).as_str())
+            .to_request();
+        let settings: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            settings,
+            json!([
+                { "error_type": "invalid_reference", "enabled": true, "is_warning": true },
+                { "error_type": "missing_route", "enabled": false, "is_warning": null },
+            ])
+        );
+
+        let req = TestRequest::put()
+            .uri(format!("/infra/{}/errors/settings", infra.id).as_str())
+            .set_json(json!([{ "error_type": "not_an_error" }]))
+            .to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
 }
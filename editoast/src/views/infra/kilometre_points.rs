@@ -0,0 +1,336 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use actix_web::dev::HttpServiceFactory;
+use actix_web::web::{block, Data, Json, Path};
+use actix_web::{get, post, services};
+use diesel::sql_types::{Array, BigInt, Integer, Jsonb, Text};
+use diesel::{sql_query, PgConnection, QueryableByName, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+use super::linear_referencing::TrackOffset;
+use crate::error::Result;
+use crate::schema::utils::KilometrePoint;
+use crate::schema::TrackSection;
+use crate::DbPool;
+use editoast_derive::EditoastError;
+
+/// Return `/infra/<infra_id>/kilometre_points` routes
+pub fn routes() -> impl HttpServiceFactory {
+    services![locate, from_track, list_line_tracks]
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra:kilometre_points")]
+enum KilometrePointError {
+    #[error("Track '{0}' not found")]
+    #[editoast_error(status = 404)]
+    TrackNotFound(String),
+    #[error("Track '{0}' has no kilometre point")]
+    NoKilometrePoint(String),
+    #[error(
+        "Kilometre point {kilometre_point} of track '{track_name}' on line {line_code} not found"
+    )]
+    #[editoast_error(status = 404)]
+    KilometrePointNotFound {
+        line_code: i32,
+        track_name: String,
+        kilometre_point: KilometrePoint,
+    },
+    #[error("Track data is invalid: {0}")]
+    #[editoast_error(status = 500)]
+    InvalidTrackData(String),
+}
+
+/// Location of a point given as a kilometre point of a line track
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct LineLocation {
+    line_code: i32,
+    track_name: String,
+    kilometre_point: KilometrePoint,
+}
+
+#[derive(QueryableByName)]
+struct TrackData {
+    #[diesel(sql_type = Jsonb)]
+    data: JsonValue,
+}
+
+fn parse_tracks(tracks: Vec<TrackData>) -> Result<Vec<TrackSection>> {
+    tracks
+        .into_iter()
+        .map(|track| {
+            serde_json::from_value(track.data)
+                .map_err(|err| KilometrePointError::InvalidTrackData(err.to_string()).into())
+        })
+        .collect()
+}
+
+/// Load the tracks of the given lines
+fn load_line_tracks(
+    conn: &mut PgConnection,
+    infra: i64,
+    line_codes: Vec<i32>,
+) -> Result<Vec<TrackSection>> {
+    let tracks = sql_query(
+        "SELECT data FROM osrd_infra_tracksectionmodel
+            WHERE infra_id = $1 AND (data->'extensions'->'sncf'->>'line_code')::integer = ANY($2)",
+    )
+    .bind::<BigInt, _>(infra)
+    .bind::<Array<Integer>, _>(line_codes)
+    .load(conn)?;
+    parse_tracks(tracks)
+}
+
+/// Convert kilometre points of line tracks to track offsets
+#[post("/kilometre_points/locate")]
+async fn locate(
+    infra: Path<i64>,
+    locations: Json<Vec<LineLocation>>,
+    db_pool: Data<DbPool>,
+) -> Result<Json<Vec<TrackOffset>>> {
+    let infra = infra.into_inner();
+    let line_codes = locations.iter().map(|loc| loc.line_code).collect();
+    let tracks = block(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        load_line_tracks(&mut conn, infra, line_codes)
+    })
+    .await
+    .unwrap()?;
+
+    let offsets = locations
+        .iter()
+        .map(|location| locate_on_line(location, &tracks))
+        .collect::<Result<_>>()?;
+    Ok(Json(offsets))
+}
+
+/// Find the track offset of a kilometre point among the tracks of a line
+fn locate_on_line(location: &LineLocation, tracks: &[TrackSection]) -> Result<TrackOffset> {
+    tracks
+        .iter()
+        .filter(|track| {
+            track.extensions.sncf.as_ref().is_some_and(|sncf| {
+                sncf.line_code == location.line_code && sncf.track_name.0 == location.track_name
+            })
+        })
+        .find_map(|track| {
+            let offset = track.kilometre_point_offset(location.kilometre_point)?;
+            Some(TrackOffset {
+                track: track.id.clone(),
+                offset,
+            })
+        })
+        .ok_or_else(|| {
+            KilometrePointError::KilometrePointNotFound {
+                line_code: location.line_code,
+                track_name: location.track_name.clone(),
+                kilometre_point: location.kilometre_point,
+            }
+            .into()
+        })
+}
+
+/// Convert track offsets to kilometre points of their line
+#[post("/kilometre_points/from_track")]
+async fn from_track(
+    infra: Path<i64>,
+    locations: Json<Vec<TrackOffset>>,
+    db_pool: Data<DbPool>,
+) -> Result<Json<Vec<LineLocation>>> {
+    let infra = infra.into_inner();
+    let track_ids: Vec<String> = locations.iter().map(|loc| loc.track.0.clone()).collect();
+    let tracks = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let tracks = sql_query(
+            "SELECT data FROM osrd_infra_tracksectionmodel WHERE infra_id = $1 AND obj_id = ANY($2)",
+        )
+        .bind::<BigInt, _>(infra)
+        .bind::<Array<Text>, _>(track_ids)
+        .load(&mut conn)?;
+        parse_tracks(tracks)
+    })
+    .await
+    .unwrap()?;
+    let tracks: HashMap<_, _> = tracks
+        .into_iter()
+        .map(|track| (track.id.0.clone(), track))
+        .collect();
+
+    let line_locations = locations
+        .iter()
+        .map(|location| {
+            let track = tracks
+                .get(location.track.as_str())
+                .ok_or_else(|| KilometrePointError::TrackNotFound(location.track.0.clone()))?;
+            let no_kilometre_point = || KilometrePointError::NoKilometrePoint(track.id.0.clone());
+            let sncf = track
+                .extensions
+                .sncf
+                .as_ref()
+                .ok_or_else(no_kilometre_point)?;
+            let kilometre_point = track
+                .kilometre_point(location.offset)
+                .ok_or_else(no_kilometre_point)?;
+            Ok(LineLocation {
+                line_code: sncf.line_code,
+                track_name: sncf.track_name.0.clone(),
+                kilometre_point,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(Json(line_locations))
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LineTrack {
+    track: String,
+    track_name: String,
+    track_number: i32,
+    /// Kilometre point at the beginning of the track
+    begin: Option<KilometrePoint>,
+    /// Kilometre point at the end of the track
+    end: Option<KilometrePoint>,
+}
+
+impl LineTrack {
+    fn min_kilometre_point(&self) -> Option<KilometrePoint> {
+        match (self.begin, self.end) {
+            (Some(begin), Some(end)) => Some(if begin < end { begin } else { end }),
+            _ => None,
+        }
+    }
+}
+
+/// List the tracks of a line ordered by kilometre point
+/// Tracks without kilometre point are listed last
+#[get("/kilometre_points/lines/{line_code}")]
+async fn list_line_tracks(
+    path: Path<(i64, i32)>,
+    db_pool: Data<DbPool>,
+) -> Result<Json<Vec<LineTrack>>> {
+    let (infra, line_code) = path.into_inner();
+    let tracks = block(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        load_line_tracks(&mut conn, infra, vec![line_code])
+    })
+    .await
+    .unwrap()?;
+    Ok(Json(sort_line_tracks(tracks)))
+}
+
+fn sort_line_tracks(tracks: Vec<TrackSection>) -> Vec<LineTrack> {
+    let mut line_tracks: Vec<_> = tracks
+        .into_iter()
+        .filter_map(|track| {
+            let sncf = track.extensions.sncf.as_ref()?;
+            Some(LineTrack {
+                track: track.id.0.clone(),
+                track_name: sncf.track_name.0.clone(),
+                track_number: sncf.track_number,
+                begin: track.kilometre_point(0.),
+                end: track.kilometre_point(track.length),
+            })
+        })
+        .collect();
+    line_tracks.sort_by(|a, b| {
+        let kilometre_points = match (a.min_kilometre_point(), b.min_kilometre_point()) {
+            (Some(a), Some(b)) => a.0.total_cmp(&b.0),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        kilometre_points
+            .then_with(|| a.track_name.cmp(&b.track_name))
+            .then_with(|| a.track.cmp(&b.track))
+    });
+    line_tracks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{locate_on_line, parse_tracks, sort_line_tracks, LineLocation, TrackData};
+    use crate::schema::utils::KilometrePoint;
+    use crate::schema::TrackSection;
+    use serde_json::{from_value, json};
+
+    fn track(id: &str, track_name: &str, kilometre_points: serde_json::Value) -> TrackSection {
+        from_value(json!({
+            "id": id,
+            "length": 1000.,
+            "slopes": [],
+            "curves": [],
+            "geo": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+            "sch": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+            "extensions": { "sncf": {
+                "line_code": 420000,
+                "line_name": "line",
+                "track_number": 1,
+                "track_name": track_name,
+                "kilometre_points": kilometre_points,
+            }},
+        }))
+        .unwrap()
+    }
+
+    fn tracks() -> Vec<TrackSection> {
+        vec![
+            track(
+                "B",
+                "V1",
+                json!([{ "offset": 0., "kilometre_point": "13+000" }]),
+            ),
+            track(
+                "A",
+                "V1",
+                json!([{ "offset": 0., "kilometre_point": "12+000" }]),
+            ),
+            track(
+                "C",
+                "V2",
+                json!([{ "offset": 0., "kilometre_point": "12+000" }]),
+            ),
+            track("D", "V1", json!([])),
+        ]
+    }
+
+    #[test]
+    fn locate_kilometre_point() {
+        let location = LineLocation {
+            line_code: 420000,
+            track_name: "V1".into(),
+            kilometre_point: "13+250".parse().unwrap(),
+        };
+        let offset = locate_on_line(&location, &tracks()).unwrap();
+        assert_eq!(offset.track.0, "B");
+        assert_eq!(offset.offset, 250.);
+
+        let location = LineLocation {
+            kilometre_point: KilometrePoint(20000.),
+            ..location
+        };
+        assert!(locate_on_line(&location, &tracks()).is_err());
+    }
+
+    #[test]
+    fn line_tracks_order() {
+        let line_tracks = sort_line_tracks(tracks());
+        let ids: Vec<_> = line_tracks
+            .iter()
+            .map(|track| track.track.as_str())
+            .collect();
+        assert_eq!(ids, vec!["A", "C", "B", "D"]);
+        assert_eq!(line_tracks[0].end, Some(KilometrePoint(13000.)));
+    }
+
+    #[test]
+    fn parse_invalid_track() {
+        let tracks = vec![TrackData {
+            data: json!({ "id": "A" }),
+        }];
+        assert!(parse_tracks(tracks).is_err());
+    }
+}
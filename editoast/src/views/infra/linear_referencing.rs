@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use actix_web::dev::HttpServiceFactory;
+use actix_web::web::{block, Data, Json, Path};
+use actix_web::{post, services};
+use diesel::sql_types::{Array, BigInt, Double, Jsonb, Text};
+use diesel::{sql_query, QueryableByName, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+use crate::error::Result;
+use crate::schema::utils::Identifier;
+use crate::schema::{GeoJson, LineString, TrackSection};
+use crate::DbPool;
+use editoast_derive::EditoastError;
+
+/// Return `/infra/<infra_id>/linear_referencing` routes
+pub fn routes() -> impl HttpServiceFactory {
+    services![locate, project]
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra:linear_referencing")]
+enum LinearReferencingError {
+    #[error("Track '{0}' not found")]
+    #[editoast_error(status = 404)]
+    TrackNotFound(String),
+    #[error("Offset {offset} is out of track '{track}' (length: {length})")]
+    InvalidOffset {
+        track: String,
+        offset: f64,
+        length: f64,
+    },
+    #[error("No track found in the infra, its generated data may need to be refreshed")]
+    #[editoast_error(status = 404)]
+    NoTrackFound,
+    #[error("Track '{0}' has a null length, offsets can't be located on it")]
+    NullLengthTrack(String),
+    #[error("Track data is invalid: {0}")]
+    #[editoast_error(status = 500)]
+    InvalidTrackData(String),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct TrackOffset {
+    pub track: Identifier,
+    pub offset: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LocatedPoint {
+    geographic: GeoJson,
+    schematic: GeoJson,
+}
+
+#[derive(QueryableByName)]
+struct TrackData {
+    #[diesel(sql_type = Jsonb)]
+    data: JsonValue,
+}
+
+/// Convert track offsets to geographic and schematic points
+/// Points are interpolated along the track geometries, scaled to the track length
+#[post("/linear_referencing/locate")]
+async fn locate(
+    infra: Path<i64>,
+    locations: Json<Vec<TrackOffset>>,
+    db_pool: Data<DbPool>,
+) -> Result<Json<Vec<LocatedPoint>>> {
+    let infra = infra.into_inner();
+    let track_ids: Vec<String> = locations.iter().map(|loc| loc.track.0.clone()).collect();
+    let tracks: Vec<TrackData> = block(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        sql_query(
+            "SELECT data FROM osrd_infra_tracksectionmodel WHERE infra_id = $1 AND obj_id = ANY($2)",
+        )
+        .bind::<BigInt, _>(infra)
+        .bind::<Array<Text>, _>(track_ids)
+        .load(&mut conn)
+    })
+    .await
+    .unwrap()?;
+    let tracks: HashMap<_, _> = tracks
+        .into_iter()
+        .map(|track| {
+            let track = serde_json::from_value::<TrackSection>(track.data)
+                .map_err(|err| LinearReferencingError::InvalidTrackData(err.to_string()))?;
+            Ok((track.id.0.clone(), track))
+        })
+        .collect::<Result<_>>()?;
+
+    let points = locations
+        .iter()
+        .map(|location| locate_on_track(location, tracks.get(location.track.as_str())))
+        .collect::<Result<_>>()?;
+    Ok(Json(points))
+}
+
+/// Compute the geographic and schematic points of a track offset
+fn locate_on_track(location: &TrackOffset, track: Option<&TrackSection>) -> Result<LocatedPoint> {
+    let track =
+        track.ok_or_else(|| LinearReferencingError::TrackNotFound(location.track.0.clone()))?;
+    if !(0. ..=track.length).contains(&location.offset) {
+        return Err(LinearReferencingError::InvalidOffset {
+            track: location.track.0.clone(),
+            offset: location.offset,
+            length: track.length,
+        }
+        .into());
+    }
+    if track.length <= 0. {
+        return Err(LinearReferencingError::NullLengthTrack(location.track.0.clone()).into());
+    }
+    let fraction = location.offset / track.length;
+    let to_point = |line: &LineString| match line.interpolate(fraction) {
+        Some([x, y]) => Ok(GeoJson::Point {
+            coordinates: (x, y),
+        }),
+        None => Err(LinearReferencingError::InvalidTrackData(format!(
+            "track '{}' has an empty geometry",
+            location.track.0
+        ))),
+    };
+    Ok(LocatedPoint {
+        geographic: to_point(&track.geo)?,
+        schematic: to_point(&track.sch)?,
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectInput {
+    /// WGS84 point (longitude, latitude)
+    point: (f64, f64),
+}
+
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+struct ProjectedPoint {
+    #[diesel(sql_type = Text)]
+    track: String,
+    #[diesel(sql_type = Double)]
+    offset: f64,
+    /// Distance in meters between the given point and the track
+    #[diesel(sql_type = Double)]
+    distance: f64,
+    #[diesel(sql_type = Jsonb)]
+    geographic: JsonValue,
+}
+
+/// Snap a point to the nearest track of the infra
+/// Relies on the generated track section layer
+#[post("/linear_referencing/project")]
+async fn project(
+    infra: Path<i64>,
+    input: Json<ProjectInput>,
+    db_pool: Data<DbPool>,
+) -> Result<Json<ProjectedPoint>> {
+    let infra = infra.into_inner();
+    let (x, y) = input.point;
+    let projected: Vec<ProjectedPoint> = block(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        sql_query(include_str!("sql/project_on_tracks.sql"))
+            .bind::<BigInt, _>(infra)
+            .bind::<Double, _>(x)
+            .bind::<Double, _>(y)
+            .load(&mut conn)
+    })
+    .await
+    .unwrap()?;
+
+    match projected.into_iter().next() {
+        Some(projected) => Ok(Json(projected)),
+        None => Err(LinearReferencingError::NoTrackFound.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test as actix_test;
+    use actix_web::test::{call_and_read_body_json, call_service, TestRequest};
+    use serde_json::{json, Value};
+
+    use super::{locate_on_track, TrackOffset};
+    use crate::infra::Infra;
+    use crate::schema::{GeoJson, LineString, TrackSection};
+    use crate::views::infra::tests::{
+        create_infra_request, create_object_request, delete_infra_request,
+    };
+    use crate::views::tests::create_test_service;
+
+    #[test]
+    fn locate_offset() {
+        let track = TrackSection {
+            length: 200.,
+            geo: LineString::LineString {
+                coordinates: vec![[0., 0.], [2., 0.]],
+            },
+            sch: LineString::LineString {
+                coordinates: vec![[0., 0.], [0., 4.]],
+            },
+            ..Default::default()
+        };
+        let location = TrackOffset {
+            track: track.id.clone(),
+            offset: 50.,
+        };
+        let point = locate_on_track(&location, Some(&track)).unwrap();
+        assert!(
+            matches!(point.geographic, GeoJson::Point { coordinates } if coordinates == (0.5, 0.))
+        );
+        assert!(
+            matches!(point.schematic, GeoJson::Point { coordinates } if coordinates == (0., 1.))
+        );
+
+        let location = TrackOffset {
+            offset: 250.,
+            ..location
+        };
+        assert!(locate_on_track(&location, Some(&track)).is_err());
+        assert!(locate_on_track(&location, None).is_err());
+
+        let track = TrackSection {
+            length: 0.,
+            ..track
+        };
+        let location = TrackOffset {
+            offset: 0.,
+            ..location
+        };
+        assert!(locate_on_track(&location, Some(&track)).is_err());
+
+        let track = TrackSection {
+            length: 200.,
+            geo: LineString::LineString {
+                coordinates: vec![],
+            },
+            ..track
+        };
+        assert!(locate_on_track(&location, Some(&track)).is_err());
+    }
+
+    #[actix_test]
+    async fn locate_and_project() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("linear_referencing_test")).await;
+
+        let track = TrackSection::default();
+        let req = create_object_request(infra.id, track.clone().into());
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}/linear_referencing/locate", infra.id).as_str())
+            .set_json(json!([{ "track": track.id, "offset": track.length / 2. }]))
+            .to_request();
+        let points: Vec<Value> = call_and_read_body_json(&app, req).await;
+        assert_eq!(points[0]["geographic"]["coordinates"], json!([0.5, 0.5]));
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}/linear_referencing/project", infra.id).as_str())
+            .set_json(json!({ "point": [1., 0.] }))
+            .to_request();
+        let projected: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(projected["track"], json!(track.id));
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+}
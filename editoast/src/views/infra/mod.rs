@@ -1,19 +1,25 @@
 mod attached;
+mod detection_sections;
 mod edition;
 mod errors;
+mod kilometre_points;
+mod linear_referencing;
 mod objects;
 mod pathfinding;
 mod railjson;
 mod routes;
+mod schematic;
 
 use std::pin::Pin;
 
 use self::edition::edit;
 use super::params::List;
+use crate::client::{MapLayersConfig, ValidationConfig};
 use crate::error::Result;
+use crate::generated_data::ErrorSettings;
 use crate::infra::{Infra, InfraName};
 use crate::infra_cache::{InfraCache, ObjectCache};
-use crate::map::{self, MapLayers};
+use crate::map::{self, MapLayers, SeedOptions, TileCache};
 use crate::schema::{ObjectType, SwitchType};
 use crate::DbPool;
 use actix_web::dev::HttpServiceFactory;
@@ -24,12 +30,15 @@ use diesel::sql_types::{BigInt, Double, Text};
 use diesel::{sql_query, QueryableByName, RunQueryDsl};
 use futures::future::join_all;
 use futures::Future;
-use redis::Client;
+use log::error;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use std::result::Result as StdResult;
 use strum::IntoEnumIterator;
 
+/// Number of tiles rendered concurrently when seeding the cache after a refresh
+const BACKGROUND_SEED_CONCURRENCY: usize = 4;
+
 /// Return `/infra` routes
 pub fn routes() -> impl HttpServiceFactory {
     scope("/infra")
@@ -54,6 +63,10 @@ pub fn routes() -> impl HttpServiceFactory {
                     routes::routes(),
                     pathfinding::routes(),
                     attached::routes(),
+                    schematic::routes(),
+                    linear_referencing::routes(),
+                    kilometre_points::routes(),
+                    detection_sections::routes(),
                 )),
         )
 }
@@ -80,13 +93,17 @@ struct RefreshQueryParams {
 
 /// Refresh infra generated data
 #[post("/refresh")]
+#[allow(clippy::too_many_arguments)]
 async fn refresh(
     db_pool: Data<DbPool>,
-    redis_client: Data<Client>,
+    tile_cache: Data<TileCache>,
     query_params: Query<RefreshQueryParams>,
     infra_caches: Data<CHashMap<i64, InfraCache>>,
     map_layers: Data<MapLayers>,
+    map_layers_config: Data<MapLayersConfig>,
+    validation_config: Data<ValidationConfig>,
 ) -> Result<Json<JsonValue>> {
+    let seed_db_pool = db_pool.clone();
     let refreshed_infra = block::<_, Result<_>>(move || {
         let mut conn = db_pool.get().expect("Failed to get DB connection");
         // Use a transaction to give scope to infra list lock
@@ -110,7 +127,12 @@ async fn refresh(
 
         for infra in infras_list {
             let infra_cache = InfraCache::get_or_load(&mut conn, &infra_caches, &infra)?;
-            if infra.refresh(&mut conn, query_params.force, &infra_cache)? {
+            if infra.refresh(
+                &mut conn,
+                query_params.force,
+                &infra_cache,
+                &validation_config,
+            )? {
                 refreshed_infra.push(infra.id);
             }
         }
@@ -118,14 +140,41 @@ async fn refresh(
     })
     .await
     .unwrap()?;
-    let mut conn = redis_client.get_tokio_connection_manager().await.unwrap();
     for infra_id in refreshed_infra.iter() {
-        map::invalidate_all(
-            &mut conn,
+        if let Err(err) = map::invalidate_all(
+            &tile_cache,
             &map_layers.layers.keys().cloned().collect(),
             *infra_id,
         )
-        .await;
+        .await
+        {
+            error!("Failed to invalidate map tiles of infra {infra_id}, cached tiles may be outdated: {err}");
+        }
+    }
+
+    // Warm the cache up in background
+    if let Some(max_zoom) = map_layers_config.seed_max_zoom {
+        let options = SeedOptions {
+            layers: vec![],
+            zoom_range: 0..=max_zoom,
+            concurrency: BACKGROUND_SEED_CONCURRENCY,
+        };
+        let infras = refreshed_infra.clone();
+        actix_web::rt::spawn(async move {
+            for infra_id in infras {
+                let seeding = map::seed_infra(
+                    &seed_db_pool,
+                    &tile_cache,
+                    &map_layers,
+                    infra_id,
+                    &options,
+                    |_| {},
+                );
+                if let Err(err) = seeding.await {
+                    error!("Failed to seed tiles of infra {infra_id}: {err}");
+                }
+            }
+        });
     }
 
     Ok(Json(json!({ "infra_refreshed": refreshed_infra })))
@@ -181,7 +230,9 @@ async fn clone(
     let cloned_infra = block::<_, Result<_>>(move || {
         let name = new_name.name.clone();
         let mut conn = db_pool_clone.get().expect("Failed to get DB connection");
-        Infra::clone(infra, &mut conn, name)
+        let cloned_infra = Infra::clone(infra, &mut conn, name)?;
+        ErrorSettings::clone_infra(&mut conn, infra, cloned_infra.id)?;
+        Ok(cloned_infra)
     })
     .await
     .unwrap()?;
@@ -364,7 +415,7 @@ async fn unlock(infra: Path<i64>, db_pool: Data<DbPool>) -> Result<HttpResponse>
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use crate::infra::Infra;
     use crate::schema::operation::{Operation, RailjsonObject};
     use crate::schema::{Catenary, SpeedSection, SwitchType};
@@ -1,22 +1,32 @@
 use std::collections::HashMap;
 
 use actix_web::dev::HttpServiceFactory;
-use actix_web::post;
-use actix_web::web::{block, Data, Json, Path};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{block, Data, Json, Path, Payload, Query};
+use actix_web::{get, post, services, HttpResponse};
+use chashmap::CHashMap;
 use diesel::sql_types::{Array, BigInt, Jsonb, Nullable, Text};
 use diesel::{sql_query, QueryableByName, RunQueryDsl};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use thiserror::Error;
 
+use super::edition::apply_edit;
+use crate::client::{MapLayersConfig, RailjsonConfig, ValidationConfig};
 use crate::error::Result;
-use crate::schema::ObjectType;
+use crate::infra::Infra;
+use crate::infra_cache::InfraCache;
+use crate::map::{self, MapLayers, TileCache};
+use crate::schema::operation::OperationResult;
+use crate::schema::{csv_update_operations, objects_to_csv, ObjectType};
 use crate::DbPool;
 use editoast_derive::EditoastError;
+use log::error;
 
 /// Return `/infra/<infra_id>/objects` routes
 pub fn routes() -> impl HttpServiceFactory {
-    get_objects
+    services![get_objects, export_csv, import_csv]
 }
 
 #[derive(Debug, Error, EditoastError)]
@@ -28,6 +38,16 @@ enum GetObjectsErrors {
     ObjectIdNotFound(String),
 }
 
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra:objects")]
+enum ImportCsvErrors {
+    #[error("Failed to receive the csv: {0}")]
+    Payload(String),
+    #[error("The csv exceeds the maximum size of {0} bytes")]
+    #[editoast_error(status = 413)]
+    TooLarge(u64),
+}
+
 /// Return whether the list of ids contains unique values or has duplicate
 fn has_unique_ids(obj_ids: &Vec<String>) -> bool {
     let mut obj_ids_2 = obj_ids.clone();
@@ -120,11 +140,132 @@ async fn get_objects(
     Ok(Json(result))
 }
 
+#[derive(QueryableByName)]
+struct ObjectData {
+    #[diesel(sql_type = Text)]
+    obj_id: String,
+    #[diesel(sql_type = Jsonb)]
+    data: JsonValue,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportCsvQueryParams {
+    expand: Option<String>,
+}
+
+/// Export all the objects of a type as csv
+/// Nested fields are flattened into columns, the `expand` array field is written one row per element
+#[get("/objects/{object_type}/export.csv")]
+async fn export_csv(
+    path_params: Path<(i64, ObjectType)>,
+    params: Query<ExportCsvQueryParams>,
+    db_pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    let (infra, obj_type) = path_params.into_inner();
+    let csv = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve(&mut conn, infra)?;
+        let objects: Vec<ObjectData> = sql_query(format!(
+            "SELECT obj_id, data FROM {} WHERE infra_id = $1 ORDER BY obj_id",
+            obj_type.get_table()
+        ))
+        .bind::<BigInt, _>(infra.id)
+        .load(&mut conn)?;
+        let objects: Vec<_> = objects.into_iter().map(|obj| obj.data).collect();
+        objects_to_csv(&objects, params.expand.as_deref())
+    })
+    .await
+    .unwrap()?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "{}.csv",
+                obj_type.get_railjson_field()
+            ))],
+        })
+        .body(csv))
+}
+
+/// Update objects attributes from a csv using the export layout
+/// Each modified row is applied as an update operation, expanded arrays are gathered back by object
+#[post("/objects/{object_type}/import.csv")]
+#[allow(clippy::too_many_arguments)]
+async fn import_csv(
+    path_params: Path<(i64, ObjectType)>,
+    mut payload: Payload,
+    railjson_config: Data<RailjsonConfig>,
+    db_pool: Data<DbPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    tile_cache: Data<TileCache>,
+    map_layers: Data<MapLayers>,
+    map_layers_config: Data<MapLayersConfig>,
+    validation_config: Data<ValidationConfig>,
+) -> Result<Json<Vec<OperationResult>>> {
+    let (infra, obj_type) = path_params.into_inner();
+    let max_size = railjson_config.csv_max_size;
+    let mut data = vec![];
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|err| ImportCsvErrors::Payload(err.to_string()))?;
+        if (data.len() + chunk.len()) as u64 > max_size {
+            return Err(ImportCsvErrors::TooLarge(max_size).into());
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let (operation_results, invalid_zone) = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve_for_update(&mut conn, infra)?;
+        let objects: Vec<ObjectData> = sql_query(format!(
+            "SELECT obj_id, data FROM {} WHERE infra_id = $1",
+            obj_type.get_table()
+        ))
+        .bind::<BigInt, _>(infra.id)
+        .load(&mut conn)?;
+        let objects = objects
+            .into_iter()
+            .map(|obj| (obj.obj_id, obj.data))
+            .collect();
+        let operations = csv_update_operations(obj_type, &data, &objects)?;
+        let mut infra_cache =
+            InfraCache::get_or_load_mut(&mut conn, &infra_caches, &infra).unwrap();
+        apply_edit(
+            &mut conn,
+            &infra,
+            &operations,
+            &mut infra_cache,
+            &validation_config,
+        )
+    })
+    .await
+    .unwrap()?;
+
+    if let Err(err) = map::invalidate_zone(
+        &tile_cache,
+        &map_layers,
+        infra,
+        &invalid_zone,
+        &map_layers_config,
+    )
+    .await
+    {
+        error!(
+            "Failed to invalidate map tiles of infra {infra}, cached tiles may be outdated: {err}"
+        );
+    }
+
+    Ok(Json(operation_results))
+}
+
 #[cfg(test)]
 mod tests {
     use actix_web::http::StatusCode;
     use actix_web::test as actix_test;
-    use actix_web::test::{call_and_read_body_json, call_service, TestRequest};
+    use actix_web::test::{call_and_read_body, call_and_read_body_json, call_service, TestRequest};
+
+    use serde_json::{json, Value};
 
     use crate::infra::Infra;
     use crate::schema::SwitchType;
@@ -204,4 +345,88 @@ mod tests {
         let response = call_service(&app, delete_infra_request(infra.id)).await;
         assert_eq!(response.status(), StatusCode::NO_CONTENT);
     }
+
+    #[actix_test]
+    async fn export_csv() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("export_csv_test")).await;
+
+        let switch_type = SwitchType::default();
+        let switch_id = switch_type.id.clone();
+        let req = create_object_request(infra.id, switch_type.into());
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/objects/SwitchType/export.csv", infra.id).as_str())
+            .to_request();
+        let body = call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let mut lines = body.lines();
+        assert!(lines.next().unwrap().starts_with("id,"));
+        assert!(lines.next().unwrap().starts_with(switch_id.0.as_str()));
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_test]
+    async fn import_csv() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("import_csv_test")).await;
+
+        let switch_type = SwitchType {
+            ports: vec!["BASE".into(), "LEFT".into()],
+            ..Default::default()
+        };
+        let switch_id = switch_type.id.clone();
+        let req = create_object_request(infra.id, switch_type.into());
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        // Expanded arrays are gathered back on import
+        let req = TestRequest::get()
+            .uri(
+                format!(
+                    "/infra/{}/objects/SwitchType/export.csv?expand=ports",
+                    infra.id
+                )
+                .as_str(),
+            )
+            .to_request();
+        let csv = call_and_read_body(&app, req).await;
+        let csv = String::from_utf8(csv.to_vec()).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        let import_uri = format!("/infra/{}/objects/SwitchType/import.csv", infra.id);
+        let req = TestRequest::post()
+            .uri(import_uri.as_str())
+            .set_payload(csv.clone())
+            .to_request();
+        let results: Vec<Value> = call_and_read_body_json(&app, req).await;
+        assert!(results.is_empty());
+
+        let req = TestRequest::post()
+            .uri(import_uri.as_str())
+            .set_payload(csv.replace(",LEFT", ",RIGHT"))
+            .to_request();
+        let results: Vec<Value> = call_and_read_body_json(&app, req).await;
+        assert_eq!(results.len(), 1);
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}/objects/SwitchType", infra.id).as_str())
+            .set_json(vec![switch_id])
+            .to_request();
+        let objects: Vec<Value> = call_and_read_body_json(&app, req).await;
+        assert_eq!(objects[0]["railjson"]["ports"], json!(["BASE", "RIGHT"]));
+
+        let req = TestRequest::post()
+            .uri(import_uri.as_str())
+            .set_payload("id,ports\nunknown,BASE\n")
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
 }
@@ -1,35 +1,62 @@
-use crate::error::Result;
-use crate::infra::RAILJSON_VERSION;
+use super::edition::apply_edit;
+use crate::client::{MapLayersConfig, RailjsonConfig, ValidationConfig};
+use crate::error::{InternalError, Result};
+use crate::infra::Infra;
 use crate::infra_cache::InfraCache;
-use crate::schema::RailJson;
+use crate::map::{self, MapLayers, TileCache};
+use crate::schema::{
+    merge_operations, persist_railjson_file, railjson_fragment_from_value, MergeConflictPolicy,
+    MergeReport, ObjectType,
+};
 use crate::DbPool;
 use actix_web::dev::HttpServiceFactory;
-use actix_web::web::{block, Data, Json, Path, Query};
-use actix_web::{get, post, services};
+use actix_web::http::header::ContentType;
+use actix_web::middleware::Compress;
+use actix_web::web::{block, Bytes, Data, Json, Path, Payload, Query};
+use actix_web::{get, post, services, HttpResponse};
 use chashmap::CHashMap;
-use diesel::sql_types::BigInt;
-use diesel::sql_types::Text;
+use diesel::sql_types::{BigInt, Nullable, Text};
 use diesel::{sql_query, RunQueryDsl};
 use editoast_derive::EditoastError;
+use futures::future::ready;
+use futures::{stream, Stream, StreamExt};
+use log::error;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{Seek, Write};
+use strum::IntoEnumIterator;
 use thiserror::Error;
 
 /// Return `/infra/<infra_id>/railjson` routes
 pub fn routes() -> impl HttpServiceFactory {
-    services![get_railjson, post_railjson]
+    services![get_railjson, post_railjson, merge_railjson]
 }
 
+/// Number of objects fetched by each query of an infra export
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+/// Page of objects of an infra export, `railjson` being their comma separated json
 #[derive(QueryableByName)]
-struct RailJsonData {
+struct RailJsonPage {
     #[diesel(sql_type = Text)]
     railjson: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    last_obj_id: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
 }
 
 #[derive(Debug, Error, EditoastError)]
 #[editoast_error(base_id = "infra:railjson")]
-enum ListErrorsRailjson {
-    #[error("Wrong Railjson version provided")]
-    WrongRailjsonVersionProvided,
+enum RailjsonTransferError {
+    #[error("Failed to receive the railjson: {0}")]
+    Payload(String),
+    #[error("Failed to buffer the railjson: {0}")]
+    #[editoast_error(status = 500)]
+    Buffer(String),
+    #[error("The railjson exceeds the maximum size of {0} bytes")]
+    #[editoast_error(status = 413)]
+    TooLarge(u64),
 }
 
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -39,28 +66,93 @@ struct GetRailjsonQueryParam {
 }
 
 /// Serialize an infra
-#[get("/{infra}/railjson")]
+/// Objects are streamed by pages, the response is compressed if the client accepts it
+#[get("/{infra}/railjson", wrap = "Compress::default()")]
 async fn get_railjson(
     infra: Path<i64>,
     params: Query<GetRailjsonQueryParam>,
     db_pool: Data<DbPool>,
-) -> Result<String> {
+) -> Result<HttpResponse> {
     let infra = infra.into_inner();
-    let query = if params.exclude_extensions {
-        include_str!("sql/get_infra_no_ext.sql")
-    } else {
-        include_str!("sql/get_infra_with_ext.sql")
-    };
-    let railjson: RailJsonData = block::<_, Result<_>>(move || {
-        let mut conn = db_pool.get().expect("Failed to get DB connection");
-        sql_query(query)
-            .bind::<BigInt, _>(infra)
-            .get_result(&mut conn)
-            .map_err(Into::into)
+    let db_pool_clone = db_pool.clone();
+    let infra = block::<_, Result<_>>(move || {
+        let mut conn = db_pool_clone.get().expect("Failed to get DB connection");
+        Infra::retrieve(&mut conn, infra)
     })
     .await
     .unwrap()?;
-    Ok(railjson.railjson)
+
+    let data = if params.exclude_extensions {
+        "data - 'extensions'"
+    } else {
+        "data"
+    };
+    let objects = stream::iter(ObjectType::iter())
+        .flat_map(move |obj_type| export_objects(db_pool.clone(), infra.id, obj_type, data));
+
+    let version = serde_json::to_string(&infra.railjson_version).unwrap();
+    let body = stream::once(ready(Ok(Bytes::from(format!(r#"{{"version":{version}"#)))))
+        .chain(objects)
+        .chain(stream::once(ready(Ok(Bytes::from_static(b"}")))));
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .streaming(body))
+}
+
+/// Stream the railjson field of an object type, fetching objects by pages ordered by id
+fn export_objects(
+    db_pool: Data<DbPool>,
+    infra_id: i64,
+    obj_type: ObjectType,
+    data: &'static str,
+) -> impl Stream<Item = std::result::Result<Bytes, InternalError>> {
+    let field = obj_type.get_railjson_field();
+    // The state is the id of the last exported object, `None` when fetching the first page
+    let pages = stream::try_unfold(Some(None), move |after: Option<Option<String>>| {
+        let db_pool = db_pool.clone();
+        async move {
+            let after = match after {
+                Some(after) => after,
+                None => return Ok(None),
+            };
+            let first = after.is_none();
+            let page: RailJsonPage = block::<_, Result<_>>(move || {
+                let mut conn = db_pool.get().expect("Failed to get DB connection");
+                // Every id is greater than or equal to the empty string
+                let operator = if first { ">=" } else { ">" };
+                sql_query(format!(
+                    "SELECT coalesce(string_agg(data::text, ',' ORDER BY obj_id), '') AS railjson,
+                        max(obj_id) AS last_obj_id, count(*) AS count
+                    FROM (
+                        SELECT obj_id, {data} AS data FROM {table}
+                        WHERE infra_id = $1 AND obj_id {operator} $3
+                        ORDER BY obj_id LIMIT $2
+                    ) AS page",
+                    table = obj_type.get_table(),
+                ))
+                .bind::<BigInt, _>(infra_id)
+                .bind::<BigInt, _>(EXPORT_PAGE_SIZE)
+                .bind::<Text, _>(after.unwrap_or_default())
+                .get_result(&mut conn)
+                .map_err(Into::into)
+            })
+            .await
+            .unwrap()?;
+            let last_obj_id = match page.last_obj_id {
+                Some(last_obj_id) => last_obj_id,
+                None => return Ok(None),
+            };
+            let separator = if first { "" } else { "," };
+            let next = (page.count == EXPORT_PAGE_SIZE).then_some(Some(last_obj_id));
+            Ok(Some((
+                Bytes::from(format!("{separator}{}", page.railjson)),
+                next,
+            )))
+        }
+    });
+    stream::once(ready(Ok(Bytes::from(format!(r#","{field}":["#)))))
+        .chain(pages)
+        .chain(stream::once(ready(Ok(Bytes::from_static(b"]")))))
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -76,24 +168,45 @@ struct PostRailjsonResponse {
 }
 
 /// Import an infra
+/// The railjson is buffered on disk then imported by chunks, without being loaded in memory.
+/// Railjson using an older version of the format are upgraded before being imported.
 #[post("/railjson")]
 async fn post_railjson(
     params: Query<PostRailjsonQueryParams>,
-    railjson: Json<RailJson>,
+    mut payload: Payload,
     db_pool: Data<DbPool>,
     infra_caches: Data<CHashMap<i64, InfraCache>>,
+    railjson_config: Data<RailjsonConfig>,
+    validation_config: Data<ValidationConfig>,
 ) -> Result<Json<PostRailjsonResponse>> {
-    if railjson.version != RAILJSON_VERSION {
-        return Err(ListErrorsRailjson::WrongRailjsonVersionProvided.into());
+    let max_size = railjson_config.railjson_max_size;
+    let mut railjson_file = block(tempfile::tempfile)
+        .await
+        .unwrap()
+        .map_err(|err| RailjsonTransferError::Buffer(err.to_string()))?;
+    let mut size = 0;
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|err| RailjsonTransferError::Payload(err.to_string()))?;
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Err(RailjsonTransferError::TooLarge(max_size).into());
+        }
+        railjson_file = block(move || railjson_file.write_all(&chunk).map(|_| railjson_file))
+            .await
+            .unwrap()
+            .map_err(|err| RailjsonTransferError::Buffer(err.to_string()))?;
     }
 
     block(move || {
+        railjson_file
+            .rewind()
+            .map_err(|err| RailjsonTransferError::Buffer(err.to_string()))?;
         let mut conn = db_pool.get().expect("Failed to get DB connection");
-        let infra = railjson.persist(&params.name, &mut conn)?;
+        let infra = persist_railjson_file(railjson_file, &params.name, &mut conn)?;
         let infra = infra.bump_version(&mut conn)?;
         if params.generate_data {
             let infra_cache = InfraCache::get_or_load(&mut conn, &infra_caches, &infra)?;
-            infra.refresh(&mut conn, true, &infra_cache)?;
+            infra.refresh(&mut conn, true, &infra_cache, &validation_config)?;
         }
 
         Ok(Json(PostRailjsonResponse { infra: infra.id }))
@@ -102,6 +215,67 @@ async fn post_railjson(
     .unwrap()
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct MergeRailjsonQueryParams {
+    #[serde(default)]
+    on_conflict: MergeConflictPolicy,
+    #[serde(default)]
+    prefix: String,
+}
+
+/// Merge a railjson fragment into an existing infra
+/// Objects are created like an edition, ids already used in the infra are handled according to `on_conflict`.
+#[post("/{infra}/railjson/merge")]
+#[allow(clippy::too_many_arguments)]
+async fn merge_railjson(
+    infra: Path<i64>,
+    params: Query<MergeRailjsonQueryParams>,
+    railjson: Json<Value>,
+    db_pool: Data<DbPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    tile_cache: Data<TileCache>,
+    map_layers: Data<MapLayers>,
+    map_layers_config: Data<MapLayersConfig>,
+    validation_config: Data<ValidationConfig>,
+) -> Result<Json<MergeReport>> {
+    let infra = infra.into_inner();
+    let (report, invalid_zone) = block::<_, Result<_>>(move || {
+        let railjson = railjson_fragment_from_value(railjson.into_inner())?;
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve_for_update(&mut conn, infra)?;
+        let mut infra_cache =
+            InfraCache::get_or_load_mut(&mut conn, &infra_caches, &infra).unwrap();
+        let (operations, report) =
+            merge_operations(railjson, &infra_cache, params.on_conflict, &params.prefix)?;
+        let (_, invalid_zone) = apply_edit(
+            &mut conn,
+            &infra,
+            &operations,
+            &mut infra_cache,
+            &validation_config,
+        )?;
+        Ok((report, invalid_zone))
+    })
+    .await
+    .unwrap()?;
+
+    if let Err(err) = map::invalidate_zone(
+        &tile_cache,
+        &map_layers,
+        infra,
+        &invalid_zone,
+        &map_layers_config,
+    )
+    .await
+    {
+        error!(
+            "Failed to invalidate map tiles of infra {infra}, cached tiles may be outdated: {err}"
+        );
+    }
+
+    Ok(Json(report))
+}
+
 #[cfg(test)]
 mod tests {
     use actix_http::StatusCode;
@@ -115,6 +289,7 @@ mod tests {
         create_infra_request, create_object_request, delete_infra_request,
     };
     use crate::views::tests::create_test_service;
+    use serde_json::{json, Value};
 
     #[actix_test]
     async fn test_get_railjson() {
@@ -169,4 +344,69 @@ mod tests {
         let response = call_service(&app, delete_infra_request(infra.infra)).await;
         assert_eq!(response.status(), StatusCode::NO_CONTENT);
     }
+
+    #[actix_test]
+    async fn test_merge_railjson() {
+        let app = create_test_service().await;
+        let req = create_infra_request("merge_railjson_test");
+        let infra: Infra = call_and_read_body_json(&app, req).await;
+
+        let switch_type = SwitchType::default();
+        let switch_type_id = switch_type.id.clone();
+        let req = create_object_request(infra.id, switch_type.into());
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        // Conflicting ids are prefixed with the rename policy
+        let fragment = json!({
+            "switch_types": [{ "id": switch_type_id, "ports": ["BASE"], "groups": {} }]
+        });
+        let req = actix_test::TestRequest::post()
+            .uri(&format!(
+                "/infra/{}/railjson/merge?on_conflict=rename&prefix=merged_",
+                infra.id
+            ))
+            .set_json(&fragment)
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let report: Value = read_body_json(response).await;
+        assert_eq!(
+            report["renamed"][0]["new_id"],
+            format!("merged_{switch_type_id}")
+        );
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/infra/{}/railjson", infra.id))
+            .to_request();
+        let railjson: RailJson = call_and_read_body_json(&app, req).await;
+        let mut ids: Vec<_> = railjson
+            .switch_types
+            .iter()
+            .map(|switch_type| switch_type.id.0.clone())
+            .collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![switch_type_id.0.clone(), format!("merged_{switch_type_id}")]
+        );
+
+        // Invalid fragment
+        let req = actix_test::TestRequest::post()
+            .uri(&format!("/infra/{}/railjson/merge", infra.id))
+            .set_json(json!({ "switch_types": 1 }))
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Unknown infra
+        let req = actix_test::TestRequest::post()
+            .uri(&format!("/infra/{}/railjson/merge", infra.id))
+            .set_json(&fragment)
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 }
@@ -0,0 +1,110 @@
+use actix_web::dev::HttpServiceFactory;
+use actix_web::post;
+use actix_web::web::{block, Data, Json, Path};
+use chashmap::CHashMap;
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use super::edition::apply_edit;
+use crate::client::{MapLayersConfig, ValidationConfig};
+use crate::error::Result;
+use crate::infra::Infra;
+use crate::infra_cache::InfraCache;
+use crate::map::{self, MapLayers, TileCache};
+use crate::schematic::schematic_operations;
+use crate::DbPool;
+
+/// Return `/infra/<infra_id>/schematic` routes
+pub fn routes() -> impl HttpServiceFactory {
+    generate_schematic
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct GenerateSchematicResponse {
+    /// Number of track sections whose schematic geometry changed
+    track_sections: usize,
+}
+
+/// Compute the schematic geometry of the infra tracks from their topology
+/// Modified tracks are updated like an edition, refreshing the generated layers
+#[post("/schematic/generate")]
+#[allow(clippy::too_many_arguments)]
+async fn generate_schematic(
+    infra: Path<i64>,
+    db_pool: Data<DbPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    tile_cache: Data<TileCache>,
+    map_layers: Data<MapLayers>,
+    map_layers_config: Data<MapLayersConfig>,
+    validation_config: Data<ValidationConfig>,
+) -> Result<Json<GenerateSchematicResponse>> {
+    let infra = infra.into_inner();
+    let (operation_results, invalid_zone) = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        let infra = Infra::retrieve_for_update(&mut conn, infra)?;
+        let operations = schematic_operations(&mut conn, infra.id);
+        let mut infra_cache =
+            InfraCache::get_or_load_mut(&mut conn, &infra_caches, &infra).unwrap();
+        apply_edit(
+            &mut conn,
+            &infra,
+            &operations,
+            &mut infra_cache,
+            &validation_config,
+        )
+    })
+    .await
+    .unwrap()?;
+
+    if let Err(err) = map::invalidate_zone(
+        &tile_cache,
+        &map_layers,
+        infra,
+        &invalid_zone,
+        &map_layers_config,
+    )
+    .await
+    {
+        error!(
+            "Failed to invalidate map tiles of infra {infra}, cached tiles may be outdated: {err}"
+        );
+    }
+
+    Ok(Json(GenerateSchematicResponse {
+        track_sections: operation_results.len(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test as actix_test;
+    use actix_web::test::{call_and_read_body_json, call_service, TestRequest};
+
+    use super::GenerateSchematicResponse;
+    use crate::infra::Infra;
+    use crate::schema::TrackSection;
+    use crate::views::infra::tests::{
+        create_infra_request, create_object_request, delete_infra_request,
+    };
+    use crate::views::tests::create_test_service;
+
+    #[actix_test]
+    async fn generate_schematic() {
+        let app = create_test_service().await;
+        let infra: Infra =
+            call_and_read_body_json(&app, create_infra_request("generate_schematic_test")).await;
+
+        let req = create_object_request(infra.id, TrackSection::default().into());
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}/schematic/generate", infra.id).as_str())
+            .to_request();
+        let response: GenerateSchematicResponse = call_and_read_body_json(&app, req).await;
+        assert_eq!(response.track_sections, 1);
+
+        let response = call_service(&app, delete_infra_request(infra.id)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,67 @@
+WITH errors AS (
+    SELECT errors.infra_id,
+        errors.infra_id = $1 AS is_current,
+        errors.information->>'error_type' AS error_type,
+        errors.information->>'obj_type' AS obj_type,
+        errors.information->>'obj_id' AS obj_id,
+        errors.information->>'field' AS field,
+        (errors.information->>'is_warning')::boolean AS is_warning,
+        ST_Transform(errors.geographic, 4326) AS geographic,
+        ST_Transform(errors.schematic, 4326) AS schematic
+    FROM osrd_infra_errorlayer AS errors
+    WHERE errors.infra_id IN ($1, $2)
+),
+matches AS (
+    SELECT error_type,
+        obj_type,
+        obj_id,
+        field,
+        bool_or(infra_id = $1) AS in_current,
+        COALESCE(bool_or(infra_id = $2), FALSE) AS in_reference
+    FROM errors
+    GROUP BY error_type,
+        obj_type,
+        obj_id,
+        field
+),
+matched_errors AS (
+    SELECT errors.*,
+        matches.in_current
+        AND matches.in_reference AS matched
+    FROM errors
+        INNER JOIN matches USING (error_type, obj_type, obj_id, field)
+),
+groups AS (
+    SELECT error_type,
+        obj_type,
+        is_warning,
+        COUNT(*) FILTER (WHERE is_current) AS count,
+        COUNT(*) FILTER (WHERE NOT is_current) AS reference_count,
+        COUNT(*) FILTER (WHERE is_current AND NOT matched) AS new_count,
+        COUNT(*) FILTER (WHERE NOT is_current AND NOT matched) AS fixed_count,
+        ST_Extent(geographic) FILTER (WHERE is_current) AS bbox_geo,
+        ST_Extent(schematic) FILTER (WHERE is_current) AS bbox_sch
+    FROM matched_errors
+    GROUP BY error_type,
+        obj_type,
+        is_warning
+)
+SELECT error_type,
+    obj_type,
+    is_warning,
+    count,
+    reference_count,
+    new_count,
+    fixed_count,
+    ST_XMin(bbox_geo) AS geo_x_min,
+    ST_YMin(bbox_geo) AS geo_y_min,
+    ST_XMax(bbox_geo) AS geo_x_max,
+    ST_YMax(bbox_geo) AS geo_y_max,
+    ST_XMin(bbox_sch) AS sch_x_min,
+    ST_YMin(bbox_sch) AS sch_y_min,
+    ST_XMax(bbox_sch) AS sch_x_max,
+    ST_YMax(bbox_sch) AS sch_y_max
+FROM groups
+ORDER BY error_type,
+    obj_type,
+    is_warning
@@ -0,0 +1,24 @@
+WITH target AS (
+    SELECT ST_SetSRID(ST_MakePoint($2, $3), 4326) AS point
+),
+nearest AS (
+    SELECT layer.obj_id AS track,
+        ST_Transform(layer.geographic, 4326) AS track_geo,
+        (tracks.data->>'length')::float AS track_length
+    FROM osrd_infra_tracksectionlayer AS layer
+        INNER JOIN osrd_infra_tracksectionmodel AS tracks ON tracks.obj_id = layer.obj_id
+        AND tracks.infra_id = layer.infra_id,
+        target
+    WHERE layer.infra_id = $1
+    ORDER BY layer.geographic <-> ST_Transform(target.point, 3857)
+    LIMIT 1
+)
+SELECT nearest.track,
+    ST_LineLocatePoint(nearest.track_geo, target.point) * nearest.track_length AS offset,
+    ST_Distance(
+        ST_ClosestPoint(nearest.track_geo, target.point)::geography,
+        target.point::geography
+    ) AS distance,
+    ST_AsGeoJSON(ST_ClosestPoint(nearest.track_geo, target.point))::jsonb AS geographic
+FROM nearest,
+    target
@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+
+use crate::client::MapLayersConfig;
+use crate::error::Result;
+use crate::infra::Infra;
+use crate::map::{
+    get_layer_cache_prefix, get_view_cache_prefix, invalidate_full_layer_cache, invalidate_zone,
+    keys, memory_usage, BoundingBox, CacheCounters, InvalidationZone, MapLayers, TileCache,
+    TileCacheStats,
+};
+use crate::DbPool;
+use actix_web::dev::HttpServiceFactory;
+use actix_web::web::{block, scope, Data, Json, Query};
+use actix_web::{get, post, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use super::LayersError;
+
+/// Number of cached tiles of a view whose memory usage is measured to estimate the view one
+const MEMORY_USAGE_SAMPLE_SIZE: usize = 100;
+
+/// Returns `/layers/cache` routes
+pub fn routes() -> impl HttpServiceFactory {
+    scope("/cache").service((cache_stats, purge_cache))
+}
+
+#[derive(Debug, Deserialize)]
+struct CacheStatsQueryParam {
+    infra: Option<i64>,
+}
+
+/// Cached tiles of a layer view for an infra
+#[derive(Debug, Serialize)]
+struct ViewCacheStats {
+    layer: String,
+    view: String,
+    infra: i64,
+    keys: u64,
+    memory_usage: u64,
+    #[serde(flatten)]
+    counters: CacheCounters,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct CacheStats {
+    keys: u64,
+    memory_usage: u64,
+    #[serde(flatten)]
+    counters: CacheCounters,
+    views: Vec<ViewCacheStats>,
+}
+
+/// Retrieves the given infra or all the infras if none is given
+async fn get_infra_ids(db_pool: Data<DbPool>, infra: Option<i64>) -> Result<Vec<i64>> {
+    block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        match infra {
+            Some(infra) => Ok(vec![Infra::retrieve(&mut conn, infra)?.id]),
+            None => Ok(Infra::list(&mut conn)
+                .into_iter()
+                .map(|infra| infra.id)
+                .collect()),
+        }
+    })
+    .await
+    .unwrap()
+}
+
+/// Returns the number of cached tiles, their memory usage in bytes and the cache hits and misses
+/// of each layer view, for a given infra or all the infras
+/// Views without cached tiles nor requests are omitted
+#[get("")]
+async fn cache_stats(
+    params: Query<CacheStatsQueryParam>,
+    map_layers: Data<MapLayers>,
+    db_pool: Data<DbPool>,
+    tile_cache: Data<TileCache>,
+    tile_cache_stats: Data<TileCacheStats>,
+) -> Result<Json<CacheStats>> {
+    let infra_ids = get_infra_ids(db_pool, params.infra).await?;
+    let key_pattern = match params.infra {
+        Some(infra) => format!("{}.*", get_layer_cache_prefix("*", infra)),
+        None => "editoast.layer.*".to_string(),
+    };
+    let stats = compute_cache_stats(
+        &tile_cache,
+        &tile_cache_stats,
+        &map_layers,
+        &infra_ids,
+        &key_pattern,
+    )
+    .await?;
+    Ok(Json(stats))
+}
+
+/// Computes the cache statistics of the layer views of the given infras
+/// The cached tiles are listed with a single scan of the keys matching the pattern and grouped by view.
+/// The memory usage of a view is extrapolated from a sample of its tiles.
+async fn compute_cache_stats(
+    tile_cache: &TileCache,
+    tile_cache_stats: &TileCacheStats,
+    map_layers: &MapLayers,
+    infra_ids: &[i64],
+    key_pattern: &str,
+) -> Result<CacheStats> {
+    let mut view_keys: HashMap<String, Vec<String>> = HashMap::new();
+    for key in keys(tile_cache, key_pattern).await? {
+        if let Some((view_prefix, _)) = key.split_once(".tile/") {
+            view_keys
+                .entry(view_prefix.to_string())
+                .or_default()
+                .push(key);
+        }
+    }
+
+    let mut layer_names: Vec<_> = map_layers.layers.keys().collect();
+    layer_names.sort();
+    let mut stats = CacheStats::default();
+    for &infra in infra_ids {
+        for layer_name in &layer_names {
+            let mut view_names: Vec<_> = map_layers.layers[*layer_name].views.keys().collect();
+            view_names.sort();
+            for view_name in view_names {
+                let view_prefix = get_view_cache_prefix(layer_name, infra, view_name);
+                let keys = view_keys.remove(&view_prefix).unwrap_or_default();
+                let sample = &keys[..keys.len().min(MEMORY_USAGE_SAMPLE_SIZE)];
+                let sample_usage: u64 = memory_usage(tile_cache, sample).await?.iter().sum();
+                let view_stats = ViewCacheStats {
+                    layer: layer_name.to_string(),
+                    view: view_name.clone(),
+                    infra,
+                    keys: keys.len() as u64,
+                    memory_usage: match sample.len() {
+                        0 => 0,
+                        sample_size => sample_usage * keys.len() as u64 / sample_size as u64,
+                    },
+                    counters: tile_cache_stats.counters(&view_prefix),
+                };
+                if view_stats.keys == 0 && view_stats.counters == CacheCounters::default() {
+                    continue;
+                }
+                stats.keys += view_stats.keys;
+                stats.memory_usage += view_stats.memory_usage;
+                stats.counters.hits += view_stats.counters.hits;
+                stats.counters.misses += view_stats.counters.misses;
+                stats.views.push(view_stats);
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Selection of the cached tiles to purge, everything is purged if no field is given
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PurgeCacheForm {
+    infra: Option<i64>,
+    layer: Option<String>,
+    /// Layers without this view are left untouched
+    view: Option<String>,
+    /// Zone to purge in the coordinates of the purged views
+    bbox: Option<BoundingBox>,
+}
+
+/// Purges the cached tiles of a layer, a view, an infra or a bounding box
+/// Layers invalidated as a whole on infra updates are fully purged even if a bounding box is given
+#[post("/purge")]
+async fn purge_cache(
+    data: Json<PurgeCacheForm>,
+    map_layers: Data<MapLayers>,
+    map_layers_config: Data<MapLayersConfig>,
+    db_pool: Data<DbPool>,
+    tile_cache: Data<TileCache>,
+) -> Result<HttpResponse> {
+    let data = data.into_inner();
+    let layers = select_purged_layers(&map_layers, &data)?;
+    let infra_ids = get_infra_ids(db_pool, data.infra).await?;
+    purge_tiles(&tile_cache, &layers, &map_layers_config, &infra_ids, &data).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Returns the layers selected by the purge form, checking that the given layer and view exist
+fn select_purged_layers(map_layers: &MapLayers, data: &PurgeCacheForm) -> Result<MapLayers> {
+    let mut layers = map_layers.clone();
+    if let Some(layer_name) = &data.layer {
+        let layer = match layers.layers.remove(layer_name) {
+            Some(layer) => layer,
+            None => return Err(LayersError::new_layer_not_found(layer_name, map_layers).into()),
+        };
+        if let Some(view_name) = &data.view {
+            if !layer.views.contains_key(view_name) {
+                return Err(LayersError::new_view_not_found(view_name, &layer).into());
+            }
+        }
+        layers.layers = [(layer_name.clone(), layer)].into();
+    }
+    if let Some(view_name) = &data.view {
+        layers
+            .layers
+            .retain(|_, layer| layer.views.contains_key(view_name));
+    }
+    Ok(layers)
+}
+
+/// Deletes the cached tiles of the selected layers for the given infras
+async fn purge_tiles(
+    tile_cache: &TileCache,
+    layers: &MapLayers,
+    map_layers_config: &MapLayersConfig,
+    infra_ids: &[i64],
+    data: &PurgeCacheForm,
+) -> Result<()> {
+    for &infra in infra_ids {
+        match &data.bbox {
+            Some(bbox) => {
+                let view_bbox = |view_name: &str| {
+                    if data.view.as_ref().is_none_or(|view| view == view_name) {
+                        bbox.clone()
+                    } else {
+                        BoundingBox::default()
+                    }
+                };
+                let zone = InvalidationZone {
+                    geo: view_bbox("geo"),
+                    sch: view_bbox("sch"),
+                };
+                invalidate_zone(tile_cache, layers, infra, &zone, map_layers_config).await?;
+            }
+            None => {
+                for layer_name in layers.layers.keys() {
+                    invalidate_full_layer_cache(
+                        tile_cache,
+                        infra,
+                        layer_name,
+                        data.view.as_deref(),
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_cache_stats, purge_tiles, select_purged_layers, PurgeCacheForm};
+    use crate::client::MapLayersConfig;
+    use crate::error::InternalError;
+    use crate::map::{
+        get_cache_tile_key, get_view_cache_prefix, keys, set_tile, BoundingBox, CachedTile,
+        MapLayers, Tile, TileCache, TileCacheStats,
+    };
+    use crate::views::layers::LayersError;
+    use crate::views::tests::create_test_service;
+    use actix_web::test as actix_test;
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, read_body_json, TestRequest},
+    };
+    use serde_json::{json, to_value, Value as JsonValue};
+    use std::time::SystemTime;
+
+    const ROOT_TILE: Tile = Tile { x: 0, y: 0, z: 0 };
+    /// Tile at the antimeridian, outside of the purged bounding boxes
+    const FAR_TILE: Tile = Tile { x: 0, y: 0, z: 10 };
+
+    fn tile_key(layer: &str, infra: i64, view: &str, tile: &Tile) -> String {
+        get_cache_tile_key(&get_view_cache_prefix(layer, infra, view), tile)
+    }
+
+    /// Caches a few tiles of two layers, both views and two infras
+    async fn fill_cache() -> (TileCache, Vec<String>) {
+        let cache = TileCache::new_memory(100);
+        let keys = vec![
+            tile_key("track_sections", 1, "geo", &ROOT_TILE),
+            tile_key("track_sections", 1, "geo", &FAR_TILE),
+            tile_key("track_sections", 1, "sch", &ROOT_TILE),
+            tile_key("signals", 1, "geo", &ROOT_TILE),
+            tile_key("track_sections", 2, "geo", &ROOT_TILE),
+        ];
+        let tile = CachedTile {
+            etag: "etag".into(),
+            last_modified: SystemTime::UNIX_EPOCH,
+            mvt_bytes: vec![0; 10],
+        };
+        for key in &keys {
+            set_tile(&cache, key, &tile).await.unwrap();
+        }
+        (cache, keys)
+    }
+
+    /// Purges the cache of the given infras and returns the remaining keys
+    async fn purge(cache: &TileCache, infra_ids: &[i64], form: JsonValue) -> Vec<String> {
+        let form: PurgeCacheForm = serde_json::from_value(form).unwrap();
+        let layers = select_purged_layers(&MapLayers::parse(), &form).unwrap();
+        purge_tiles(
+            cache,
+            &layers,
+            &MapLayersConfig::default(),
+            infra_ids,
+            &form,
+        )
+        .await
+        .unwrap();
+        let mut remaining = keys(cache, "*").await.unwrap();
+        remaining.sort();
+        remaining
+    }
+
+    fn sorted(keys: &[String], indexes: &[usize]) -> Vec<String> {
+        let mut keys: Vec<_> = indexes.iter().map(|&i| keys[i].clone()).collect();
+        keys.sort();
+        keys
+    }
+
+    #[actix_test]
+    async fn stats_count_cached_tiles() {
+        let (cache, keys) = fill_cache().await;
+        let stats_counters = TileCacheStats::default();
+        stats_counters.record(&get_view_cache_prefix("signals", 1, "sch"), false);
+        let map_layers = MapLayers::parse();
+
+        let stats = compute_cache_stats(
+            &cache,
+            &stats_counters,
+            &map_layers,
+            &[1, 2],
+            "editoast.layer.*",
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.keys, 5);
+        assert_eq!(stats.counters.misses, 1);
+        let views: Vec<_> = stats
+            .views
+            .iter()
+            .map(|view| {
+                (
+                    view.layer.as_str(),
+                    view.infra,
+                    view.view.as_str(),
+                    view.keys,
+                )
+            })
+            .collect();
+        assert_eq!(
+            views,
+            vec![
+                ("signals", 1, "geo", 1),
+                ("signals", 1, "sch", 0),
+                ("track_sections", 1, "geo", 2),
+                ("track_sections", 1, "sch", 1),
+                ("track_sections", 2, "geo", 1),
+            ]
+        );
+        let expected_usage: usize = keys.iter().map(|key| key.len() + 1 + 2 + 4 + 8 + 10).sum();
+        assert_eq!(stats.memory_usage, expected_usage as u64);
+
+        let stats = compute_cache_stats(
+            &cache,
+            &stats_counters,
+            &map_layers,
+            &[2],
+            "editoast.layer.*.infra_2.*",
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.keys, 1);
+        assert_eq!(stats.views.len(), 1);
+    }
+
+    #[actix_test]
+    async fn purge_by_layer() {
+        let (cache, keys) = fill_cache().await;
+        let remaining = purge(&cache, &[1, 2], json!({"layer": "track_sections"})).await;
+        assert_eq!(remaining, sorted(&keys, &[3]));
+    }
+
+    #[actix_test]
+    async fn purge_by_view() {
+        let (cache, keys) = fill_cache().await;
+        let remaining = purge(&cache, &[1, 2], json!({"view": "geo"})).await;
+        assert_eq!(remaining, sorted(&keys, &[2]));
+    }
+
+    #[actix_test]
+    async fn purge_by_infra() {
+        let (cache, keys) = fill_cache().await;
+        let remaining = purge(&cache, &[2], json!({"infra": 2})).await;
+        assert_eq!(remaining, sorted(&keys, &[0, 1, 2, 3]));
+    }
+
+    #[actix_test]
+    async fn purge_by_bbox() {
+        let (cache, keys) = fill_cache().await;
+        let bbox = BoundingBox((2., 48.), (3., 49.));
+        let remaining = purge(&cache, &[1, 2], json!({"view": "geo", "bbox": bbox})).await;
+        assert_eq!(remaining, sorted(&keys, &[1, 2]));
+    }
+
+    #[actix_test]
+    async fn purge_unknown_view() {
+        let app = create_test_service().await;
+        let req = TestRequest::post()
+            .uri("/layers/cache/purge")
+            .set_json(json!({"layer": "track_sections", "view": "does_not_exist"}))
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let map_layers = MapLayers::parse();
+        let error: InternalError =
+            LayersError::new_view_not_found("does_not_exist", &map_layers.layers["track_sections"])
+                .into();
+        let body: JsonValue = read_body_json(response).await;
+        assert_eq!(body, to_value(error).unwrap());
+    }
+}
@@ -1,24 +1,39 @@
-mod mvt_utils;
+mod cache;
+
+use std::collections::HashMap;
+use std::time::SystemTime;
 
 use crate::client::MapLayersConfig;
 use crate::error::Result;
-use crate::map::{get, get_cache_tile_key, get_view_cache_prefix, set, Layer, MapLayers, Tile};
+use crate::infra::Infra;
+use crate::map::{
+    get_cache_tile_key, get_tile, get_view_cache_prefix, render_diff_mvt_tile, render_mvt_tile,
+    set_tile, CachedTile, Layer, MapLayers, Tile, TileCache, TileCacheStats, TileFilters, View,
+};
 use crate::DbPool;
 use actix_web::dev::HttpServiceFactory;
-use actix_web::web::{block, scope, Data, Json, Path, Query};
-use actix_web::{get, HttpResponse};
-use diesel::sql_types::Integer;
-use diesel::{sql_query, RunQueryDsl};
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ETag, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch,
+    LastModified,
+};
+use actix_web::middleware::Compress;
+use actix_web::web::{block, scope, Data, Header, Json, Path, Query};
+use actix_web::{get, HttpResponse, HttpResponseBuilder};
 use editoast_derive::EditoastError;
-use mvt_utils::{create_and_fill_mvt_tile, get_geo_json_sql_query, GeoJsonAndData};
-use redis::Client;
+use log::warn;
 use serde::Deserialize;
-use serde_json::{json, Value as JsonValue};
+use serde_json::Value as JsonValue;
 use thiserror::Error;
 
 /// Returns `/layers` routes
 pub fn routes() -> impl HttpServiceFactory {
-    scope("/layers").service((layer_view, cache_and_get_mvt_tile))
+    scope("/layers").service((
+        layer_view,
+        // Registered first since its path also matches the layers tiles one
+        get_diff_mvt_tile,
+        cache_and_get_mvt_tile,
+        cache::routes(),
+    ))
 }
 
 #[derive(Debug, Error, EditoastError)]
@@ -84,26 +99,122 @@ async fn layer_view(
         root_url = map_layers_config.root_url
     );
 
-    Ok(Json(json!({
-        "type": "vector",
-        "name": layer_slug,
-        "promoteId": {layer_slug: layer.id_field},
-        "scheme": "xyz",
-        "tiles": [tiles_url_pattern],
-        "attribution": layer.attribution.clone().unwrap_or_default(),
-        "minzoom": 0,
-        "maxzoom": map_layers_config.max_zoom,
-    })))
+    Ok(Json(layer.tilejson(
+        &layer_slug,
+        vec![tiles_url_pattern],
+        0..=map_layers_config.max_zoom,
+    )))
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct DiffQueryParam {
+    infra: i64,
+    base: i64,
+}
+
+/// Gets a mvt tile of the objects added, removed or modified in an infra compared to a base infra
+/// Comparison tiles are not cached since both infras can be edited during a review
+#[get("/tile/diff/{view_slug}/{z}/{x}/{y}", wrap = "Compress::default()")]
+async fn get_diff_mvt_tile(
+    path: Path<(String, u64, u64, u64)>,
+    params: Query<DiffQueryParam>,
+    db_pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    let (view_slug, z, x, y) = path.into_inner();
+    let DiffQueryParam { infra, base } = params.into_inner();
+    let on_field = match view_slug.as_str() {
+        "geo" => "geographic",
+        "sch" => "schematic",
+        _ => {
+            return Err(LayersError::ViewNotFound {
+                view_name: view_slug,
+                expected_names: vec!["geo".into(), "sch".into()],
+            }
+            .into())
+        }
+    };
+
+    let mvt_bytes = block::<_, Result<_>>(move || {
+        let mut conn = db_pool.get().expect("Failed to get DB connection");
+        Infra::retrieve(&mut conn, infra)?;
+        Infra::retrieve(&mut conn, base)?;
+        render_diff_mvt_tile(&mut conn, on_field, infra, base, &Tile { x, y, z })
+    })
+    .await
+    .unwrap()?;
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .content_type("application/x-protobuf")
+        .body(mvt_bytes))
+}
+
+/// HTTP caching headers of a tile
+/// Editor tiles change with each edition, clients have to revalidate them with their entity tag or
+/// modification date. Shared caches (CDNs) may keep them for the view cache duration.
+struct TileCacheHeaders {
+    etag: EntityTag,
+    last_modified: SystemTime,
+    shared_max_age: u32,
 }
 
-/// Gets mvt tile from the cache if possible, otherwise gets data fro the data base and caches it in redis
-#[get("/tile/{layer_slug}/{view_slug}/{z}/{x}/{y}")]
+impl TileCacheHeaders {
+    fn new(etag: String, last_modified: SystemTime, view: &View) -> Self {
+        Self {
+            etag: EntityTag::new_weak(etag),
+            last_modified,
+            shared_max_age: view.cache_duration,
+        }
+    }
+
+    /// Whether the client copy of the tile is still valid
+    /// The modification date is only checked if no entity tag is given
+    fn matches(
+        &self,
+        if_none_match: Option<&IfNoneMatch>,
+        if_modified_since: Option<&IfModifiedSince>,
+    ) -> bool {
+        match (if_none_match, if_modified_since) {
+            (Some(IfNoneMatch::Any), _) => true,
+            (Some(IfNoneMatch::Items(etags)), _) => {
+                etags.iter().any(|etag| etag.weak_eq(&self.etag))
+            }
+            (None, Some(IfModifiedSince(date))) => SystemTime::from(*date) >= self.last_modified,
+            (None, None) => false,
+        }
+    }
+
+    fn apply<'a>(&self, response: &'a mut HttpResponseBuilder) -> &'a mut HttpResponseBuilder {
+        response
+            .insert_header(ETag(self.etag.clone()))
+            .insert_header(LastModified(HttpDate::from(self.last_modified)))
+            .insert_header(CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(0),
+                CacheDirective::SMaxAge(self.shared_max_age),
+                CacheDirective::MustRevalidate,
+            ]))
+    }
+}
+
+/// Gets mvt tile from the cache if possible, otherwise gets data from the data base and caches it
+/// Tiles are rendered from the data base if the cache is unavailable
+/// The response is compressed if the client accepts it
+/// View filters can be given as query parameters with comma separated values
+#[get(
+    "/tile/{layer_slug}/{view_slug}/{z}/{x}/{y}",
+    wrap = "Compress::default()"
+)]
+#[allow(clippy::too_many_arguments)]
 async fn cache_and_get_mvt_tile(
     path: Path<(String, String, u64, u64, u64)>,
     params: Query<InfraQueryParam>,
+    filter_params: Query<HashMap<String, String>>,
+    if_none_match: Option<Header<IfNoneMatch>>,
+    if_modified_since: Option<Header<IfModifiedSince>>,
     map_layers: Data<MapLayers>,
     db_pool: Data<DbPool>,
-    redis_client: Data<Client>,
+    tile_cache: Data<TileCache>,
+    tile_cache_stats: Data<TileCacheStats>,
 ) -> Result<HttpResponse> {
     let (layer_slug, view_slug, z, x, y) = path.into_inner();
     let infra = params.infra;
@@ -115,44 +226,104 @@ async fn cache_and_get_mvt_tile(
         Some(view) => view,
         None => return Err(LayersError::new_view_not_found(view_slug, layer).into()),
     };
-    let cache_key = get_cache_tile_key(
-        &get_view_cache_prefix(&layer_slug, infra, &view_slug),
-        &Tile { x, y, z },
-    );
 
-    let mut redis_conn = redis_client.get_tokio_connection_manager().await.unwrap();
-    let cached_value = get::<Vec<u8>>(&mut redis_conn, &cache_key).await;
+    let filters: TileFilters = filter_params
+        .into_inner()
+        .into_iter()
+        .filter(|(name, _)| view.filters.contains_key(name))
+        .map(|(name, values)| {
+            let values = values
+                .split(',')
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+                .collect();
+            (name, values)
+        })
+        // Empty filters are ignored instead of filtering everything out
+        .filter(|(_, values): &(String, Vec<String>)| !values.is_empty())
+        .collect();
+    // Filtered tiles are not cached since zone invalidations only remove unfiltered tiles
+    let use_cache = filters.is_empty();
+
+    let view_cache_prefix = get_view_cache_prefix(&layer_slug, infra, &view_slug);
+    let cache_key = get_cache_tile_key(&view_cache_prefix, &Tile { x, y, z });
+
+    let cached_tile = if use_cache {
+        get_tile(&tile_cache, &cache_key)
+            .await
+            .unwrap_or_else(|err| {
+                warn!("Tile cache unavailable, rendering '{cache_key}' from the database: {err}");
+                None
+            })
+    } else {
+        None
+    };
 
-    if let Some(value) = cached_value {
-        return Ok(HttpResponse::Ok()
+    if use_cache {
+        tile_cache_stats.record(&view_cache_prefix, cached_tile.is_some());
+    }
+    let if_none_match = if_none_match.map(Header::into_inner);
+    let if_modified_since = if_modified_since.map(Header::into_inner);
+    // Cached tiles are stored with their caching headers, the infra is only retrieved to render tiles
+    if let Some(cached_tile) = cached_tile {
+        let cache_headers =
+            TileCacheHeaders::new(cached_tile.etag, cached_tile.last_modified, view);
+        if cache_headers.matches(if_none_match.as_ref(), if_modified_since.as_ref()) {
+            return Ok(cache_headers
+                .apply(&mut HttpResponse::NotModified())
+                .finish());
+        }
+        return Ok(cache_headers
+            .apply(&mut HttpResponse::Ok())
             .content_type("application/x-protobuf")
-            .body(value));
+            .body(cached_tile.mvt_bytes));
+    }
+
+    let infra_db_pool = db_pool.clone();
+    let infra_model = block::<_, Result<_>>(move || {
+        let mut conn = infra_db_pool.get().expect("Failed to get DB connection");
+        Infra::retrieve(&mut conn, infra)
+    })
+    .await
+    .unwrap()?;
+    let etag = view.tile_etag(infra, infra_model.generated_version.as_deref());
+    let last_modified = infra_model.modified_time();
+    let cache_headers = TileCacheHeaders::new(etag.clone(), last_modified, view);
+    if cache_headers.matches(if_none_match.as_ref(), if_modified_since.as_ref()) {
+        return Ok(cache_headers
+            .apply(&mut HttpResponse::NotModified())
+            .finish());
     }
 
-    let geo_json_query = get_geo_json_sql_query(&layer.table_name, view);
-    let records = block::<_, Result<_>>(move || {
+    let layer = layer.clone();
+    let view = view.clone();
+    let mvt_bytes = block::<_, Result<_>>(move || {
         let mut conn = db_pool.get().expect("Fail to get DB connection");
-        match sql_query(geo_json_query)
-            .bind::<Integer, _>(z as i32)
-            .bind::<Integer, _>(x as i32)
-            .bind::<Integer, _>(y as i32)
-            .bind::<Integer, _>(infra as i32)
-            .get_results::<GeoJsonAndData>(&mut conn)
-        {
-            Ok(results) => Ok(results),
-            Err(err) => Err(err.into()),
-        }
+        render_mvt_tile(
+            &mut conn,
+            &layer_slug,
+            &layer,
+            &view,
+            infra,
+            &Tile { x, y, z },
+            &filters,
+        )
     })
     .await
     .unwrap()?;
 
-    let mvt_bytes: Vec<u8> = create_and_fill_mvt_tile(z, x, y, layer_slug, records)
-        .to_bytes()
-        .unwrap();
-    set(&mut redis_conn, &cache_key, mvt_bytes.clone())
-        .await
-        .unwrap_or_else(|_| panic!("Fail to set value in redis with key {cache_key}"));
-    Ok(HttpResponse::Ok()
+    if use_cache {
+        let cached_tile = CachedTile {
+            etag,
+            last_modified,
+            mvt_bytes: mvt_bytes.clone(),
+        };
+        if let Err(err) = set_tile(&tile_cache, &cache_key, &cached_tile).await {
+            warn!("Failed to cache tile '{cache_key}': {err}");
+        }
+    }
+    Ok(cache_headers
+        .apply(&mut HttpResponse::Ok())
         .content_type("application/x-protobuf")
         .body(mvt_bytes))
 }
@@ -160,16 +331,27 @@ async fn cache_and_get_mvt_tile(
 #[cfg(test)]
 mod tests {
     use crate::error::InternalError;
+    use crate::infra::Infra;
     use crate::map::MapLayers;
+    use crate::schema::operation::{Operation, RailjsonObject};
+    use crate::schema::{LineString, TrackSection};
+    use crate::views::infra::tests::{
+        create_infra_request, create_object_request, delete_infra_request,
+    };
     use crate::views::tests::create_test_service;
+    use actix_http::Request;
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_web::http::header::{HttpDate, IfModifiedSince, IfNoneMatch};
     use actix_web::test as actix_test;
     use actix_web::{
         http::StatusCode,
-        test::{call_service, read_body_json, TestRequest},
+        test::{call_and_read_body_json, call_service, read_body, read_body_json, TestRequest},
     };
     use serde_json::{json, to_value, Value as JsonValue};
+    use std::collections::HashMap;
+    use std::time::{Duration, SystemTime};
 
-    use super::LayersError;
+    use super::{LayersError, TileCacheHeaders};
 
     /// Run a simple get query on `uri` and check the status code and json body
     async fn test_get_query(uri: &str, expected_status: StatusCode, expected_body: JsonValue) {
@@ -211,4 +393,246 @@ mod tests {
             }),
         ).await;
     }
+
+    #[actix_test]
+    async fn diff_tile_unknown_view() {
+        let error: InternalError = LayersError::ViewNotFound {
+            view_name: "does_not_exist".into(),
+            expected_names: vec!["geo".into(), "sch".into()],
+        }
+        .into();
+        test_get_query(
+            "/layers/tile/diff/does_not_exist/0/0/0?infra=2&base=1",
+            StatusCode::NOT_FOUND,
+            to_value(error).unwrap(),
+        )
+        .await;
+    }
+
+    /// Value of a protobuf field, only length delimited values are kept
+    enum ProtoField<'a> {
+        Bytes(&'a [u8]),
+        Skipped,
+    }
+
+    fn read_varint(bytes: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[0];
+            *bytes = &bytes[1..];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads the fields of a protobuf message
+    fn read_proto_fields(mut bytes: &[u8]) -> Vec<(u64, ProtoField<'_>)> {
+        let mut fields = vec![];
+        while !bytes.is_empty() {
+            let key = read_varint(&mut bytes);
+            let field = match key & 0x7 {
+                0 => {
+                    read_varint(&mut bytes);
+                    ProtoField::Skipped
+                }
+                2 => {
+                    let len = read_varint(&mut bytes) as usize;
+                    let (value, rest) = bytes.split_at(len);
+                    bytes = rest;
+                    ProtoField::Bytes(value)
+                }
+                wire_type => {
+                    let len = if wire_type == 1 { 8 } else { 4 };
+                    bytes = &bytes[len..];
+                    ProtoField::Skipped
+                }
+            };
+            fields.push((key >> 3, field));
+        }
+        fields
+    }
+
+    /// Decodes the string properties of the features of a MVT tile
+    fn decode_mvt_features(tile: &[u8]) -> Vec<HashMap<String, String>> {
+        let mut features = vec![];
+        for (_, layer) in read_proto_fields(tile)
+            .into_iter()
+            .filter(|(field, _)| *field == 3)
+        {
+            let ProtoField::Bytes(layer) = layer else {
+                continue;
+            };
+            let (mut keys, mut values, mut layer_features) = (vec![], vec![], vec![]);
+            for (field, value) in read_proto_fields(layer) {
+                match (field, value) {
+                    (2, ProtoField::Bytes(feature)) => layer_features.push(feature),
+                    (3, ProtoField::Bytes(key)) => {
+                        keys.push(String::from_utf8(key.to_vec()).unwrap())
+                    }
+                    (4, ProtoField::Bytes(value)) => {
+                        let string_value =
+                            read_proto_fields(value)
+                                .into_iter()
+                                .find_map(|field| match field {
+                                    (1, ProtoField::Bytes(string)) => {
+                                        Some(String::from_utf8(string.to_vec()).unwrap())
+                                    }
+                                    _ => None,
+                                });
+                        values.push(string_value.unwrap_or_default());
+                    }
+                    _ => (),
+                }
+            }
+            for feature in layer_features {
+                let mut properties = HashMap::new();
+                for (field, value) in read_proto_fields(feature) {
+                    let (2, ProtoField::Bytes(mut tags)) = (field, value) else {
+                        continue;
+                    };
+                    while !tags.is_empty() {
+                        let key = read_varint(&mut tags) as usize;
+                        let value = read_varint(&mut tags) as usize;
+                        properties.insert(keys[key].clone(), values[value].clone());
+                    }
+                }
+                features.push(properties);
+            }
+        }
+        features
+    }
+
+    /// Gets the sorted ids and changes of the objects of a diff tile
+    async fn get_diff_changes(
+        app: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+        infra: i64,
+        base: i64,
+        tile: (u64, u64, u64),
+    ) -> Vec<(String, String)> {
+        let (z, x, y) = tile;
+        let req = TestRequest::get()
+            .uri(&format!(
+                "/layers/tile/diff/geo/{z}/{x}/{y}?infra={infra}&base={base}"
+            ))
+            .to_request();
+        let response = call_service(app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut changes: Vec<_> = decode_mvt_features(&read_body(response).await)
+            .into_iter()
+            .map(|mut properties| {
+                (
+                    properties.remove("id").unwrap(),
+                    properties.remove("change").unwrap(),
+                )
+            })
+            .collect();
+        changes.sort();
+        changes
+    }
+
+    #[actix_test]
+    async fn diff_tile_changes() {
+        let app = create_test_service().await;
+        let line = |lon: f64| -> LineString {
+            serde_json::from_value(json!({
+                "type": "LineString",
+                "coordinates": [[lon, 45.], [lon + 1., 45.]]
+            }))
+            .unwrap()
+        };
+        let track = |id: &str, lon: f64| -> RailjsonObject {
+            TrackSection {
+                id: id.into(),
+                geo: line(lon),
+                sch: line(lon),
+                ..Default::default()
+            }
+            .into()
+        };
+
+        // Tracks of the base infra are in the west tile (1/0/0)
+        let base: Infra = call_and_read_body_json(&app, create_infra_request("diff_base")).await;
+        for (id, lon) in [
+            ("diff_edited", -10.),
+            ("diff_deleted", -20.),
+            ("diff_kept", -30.),
+        ] {
+            let req = create_object_request(base.id, track(id, lon));
+            assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+        }
+        let req = TestRequest::post()
+            .uri(&format!("/infra/{}/clone/?name=diff_infra", base.id))
+            .to_request();
+        let infra: i64 = call_and_read_body_json(&app, req).await;
+
+        // The edited track moves to the east tile (1/1/0), where the created one is
+        let operations = json!([
+            {
+                "operation_type": "UPDATE",
+                "obj_id": "diff_edited",
+                "obj_type": "TrackSection",
+                "railjson_patch": [
+                    { "op": "replace", "path": "/geo", "value": line(10.) },
+                    { "op": "replace", "path": "/sch", "value": line(10.) }
+                ]
+            },
+            { "operation_type": "DELETE", "obj_id": "diff_deleted", "obj_type": "TrackSection" },
+            Operation::Create(Box::new(track("diff_created", 20.)))
+        ]);
+        let req = TestRequest::post()
+            .uri(&format!("/infra/{infra}/"))
+            .set_json(operations)
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        // Removed objects use the geometry of the base infra, the others the one of the compared infra
+        assert_eq!(
+            get_diff_changes(&app, infra, base.id, (1, 0, 0)).await,
+            vec![("diff_deleted".into(), "removed".into())]
+        );
+        assert_eq!(
+            get_diff_changes(&app, infra, base.id, (1, 1, 0)).await,
+            vec![
+                ("diff_created".into(), "added".into()),
+                ("diff_edited".into(), "modified".into())
+            ]
+        );
+
+        for infra in [infra, base.id] {
+            let response = call_service(&app, delete_infra_request(infra)).await;
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        }
+    }
+
+    #[test]
+    fn tile_cache_headers() {
+        let map_layers = MapLayers::parse();
+        let view = &map_layers.layers["track_sections"].views["geo"];
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let headers = TileCacheHeaders::new(view.tile_etag(1, Some("1")), modified, view);
+        assert!(headers.etag.weak);
+        assert_eq!(headers.shared_max_age, view.cache_duration);
+        let etags = IfNoneMatch::Items(vec![headers.etag.clone()]);
+        assert!(headers.matches(Some(&etags), None));
+        assert!(headers.matches(Some(&IfNoneMatch::Any), None));
+        assert!(!headers.matches(None, None));
+
+        // Regenerating the infra changes the entity tag
+        let new_headers = TileCacheHeaders::new(view.tile_etag(1, Some("2")), modified, view);
+        assert!(!new_headers.matches(Some(&etags), None));
+
+        // The modification date is only used without entity tag
+        let since = |secs| {
+            IfModifiedSince(HttpDate::from(
+                SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+            ))
+        };
+        assert!(headers.matches(None, Some(&since(1_000))));
+        assert!(!headers.matches(None, Some(&since(999))));
+        assert!(!new_headers.matches(Some(&etags), Some(&since(1_000))));
+    }
 }
@@ -5,12 +5,12 @@ pub mod pagination;
 pub mod params;
 pub mod search;
 
+use crate::map::TileCache;
 use crate::DbPool;
 use actix_web::dev::HttpServiceFactory;
 use actix_web::web::{block, Data, Json};
 use actix_web::{get, services};
 use diesel::{sql_query, RunQueryDsl};
-use redis::{cmd, Client};
 use serde_json::{json, Value as JsonValue};
 use std::env;
 
@@ -26,7 +26,7 @@ pub fn routes() -> impl HttpServiceFactory {
 }
 
 #[get("/health")]
-async fn health(db_pool: Data<DbPool>, redis_client: Data<Client>) -> &'static str {
+async fn health(db_pool: Data<DbPool>, tile_cache: Data<TileCache>) -> &'static str {
     block(move || {
         let mut conn = db_pool.get().expect("Failed to get DB connection");
         sql_query("SELECT 1").execute(&mut conn).unwrap();
@@ -34,8 +34,7 @@ async fn health(db_pool: Data<DbPool>, redis_client: Data<Client>) -> &'static s
     .await
     .unwrap();
 
-    let mut conn = redis_client.get_tokio_connection_manager().await.unwrap();
-    cmd("PING").query_async::<_, ()>(&mut conn).await.unwrap();
+    tile_cache.ping().await.unwrap();
     "ok"
 }
 
@@ -49,9 +48,12 @@ async fn version() -> Json<JsonValue> {
 mod tests {
     use std::collections::HashMap;
 
-    use crate::client::{MapLayersConfig, PostgresConfig, RedisConfig};
+    use crate::client::{
+        MapLayersConfig, PostgresConfig, RailjsonConfig, RedisConfig, TileCacheConfig,
+        ValidationConfig,
+    };
     use crate::infra_cache::InfraCache;
-    use crate::map::MapLayers;
+    use crate::map::{MapLayers, TileCache, TileCacheStats};
 
     use super::routes;
     use actix_http::body::BoxBody;
@@ -76,7 +78,8 @@ mod tests {
             .max_size(1)
             .build(manager)
             .expect("Failed to create pool.");
-        let redis = redis::Client::open(RedisConfig::default().redis_url).unwrap();
+        let tile_cache =
+            TileCache::new(&TileCacheConfig::default(), &RedisConfig::default()).unwrap();
 
         // Custom Json extractor configuration
         let json_cfg = JsonConfig::default()
@@ -87,10 +90,13 @@ mod tests {
             .wrap(NormalizePath::trim())
             .app_data(json_cfg)
             .app_data(Data::new(pool))
-            .app_data(Data::new(redis))
+            .app_data(Data::new(tile_cache))
+            .app_data(Data::new(TileCacheStats::default()))
             .app_data(Data::new(CHashMap::<i64, InfraCache>::default()))
             .app_data(Data::new(MapLayers::parse()))
             .app_data(Data::new(MapLayersConfig::default()))
+            .app_data(Data::new(RailjsonConfig::default()))
+            .app_data(Data::new(ValidationConfig::default()))
             .service(routes());
         init_service(app).await
     }
@@ -70,6 +70,7 @@ export interface TrackSectionEntity
           line_name?: string;
           track_name?: string;
           track_number?: number;
+          kilometre_points?: { offset: number; kilometre_point: string }[];
         };
       };
     }